@@ -1,88 +1,605 @@
+use bank_system::analytics::{Concentration, Report, TransferGraph};
+use bank_system::command::{self, Failure, Reply, Topic, amount, initial_balance, open_account};
+use bank_system::config::Config;
+use bank_system::errors::{BankError, FileContext};
+use bank_system::json;
+use bank_system::locale::{self, Lang};
+use bank_system::screening::{Outcome, ReviewQueue, Screener};
 use bank_system::storage::{Balance, Name, Storage};
+use bank_system::time::Timestamp;
+use bank_system::transaction::{self, Deposit, Transfer, TxKind, Withdraw};
+use std::collections::HashMap;
 use std::env;
+use std::fmt::Display;
+use std::fs;
+use std::process::ExitCode;
+use std::str::FromStr;
 
-fn main() {
-    // Загружаем текущее состояние банка из CSV-файла
-    // Здесь демонстрация использования BufRead в методе load_data()
-    // Файл читается построчно, и каждая строка преобразуется в (Name, Balance)
-    let mut storage = match Storage::load_data("balance.csv") {
-        Ok(val) => val,
+/// Команды: имя, аргументы и ключ описания в каталоге
+const COMMANDS: &[Topic] = &[
+    ("init", "[--seed <file>]", "help.init"),
+    ("create", "<name> [balance]", "help.create"),
+    ("remove", "<name>", "help.remove"),
+    ("deposit", "<name> <amount>", "help.deposit"),
+    ("withdraw", "<name> <amount>", "help.withdraw"),
+    ("transfer", "<from> <to> <amount>", "help.transfer"),
+    ("balance", "<name>", "help.balance"),
+    (
+        "list",
+        "[--sort name|balance] [--desc] [--min n] [--max n] [--prefix p] [--limit n]",
+        "help.list",
+    ),
+    ("history", "[name] [--limit n]", "help.history"),
+    ("report", "[n]", "help.report"),
+    ("concentration", "", "help.concentration"),
+    ("network", "[name] [other]", "help.network"),
+    ("import", "<accounts|operations> <file>", "help.import"),
+    ("export", "<accounts|operations> <file>", "help.export"),
+    ("config", "", "help.config"),
+    ("help", "[command]", "help.help"),
+];
+
+/// Формат вывода: текст для человека или один объект JSON на команду
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Format {
+    Text,
+    Json,
+}
+
+fn main() -> ExitCode {
+    let lang = Lang::from_env().unwrap_or(Lang::Ru);
+
+    // Общие параметры можно указать в любом месте:
+    // --format text|json, --config <file>, --profile <name>, --set key=value
+    let mut format = Format::Text;
+    let (mut file, mut profile, mut settings) = (None, None, Vec::new());
+    let mut args = Vec::new();
+    let mut all = env::args().skip(1);
+    while let Some(arg) = all.next() {
+        if !["--format", "--config", "--profile", "--set"].contains(&arg.as_str()) {
+            args.push(arg);
+            continue;
+        }
+        let value = all.next().unwrap_or_default();
+        match (arg.as_str(), value.as_str()) {
+            ("--format", "text") => format = Format::Text,
+            ("--format", "json") => format = Format::Json,
+            ("--config", path) if !path.is_empty() => file = Some(value),
+            ("--profile", name) if !name.is_empty() => profile = Some(value),
+            ("--set", setting) if setting.contains('=') => {
+                let (key, value) = setting.split_once('=').unwrap_or_default();
+                settings.push((key.to_owned(), value.to_owned()));
+            }
+            _ => {
+                eprintln!("{}", bad_value(lang, &arg, &value));
+                return ExitCode::from(2);
+            }
+        }
+    }
+    let command = args.first().map_or("", String::as_str);
+
+    let config = match Config::load(file.as_deref(), profile.as_deref(), &settings) {
+        Ok(config) => config,
         Err(e) => {
-            eprintln!("Невозможно загрузить данные из файла: {e}");
-            return;
+            let failure = Failure::Context("load.config_failed", e);
+            print_result(format, lang, command, Err(failure));
+            return ExitCode::from(2);
+        }
+    };
+    // Язык из настроек важнее языка окружения
+    let lang = config.lang.unwrap_or(lang);
+    print_result(format, lang, command, run(lang, &config, &args))
+}
+
+/// Печатает ответ команды в выбранном формате и возвращает код выхода
+fn print_result(
+    format: Format,
+    lang: Lang,
+    command: &str,
+    result: Result<Reply, Failure>,
+) -> ExitCode {
+    match (result, format) {
+        (Ok(reply), Format::Text) => {
+            for line in reply.lines {
+                println!("{line}");
+            }
+            ExitCode::SUCCESS
+        }
+        (Ok(reply), Format::Json) => {
+            println!("{}", json::success(command, reply.result));
+            ExitCode::SUCCESS
+        }
+        (Err(failure), Format::Text) => {
+            eprintln!("{}", failure.text(lang));
+            if let Some(BankError::DataNotFound(_)) = failure.error() {
+                eprintln!("{}", locale::text(lang, "cli.init_hint"));
+            }
+            failure.exit_code()
+        }
+        (Err(failure), Format::Json) => {
+            println!("{}", failure.to_json(command, lang));
+            failure.exit_code()
+        }
+    }
+}
+
+fn run(lang: Lang, config: &Config, args: &[String]) -> Result<Reply, Failure> {
+    let Some(name) = args.first() else {
+        return Err(usage(lang, None));
+    };
+    let Some(command) = lookup(name) else {
+        return Err(Failure::Invalid(
+            locale::text(lang, "repl.unknown_command").to_owned(),
+        ));
+    };
+    let args = &args[1..];
+
+    // Справка не трогает данные банка
+    if command == "help" {
+        let topic = match args {
+            [] => None,
+            [topic] => Some(lookup(topic).ok_or_else(|| {
+                Failure::Invalid(locale::text(lang, "repl.unknown_command").to_owned())
+            })?),
+            _ => return Err(usage(lang, Some(command))),
+        };
+        return Ok(help(lang, topic));
+    }
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        return Ok(help(lang, Some(command)));
+    }
+    if command == "config" {
+        if !args.is_empty() {
+            return Err(usage(lang, Some(command)));
         }
+        return Ok(Reply::new(config.into()).line(config));
+    }
+    if command == "init" {
+        let options = Options::parse(lang, args, &["--seed"], &[])?;
+        if !options.positional.is_empty() {
+            return Err(usage(lang, Some(command)));
+        }
+        let seed = options.values.get("--seed").copied();
+        let storage = config.init_storage(seed)?;
+        let path = config.data_file();
+        let args: [(&str, &dyn Display); 2] = [("path", &path), ("count", &storage.len())];
+        let result = json::object([
+            ("path", path.as_str().into()),
+            ("accounts", json::array(sorted_accounts(&storage))),
+        ]);
+        return Ok(Reply::new(result).line(locale::format(lang, "cli.initialized", &args)));
+    }
+
+    let mut storage = config.load_storage()?;
+    // Часы банка нужны для журнала операций и лимитов
+    storage.set_time(Timestamp::now());
+    // Операции клиентов проходят ту же проверку, что и в REPL
+    let screener = Screener::standard();
+    let mut queue = config.load_queue()?;
+
+    let reply = match (command, args) {
+        ("create", [name, rest @ ..]) if rest.len() <= 1 => {
+            let balance = match rest.first() {
+                Some(arg) => initial_balance(lang, arg)?,
+                None => Balance::new(0),
+            };
+            open_account(&mut storage, name, balance)?;
+            let args: [(&str, &dyn Display); 2] = [("name", name), ("balance", &balance)];
+            Reply::new((name, balance).into()).line(locale::format(lang, "cli.created", &args))
+        }
+        ("remove", [name]) => {
+            storage
+                .remove_user(name)
+                .ok_or_else(|| BankError::UserNotFound(name.clone()))?;
+            Reply::new(json::object([("account", name.into())])).line(locale::format(
+                lang,
+                "user.removed",
+                &[("name", name)],
+            ))
+        }
+        ("deposit", [name, arg]) => {
+            let amount = amount(lang, arg)?;
+            let tx: TxKind = Deposit::new(name, amount).into();
+            let queued = submit(lang, config, &screener, &mut storage, &mut queue, &tx)?;
+            if let Some(reply) = queued {
+                return Ok(reply);
+            }
+            let args: [(&str, &dyn Display); 2] = [("name", name), ("amount", &amount)];
+            Reply::new(json::applied(&tx, &storage)).line(locale::format(
+                lang,
+                "cli.deposited",
+                &args,
+            ))
+        }
+        ("withdraw", [name, arg]) => {
+            let amount = amount(lang, arg)?;
+            let tx: TxKind = Withdraw::new(name, amount).into();
+            let queued = submit(lang, config, &screener, &mut storage, &mut queue, &tx)?;
+            if let Some(reply) = queued {
+                return Ok(reply);
+            }
+            let args: [(&str, &dyn Display); 2] = [("name", name), ("amount", &amount)];
+            Reply::new(json::applied(&tx, &storage)).line(locale::format(
+                lang,
+                "cli.withdrawn",
+                &args,
+            ))
+        }
+        ("transfer", [from, to, arg]) => {
+            let amount = amount(lang, arg)?;
+            let tx: TxKind = Transfer::new(from, to, amount).into();
+            let queued = submit(lang, config, &screener, &mut storage, &mut queue, &tx)?;
+            if let Some(reply) = queued {
+                return Ok(reply);
+            }
+            let args: [(&str, &dyn Display); 3] = [("amount", &amount), ("from", from), ("to", to)];
+            Reply::new(json::applied(&tx, &storage)).line(locale::format(
+                lang,
+                "cli.transferred",
+                &args,
+            ))
+        }
+        ("balance", [name]) => {
+            let balance = storage
+                .get_balance(name)
+                .ok_or_else(|| BankError::UserNotFound(name.clone()))?;
+            let args: [(&str, &dyn Display); 3] = [
+                ("name", name),
+                ("balance", &balance),
+                ("currency", &config.currency),
+            ];
+            Reply::new((name, balance).into()).line(locale::format(lang, "cli.balance", &args))
+        }
+        ("list", args) => list(lang, config, &storage, args)?,
+        ("history", args) => {
+            let options = Options::parse(lang, args, &["--limit"], &[])?;
+            let name = match options.positional.as_slice() {
+                [] => None,
+                [name] => Some(name.to_string()),
+                _ => return Err(usage(lang, Some(command))),
+            };
+            let records: Vec<_> = storage
+                .history()
+                .records()
+                .iter()
+                .filter(|r| name.as_ref().is_none_or(|name| r.involves(name)))
+                .collect();
+            // Последние `limit` операций
+            let limit = options.value(lang, "--limit")?.unwrap_or(records.len());
+            let records = &records[records.len().saturating_sub(limit)..];
+            let mut reply = Reply::new(json::object([(
+                "records",
+                json::array(records.iter().copied()),
+            )]));
+            if records.is_empty() {
+                reply = reply.line(locale::text(lang, "cli.no_history"));
+            }
+            for record in records {
+                reply = reply.line(format!(
+                    "#{} {} {}",
+                    record.id(),
+                    record.time().date(),
+                    record.tx()
+                ));
+            }
+            reply
+        }
+        ("report", []) => report(lang, &storage, 5),
+        ("report", [n]) => report(lang, &storage, parse(lang, "n", n)?),
+        ("concentration", []) => {
+            let concentration = Concentration::new(&storage);
+            Reply::new((&concentration).into()).line(concentration.message(lang))
+        }
+        ("network", args) if args.len() <= 2 => network(lang, &storage, args),
+        ("import", [kind, file]) => {
+            let (count, queued) = match kind.as_str() {
+                "accounts" => (storage.import_accounts(file)?, 0),
+                "operations" => import_operations(&screener, &mut storage, &mut queue, file)?,
+                _ => return Err(usage(lang, Some(command))),
+            };
+            let mut reply = Reply::new(json::object([
+                ("count", count.into()),
+                ("queued", queued.into()),
+            ]))
+            .line(locale::format(lang, "cli.imported", &[("count", &count)]));
+            if queued > 0 {
+                config.save_queue(&queue)?;
+                reply = reply.line(locale::format(
+                    lang,
+                    "cli.import_queued",
+                    &[("count", &queued)],
+                ));
+            }
+            reply
+        }
+        ("export", [kind, file]) => {
+            let lines: Vec<String> = match kind.as_str() {
+                "accounts" => sorted_accounts(&storage)
+                    .iter()
+                    .map(|(name, balance)| format!("{name},{balance}\n"))
+                    .collect(),
+                "operations" => storage
+                    .history()
+                    .records()
+                    .iter()
+                    .map(|record| format!("{}\n", record.tx()))
+                    .collect(),
+                _ => return Err(usage(lang, Some(command))),
+            };
+            fs::write(file, lines.concat()).in_file(file)?;
+            Reply::new(json::object([
+                ("file", file.into()),
+                ("rows", lines.len().into()),
+            ]))
+            .line(locale::format(lang, "export.done", &[("file", file)]))
+        }
+        _ => return Err(usage(lang, Some(command))),
     };
+    // Одна запись на запуск, и только если команда что-то изменила
+    config.flush_storage(&mut storage)?;
+    Ok(reply)
+}
+
+/// Команда из таблицы по имени. `add` - старое имя `deposit`
+fn lookup(name: &str) -> Option<&'static str> {
+    let name = if name == "add" { "deposit" } else { name };
+    COMMANDS.iter().map(|(n, _, _)| *n).find(|n| *n == name)
+}
+
+/// Справка текстом: по одной команде или по всем, с общими параметрами
+fn help_lines(lang: Lang, command: Option<&str>) -> Vec<String> {
+    let mut lines = command::help_lines(lang, COMMANDS, command);
+    if command.is_none() {
+        lines.insert(0, locale::text(lang, "cli.usage").to_owned());
+        lines.push(locale::text(lang, "cli.options").to_owned());
+        lines.push(locale::text(lang, "cli.help_hint").to_owned());
+    }
+    lines
+}
+
+fn help(lang: Lang, command: Option<&str>) -> Reply {
+    let mut reply = command::help(lang, COMMANDS, command);
+    reply.lines = help_lines(lang, command);
+    reply
+}
+
+/// Неверное число аргументов: справка по команде или общая справка
+fn usage(lang: Lang, command: Option<&str>) -> Failure {
+    Failure::Usage(help_lines(lang, command).join("\n"))
+}
+
+/// Значение аргумента; ошибка с именем параметра, если он в неверном формате
+fn parse<T: FromStr>(lang: Lang, option: &str, value: &str) -> Result<T, Failure> {
+    value
+        .parse()
+        .map_err(|_| Failure::Invalid(bad_value(lang, option, value)))
+}
+
+/// Позиционные аргументы, параметры `--key value` и флаги `--key`
+struct Options<'a> {
+    positional: Vec<&'a str>,
+    values: HashMap<&'a str, &'a str>,
+    flags: Vec<&'a str>,
+}
+
+impl<'a> Options<'a> {
+    fn parse(
+        lang: Lang,
+        args: &'a [String],
+        valued: &[&str],
+        flags: &[&str],
+    ) -> Result<Self, Failure> {
+        let mut options = Options {
+            positional: Vec::new(),
+            values: HashMap::new(),
+            flags: Vec::new(),
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if valued.contains(&arg.as_str()) {
+                let Some(value) = args.next() else {
+                    return Err(Failure::Invalid(bad_value(lang, arg, "")));
+                };
+                options.values.insert(arg, value);
+            } else if flags.contains(&arg.as_str()) {
+                options.flags.push(arg);
+            } else if arg.starts_with("--") {
+                return Err(Failure::Invalid(locale::format(
+                    lang,
+                    "cli.bad_option",
+                    &[("option", arg)],
+                )));
+            } else {
+                options.positional.push(arg);
+            }
+        }
+        Ok(options)
+    }
+
+    fn value<T: FromStr>(&self, lang: Lang, option: &str) -> Result<Option<T>, Failure> {
+        self.values
+            .get(option)
+            .map(|value| parse(lang, option, value))
+            .transpose()
+    }
+
+    fn flag(&self, flag: &str) -> bool {
+        self.flags.contains(&flag)
+    }
+}
+
+fn sorted_accounts(storage: &Storage) -> Vec<(&Name, Balance)> {
+    let mut accounts: Vec<_> = storage.accounts().collect();
+    accounts.sort();
+    accounts
+}
+
+fn list(lang: Lang, config: &Config, storage: &Storage, args: &[String]) -> Result<Reply, Failure> {
+    let options = Options::parse(
+        lang,
+        args,
+        &["--sort", "--min", "--max", "--prefix", "--limit"],
+        &["--desc"],
+    )?;
+    if !options.positional.is_empty() {
+        return Err(usage(lang, Some("list")));
+    }
+    let min: Option<i64> = options.value(lang, "--min")?;
+    let max: Option<i64> = options.value(lang, "--max")?;
+    let prefix = options.values.get("--prefix").copied().unwrap_or("");
+
+    let mut accounts: Vec<_> = sorted_accounts(storage)
+        .into_iter()
+        .filter(|(name, balance)| {
+            name.starts_with(prefix)
+                && min.is_none_or(|min| balance.value() >= min)
+                && max.is_none_or(|max| balance.value() <= max)
+        })
+        .collect();
+    match options.values.get("--sort").copied() {
+        None | Some("name") => {}
+        // Сортировка устойчивая, так что равные балансы остаются по имени
+        Some("balance") => accounts.sort_by_key(|(_, balance)| *balance),
+        Some(other) => return Err(Failure::Invalid(bad_value(lang, "--sort", other))),
+    }
+    if options.flag("--desc") {
+        accounts.reverse();
+    }
+    let limit = options.value(lang, "--limit")?.unwrap_or(accounts.len());
+    accounts.truncate(limit);
+
+    let total = Balance::new(accounts.iter().map(|(_, b)| b.value()).sum());
+    let mut reply = Reply::new(json::object([
+        ("accounts", json::array(accounts.iter().copied())),
+        ("count", accounts.len().into()),
+        ("total", total.into()),
+    ]));
+    for (name, balance) in &accounts {
+        reply = reply.line(format!("{name},{balance}"));
+    }
+    let args: [(&str, &dyn Display); 3] = [
+        ("count", &accounts.len()),
+        ("total", &total),
+        ("currency", &config.currency),
+    ];
+    Ok(reply.line(locale::format(lang, "cli.accounts_total", &args)))
+}
+
+fn bad_value(lang: Lang, option: &str, value: &str) -> String {
+    let args: [(&str, &dyn Display); 2] = [("option", &option), ("value", &value)];
+    locale::format(lang, "cli.bad_value", &args)
+}
+
+fn report(lang: Lang, storage: &Storage, n: usize) -> Reply {
+    let report = Report::new(storage, n, &[10, 25, 75, 90, 99], &[0, 100, 1000, 10000]);
+    Reply::new((&report).into()).line(report.message(lang))
+}
+
+fn network(lang: Lang, storage: &Storage, args: &[String]) -> Reply {
+    let graph = TransferGraph::from_history(storage.history());
+    match args {
+        [] => {
+            let groups = graph.circular_groups();
+            let fan_out = graph.fan_out(5);
+            let fan_in = graph.fan_in(5);
+            let counts = |rows: &[(Name, usize)]| {
+                json::array(rows.iter().map(|(name, count)| {
+                    json::object([("account", name.into()), ("count", (*count).into())])
+                }))
+            };
+            let mut reply = Reply::new(json::object([
+                ("groups", json::array(groups.iter().map(json::array))),
+                ("fan_out", counts(&fan_out)),
+                ("fan_in", counts(&fan_in)),
+            ]))
+            .line(locale::text(lang, "network.groups"));
+            for group in &groups {
+                reply = reply.line(format!("  {}", group.join(" -> ")));
+            }
+            reply = reply.line(locale::text(lang, "network.fan_out"));
+            for (name, count) in &fan_out {
+                reply = reply.line(format!("  {name}: {count}"));
+            }
+            reply = reply.line(locale::text(lang, "network.fan_in"));
+            for (name, count) in &fan_in {
+                reply = reply.line(format!("  {name}: {count}"));
+            }
+            reply
+        }
+        [name] => {
+            let counterparties = graph.counterparties(name, 10);
+            let mut reply = Reply::new(json::object([
+                ("account", name.into()),
+                ("counterparties", json::array(&counterparties)),
+            ]))
+            .line(locale::format(
+                lang,
+                "network.counterparties",
+                &[("name", name)],
+            ));
+            for (other, amount) in &counterparties {
+                reply = reply.line(format!("  {other}: {amount}"));
+            }
+            reply
+        }
+        [a, b] => {
+            let net = graph.net_flow(a, b);
+            let args: [(&str, &dyn Display); 3] = [("from", a), ("to", b), ("net", &net)];
+            Reply::new(json::object([
+                ("from", a.into()),
+                ("to", b.into()),
+                ("net", net.into()),
+            ]))
+            .line(locale::format(lang, "network.net_flow", &args))
+        }
+        _ => unreachable!("network takes at most two accounts"),
+    }
+}
+
+/// Выполняет операции из файла все вместе или ни одной. Каждая проходит
+/// проверку, подозрительные ставятся в очередь. Возвращает (выполнено, в очереди)
+fn import_operations(
+    screener: &Screener,
+    storage: &mut Storage,
+    queue: &mut ReviewQueue,
+    file: &str,
+) -> Result<(usize, usize), Failure> {
+    let txs = transaction::load_txs(file)?;
+    let mut staged = queue.clone();
+    let counts = storage.atomically(|storage| {
+        let mut queued = 0;
+        for (i, tx) in txs.iter().enumerate() {
+            let outcome = screener
+                .submit(tx.clone(), storage, &mut staged)
+                .map_err(|e| Failure::Import(i + 1, e))?;
+            if let Outcome::Queued(..) = outcome {
+                queued += 1;
+            }
+        }
+        Ok::<_, Failure>((txs.len() - queued, queued))
+    })?;
+    *queue = staged;
+    Ok(counts)
+}
 
-    // Получаем аргументы командной строки
-    let args: Vec<String> = env::args().collect();
-
-    // Если аргументов недостаточно, показываем справку
-    if args.len() < 2 {
-        eprintln!("Использование:");
-        eprintln!("  add <name> <amount>");
-        eprintln!("  withdraw <name> <amount>");
-        eprintln!("  balance <name>");
-        return;
-    }
-
-    // Разбор команды
-    match args[1].as_str() {
-        "add" => {
-            // Проверяем, что указан пользователь и сумма
-            if args.len() != 4 {
-                eprintln!("Пример: add John 200");
-                return;
-            }
-            let name: Name = args[2].clone();
-            let amount = Balance::new(args[3].parse().expect("Сумма должна быть числом"));
-
-            // Пытаемся пополнить баланс
-            match storage.deposit(&name, amount) {
-                Ok(_) => {
-                    println!("Пополнено: {} на {}", name, amount);
-                    // После изменения баланса сохраняем новое состояние в CSV
-                    if let Err(e) = storage.save("balance.csv") {
-                        eprintln!("Невозможно сохранить данные в файл: {e}");
-                    }
-                }
-                Err(e) => println!("Ошибка: {}", e),
-            }
-        }
-        "withdraw" => {
-            if args.len() != 4 {
-                eprintln!("Пример: withdraw John 100");
-                return;
-            }
-            let name: Name = args[2].clone();
-            let amount = Balance::new(args[3].parse().expect("Сумма должна быть числом"));
-
-            // Пытаемся снять деньги
-            match storage.withdraw(&name, amount) {
-                Ok(_) => {
-                    println!("Снято: {} на {}", name, amount);
-                    // Сохраняем изменения
-                    if let Err(e) = storage.save("balance.csv") {
-                        eprintln!("Невозможно сохранить данные в файл: {e}");
-                    }
-                }
-                Err(e) => println!("Ошибка: {}", e),
-            }
-        }
-        "balance" => {
-            if args.len() != 3 {
-                eprintln!("Пример: balance John");
-                return;
-            }
-            let name: Name = args[2].clone();
-
-            // Показываем текущий баланс
-            match storage.get_balance(&name) {
-                Some(b) => println!("Баланс {}: {}", name, b),
-                None => println!("Пользователь {} не найден", name),
-            }
-        }
-        _ => {
-            eprintln!("Неизвестная команда");
+/// Проверяет операцию и выполняет её. Подозрительная операция не выполняется,
+/// а ставится в очередь на проверку; тогда возвращается ответ об этом
+fn submit(
+    lang: Lang,
+    config: &Config,
+    screener: &Screener,
+    storage: &mut Storage,
+    queue: &mut ReviewQueue,
+    tx: &TxKind,
+) -> Result<Option<Reply>, Failure> {
+    match screener.submit(tx.clone(), storage, queue)? {
+        Outcome::Applied => Ok(None),
+        Outcome::Queued(id, reason) => {
+            config.save_queue(queue)?;
+            let reason = reason.message(lang);
+            let args: [(&str, &dyn Display); 2] = [("id", &id), ("reason", &reason)];
+            let line = locale::format(lang, "review.queued", &args);
+            Ok(Some(Reply::new(json::queued(tx, id, &reason)).line(line)))
         }
     }
 }
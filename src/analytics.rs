@@ -1,15 +1,627 @@
+use super::chart::ChartOfAccounts;
+use super::history::History;
+use super::ledger::Ledger;
+use super::locale::{self, Lang};
 use super::storage::{Balance, Name, Storage};
+use super::transaction::TxKind;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap};
+use std::fmt::Display;
 
+/// Счёт с наибольшим балансом. При равенстве выигрывает первое по алфавиту имя.
+/// Один проход по счетам без копирования
 pub fn compute_most_profitable(storage: &Storage) -> Option<(Name, Balance)> {
-    storage
-        .get_all()
+    let mut best: Option<(&Name, Balance)> = None;
+    for (name, balance) in storage.accounts() {
+        let better = match best {
+            None => true,
+            Some((best_name, best_balance)) => {
+                balance > best_balance || (balance == best_balance && name < best_name)
+            }
+        };
+        if better {
+            best = Some((name, balance));
+        }
+    }
+    best.map(|(name, balance)| (name.clone(), balance))
+}
+
+/// `n` счетов с наибольшими балансами, при равенстве - по имени.
+/// Один проход, в памяти держится не больше `n` счетов
+pub fn top_n(storage: &Storage, n: usize) -> Vec<(Name, Balance)> {
+    // На вершине кучи худший из отобранных
+    let mut heap: BinaryHeap<Reverse<(Balance, Reverse<&Name>)>> = BinaryHeap::new();
+    for (name, balance) in storage.accounts() {
+        heap.push(Reverse((balance, Reverse(name))));
+        if heap.len() > n {
+            heap.pop();
+        }
+    }
+    heap.into_sorted_vec()
+        .into_iter()
+        .map(|Reverse((balance, Reverse(name)))| (name.clone(), balance))
+        .collect()
+}
+
+/// `n` счетов с наименьшими балансами, при равенстве - по имени
+pub fn bottom_n(storage: &Storage, n: usize) -> Vec<(Name, Balance)> {
+    let mut heap: BinaryHeap<(Balance, &Name)> = BinaryHeap::new();
+    for (name, balance) in storage.accounts() {
+        heap.push((balance, name));
+        if heap.len() > n {
+            heap.pop();
+        }
+    }
+    heap.into_sorted_vec()
         .into_iter()
-        .max_by(|(_, lhs), (_, rhs)| lhs.value().cmp(&rhs.value()))
+        .map(|(balance, name)| (name.clone(), balance))
+        .collect()
+}
+
+/// Процентиль по методу ближайшего ранга. `sorted` отсортирован по возрастанию
+/// и не пуст
+fn percentile(sorted: &[Balance], p: u8) -> Balance {
+    let rank = (p as usize * sorted.len()).div_ceil(100).max(1);
+    sorted[rank.min(sorted.len()) - 1]
+}
+
+/// Сводная статистика по балансам счетов
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stats {
+    pub count: usize,
+    pub total: Balance,
+    pub mean: f64,
+    pub median: f64,
+    pub min: Balance,
+    pub max: Balance,
+    /// Пары (процент, значение)
+    pub percentiles: Vec<(u8, Balance)>,
+    pub zero: usize,
+    pub negative: usize,
+}
+
+/// Статистика по балансам с запрошенными процентилями. Для банка без счетов - None.
+/// Для медианы и процентилей копируются только балансы, без имён
+pub fn stats(storage: &Storage, percentiles: &[u8]) -> Option<Stats> {
+    let mut values: Vec<Balance> = Vec::with_capacity(storage.len());
+    let (mut zero, mut negative) = (0, 0);
+    for (_, balance) in storage.accounts() {
+        zero += (balance.value() == 0) as usize;
+        negative += (balance.value() < 0) as usize;
+        values.push(balance);
+    }
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_unstable();
+
+    let count = values.len();
+    let sum = storage.total_balance().value();
+    let median = if count % 2 == 1 {
+        values[count / 2].value() as f64
+    } else {
+        (values[count / 2 - 1].value() + values[count / 2].value()) as f64 / 2.0
+    };
+    Some(Stats {
+        count,
+        total: Balance::new(sum),
+        mean: sum as f64 / count as f64,
+        median,
+        min: values[0],
+        max: values[count - 1],
+        percentiles: percentiles
+            .iter()
+            .map(|&p| (p, percentile(&values, p.min(100))))
+            .collect(),
+        zero,
+        negative,
+    })
+}
+
+/// Корзина гистограммы: [from, to), открытая с краёв
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Bucket {
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    pub count: usize,
+}
+
+impl Bucket {
+    /// Границы корзины на выбранном языке
+    pub fn message(&self, lang: Lang) -> String {
+        match (self.from, self.to) {
+            (None, Some(to)) => format!("< {to}"),
+            (Some(from), Some(to)) => format!("{from}..{to}"),
+            (Some(from), None) => format!(">= {from}"),
+            (None, None) => locale::text(lang, "report.all").to_owned(),
+        }
+    }
+}
+
+impl Display for Bucket {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message(Lang::En))
+    }
+}
+
+/// Распределение балансов по корзинам. Границы `bounds` сортируются,
+/// n границ дают n + 1 корзину
+pub fn histogram(storage: &Storage, bounds: &[i64]) -> Vec<Bucket> {
+    let mut bounds = bounds.to_vec();
+    bounds.sort();
+    bounds.dedup();
+
+    let mut buckets: Vec<Bucket> = (0..=bounds.len())
+        .map(|i| Bucket {
+            from: i.checked_sub(1).map(|j| bounds[j]),
+            to: bounds.get(i).copied(),
+            count: 0,
+        })
+        .collect();
+    for (_, balance) in storage.accounts() {
+        let index = bounds.partition_point(|&b| b <= balance.value());
+        buckets[index].count += 1;
+    }
+    buckets
+}
+
+/// Отчёт по балансам: лидеры, отстающие, статистика и распределение
+#[derive(Clone, Debug, PartialEq)]
+pub struct Report {
+    pub top: Vec<(Name, Balance)>,
+    pub bottom: Vec<(Name, Balance)>,
+    pub stats: Option<Stats>,
+    pub histogram: Vec<Bucket>,
+}
+
+impl Report {
+    pub fn new(storage: &Storage, n: usize, percentiles: &[u8], bounds: &[i64]) -> Self {
+        Self {
+            top: top_n(storage, n),
+            bottom: bottom_n(storage, n),
+            stats: stats(storage, percentiles),
+            histogram: histogram(storage, bounds),
+        }
+    }
+}
+
+impl Report {
+    /// Текст отчёта на выбранном языке
+    pub fn message(&self, lang: Lang) -> String {
+        let Some(stats) = &self.stats else {
+            return locale::text(lang, "report.no_accounts").to_owned();
+        };
+        let line = |key: &str, value: &dyn Display| locale::format(lang, key, &[("value", value)]);
+        let mut lines = vec![
+            line("report.accounts", &stats.count),
+            line("report.total", &stats.total),
+            line("report.mean", &format!("{:.2}", stats.mean)),
+            line("report.median", &format!("{:.2}", stats.median)),
+            line("report.min", &stats.min),
+            line("report.max", &stats.max),
+        ];
+        for (p, value) in &stats.percentiles {
+            lines.push(format!("p{p}: {value}"));
+        }
+        lines.push(line("report.zero", &stats.zero));
+        lines.push(line("report.negative", &stats.negative));
+        lines.push(locale::text(lang, "report.top").to_owned());
+        for (name, balance) in &self.top {
+            lines.push(format!("  {name},{balance}"));
+        }
+        lines.push(locale::text(lang, "report.bottom").to_owned());
+        for (name, balance) in &self.bottom {
+            lines.push(format!("  {name},{balance}"));
+        }
+        lines.push(locale::text(lang, "report.histogram").to_owned());
+        for bucket in &self.histogram {
+            lines.push(format!("  {:<16} {}", bucket.message(lang), bucket.count));
+        }
+        lines.join("\n")
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message(Lang::En))
+    }
+}
+
+/// Сумма сальдо всех счетов главной книги, входящих в узел плана счетов `code`
+/// вместе с его потомками
+pub fn aggregate_by_node(ledger: &Ledger, chart: &ChartOfAccounts, code: &str) -> Balance {
+    let mut total = Balance::default();
+    for account in ledger.accounts() {
+        if let Some(node) = chart.node_of(account)
+            && chart.is_under(node.code(), code)
+        {
+            total += ledger.balance(account);
+        }
+    }
+    total
+}
+
+/// Сальдо по каждому узлу плана счетов: (код, название, глубина, сумма)
+pub fn rollup(ledger: &Ledger, chart: &ChartOfAccounts) -> Vec<(String, String, usize, Balance)> {
+    chart
+        .nodes()
+        .map(|node| {
+            (
+                node.code().to_owned(),
+                node.name().to_owned(),
+                chart.depth(node.code()),
+                aggregate_by_node(ledger, chart, node.code()),
+            )
+        })
+        .collect()
+}
+
+/// Точная дробь для показателей концентрации
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Ratio {
+    num: i128,
+    den: i128,
+}
+
+impl Ratio {
+    /// Сокращает дробь. Знаменатель 0 даёт ноль
+    pub fn new(num: i128, den: i128) -> Self {
+        if den == 0 {
+            return Self { num: 0, den: 1 };
+        }
+        let sign = if den < 0 { -1 } else { 1 };
+        let g = gcd(num.abs(), den.abs()).max(1);
+        Self {
+            num: sign * num / g,
+            den: sign * den / g,
+        }
+    }
+
+    pub fn num(&self) -> i128 {
+        self.num
+    }
+
+    pub fn den(&self) -> i128 {
+        self.den
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.num as f64 / self.den as f64
+    }
+}
+
+impl Display for Ratio {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.4}", self.to_f64())
+    }
+}
+
+fn gcd(mut a: i128, mut b: i128) -> i128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Балансы по возрастанию. Долги богатством не считаются, поэтому
+/// отрицательные балансы берутся как нулевые
+fn wealth(storage: &Storage) -> Vec<i128> {
+    let mut values: Vec<i128> = storage
+        .accounts()
+        .map(|(_, b)| b.value().max(0) as i128)
+        .collect();
+    values.sort_unstable();
+    values
+}
+
+/// Коэффициент Джини: 0 - все равны, ближе к 1 - всё у одного
+pub fn gini(storage: &Storage) -> Ratio {
+    let values = wealth(storage);
+    let n = values.len() as i128;
+    let total: i128 = values.iter().sum();
+    // G = (2 * sum(i * x_i) - (n + 1) * S) / (n * S), x по возрастанию, i с 1
+    let weighted: i128 = values
+        .iter()
+        .enumerate()
+        .map(|(i, x)| (i as i128 + 1) * x)
+        .sum();
+    Ratio::new(2 * weighted - (n + 1) * total, n * total)
+}
+
+/// Доля денег у `percent` процентов самых богатых счетов (не меньше одного счёта)
+pub fn top_share(storage: &Storage, percent: u32) -> Ratio {
+    let values = wealth(storage);
+    let total: i128 = values.iter().sum();
+    let k = (values.len() * percent.min(100) as usize)
+        .div_ceil(100)
+        .max(1);
+    let top: i128 = values.iter().rev().take(k).sum();
+    Ratio::new(top, total)
+}
+
+/// Точки кривой Лоренца: (доля счетов, доля денег) от (0, 0) до (1, 1)
+pub fn lorenz(storage: &Storage) -> Vec<(Ratio, Ratio)> {
+    let values = wealth(storage);
+    let n = values.len() as i128;
+    let total: i128 = values.iter().sum();
+    let mut points = vec![(Ratio::new(0, 1), Ratio::new(0, 1))];
+    let mut cumulative = 0;
+    for (i, x) in values.iter().enumerate() {
+        cumulative += x;
+        points.push((Ratio::new(i as i128 + 1, n), Ratio::new(cumulative, total)));
+    }
+    points
+}
+
+/// Индекс Херфиндаля: сумма квадратов долей, от 1/n до 1
+pub fn herfindahl(storage: &Storage) -> Ratio {
+    let values = wealth(storage);
+    let total: i128 = values.iter().sum();
+    Ratio::new(values.iter().map(|x| x * x).sum(), total * total)
+}
+
+/// Показатели концентрации для квартальной отчётности
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Concentration {
+    pub gini: Ratio,
+    pub top1: Ratio,
+    pub top10: Ratio,
+    pub herfindahl: Ratio,
+    pub lorenz: Vec<(Ratio, Ratio)>,
+}
+
+impl Concentration {
+    pub fn new(storage: &Storage) -> Self {
+        Self {
+            gini: gini(storage),
+            top1: top_share(storage, 1),
+            top10: top_share(storage, 10),
+            herfindahl: herfindahl(storage),
+            lorenz: lorenz(storage),
+        }
+    }
+}
+
+impl Concentration {
+    /// Текст показателей на выбранном языке
+    pub fn message(&self, lang: Lang) -> String {
+        let line = |key: &str, value: &Ratio| locale::format(lang, key, &[("value", value)]);
+        let mut lines = vec![
+            line("concentration.gini", &self.gini),
+            line("concentration.top1", &self.top1),
+            line("concentration.top10", &self.top10),
+            line("concentration.herfindahl", &self.herfindahl),
+            locale::text(lang, "concentration.lorenz").to_owned(),
+        ];
+        for (accounts, money) in &self.lorenz {
+            lines.push(format!("  {accounts},{money}"));
+        }
+        lines.join("\n")
+    }
+}
+
+impl Display for Concentration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message(Lang::En))
+    }
+}
+
+/// Направленный граф переводов: ребро от отправителя к получателю
+/// с общей суммой и числом переводов
+#[derive(Clone, Debug, Default)]
+pub struct TransferGraph {
+    edges: BTreeMap<Name, BTreeMap<Name, (Balance, usize)>>,
+}
+
+impl TransferGraph {
+    pub fn from_history(history: &History) -> Self {
+        let mut graph = TransferGraph::default();
+        for record in history.records() {
+            if let TxKind::Transfer(tx) = record.tx() {
+                let edge = graph
+                    .edges
+                    .entry(tx.from().clone())
+                    .or_default()
+                    .entry(tx.to().clone())
+                    .or_default();
+                edge.0 += tx.amount();
+                edge.1 += 1;
+            }
+        }
+        graph
+    }
+
+    /// Все счета, участвовавшие в переводах
+    pub fn nodes(&self) -> BTreeSet<&Name> {
+        self.edges
+            .iter()
+            .flat_map(|(from, targets)| std::iter::once(from).chain(targets.keys()))
+            .collect()
+    }
+
+    /// Сумма переводов от `from` к `to`
+    pub fn amount(&self, from: &Name, to: &Name) -> Balance {
+        self.edges
+            .get(from)
+            .and_then(|targets| targets.get(to))
+            .map(|(amount, _)| *amount)
+            .unwrap_or_default()
+    }
+
+    /// Чистый поток от `a` к `b`: отрицательный, если `b` перевёл больше
+    pub fn net_flow(&self, a: &Name, b: &Name) -> i64 {
+        self.amount(a, b).value() - self.amount(b, a).value()
+    }
+
+    /// `n` крупнейших контрагентов счёта по обороту в обе стороны,
+    /// при равенстве - по имени
+    pub fn counterparties(&self, account: &Name, n: usize) -> Vec<(Name, Balance)> {
+        let mut volume: BTreeMap<&Name, Balance> = BTreeMap::new();
+        if let Some(targets) = self.edges.get(account) {
+            for (to, (amount, _)) in targets {
+                *volume.entry(to).or_default() += *amount;
+            }
+        }
+        for (from, targets) in &self.edges {
+            if let Some((amount, _)) = targets.get(account) {
+                *volume.entry(from).or_default() += *amount;
+            }
+        }
+        let mut all: Vec<(Name, Balance)> = volume
+            .into_iter()
+            .filter(|(name, _)| *name != account)
+            .map(|(name, amount)| (name.clone(), amount))
+            .collect();
+        all.sort_by(|(lname, lhs), (rname, rhs)| rhs.cmp(lhs).then_with(|| lname.cmp(rname)));
+        all.truncate(n);
+        all
+    }
+
+    /// `n` счетов с наибольшим числом разных получателей
+    pub fn fan_out(&self, n: usize) -> Vec<(Name, usize)> {
+        let counts = self
+            .edges
+            .iter()
+            .map(|(from, targets)| (from.clone(), targets.len()))
+            .collect();
+        top_counts(counts, n)
+    }
+
+    /// `n` счетов с наибольшим числом разных отправителей
+    pub fn fan_in(&self, n: usize) -> Vec<(Name, usize)> {
+        let mut counts: BTreeMap<Name, usize> = BTreeMap::new();
+        for targets in self.edges.values() {
+            for to in targets.keys() {
+                *counts.entry(to.clone()).or_default() += 1;
+            }
+        }
+        top_counts(counts.into_iter().collect(), n)
+    }
+
+    /// Группы счетов, между которыми деньги ходят по кругу: компоненты
+    /// сильной связности (алгоритм Тарьяна) из двух и более счетов или
+    /// счёт с переводом самому себе. Счета в группах и группы упорядочены
+    pub fn circular_groups(&self) -> Vec<Vec<Name>> {
+        let nodes: Vec<&Name> = self.nodes().into_iter().collect();
+        let index_of: BTreeMap<&Name, usize> =
+            nodes.iter().enumerate().map(|(i, n)| (*n, i)).collect();
+        let adjacency: Vec<Vec<usize>> = nodes
+            .iter()
+            .map(|node| {
+                self.edges
+                    .get(*node)
+                    .map(|targets| targets.keys().map(|to| index_of[to]).collect())
+                    .unwrap_or_default()
+            })
+            .collect();
+
+        let mut tarjan = Tarjan::new(&adjacency);
+        for v in 0..nodes.len() {
+            if tarjan.index[v].is_none() {
+                tarjan.visit(v);
+            }
+        }
+
+        let mut groups: Vec<Vec<Name>> = tarjan
+            .components
+            .into_iter()
+            .filter(|c| c.len() > 1 || adjacency[c[0]].contains(&c[0]))
+            .map(|c| {
+                let mut group: Vec<Name> = c.into_iter().map(|v| nodes[v].clone()).collect();
+                group.sort();
+                group
+            })
+            .collect();
+        groups.sort();
+        groups
+    }
+}
+
+fn top_counts(mut counts: Vec<(Name, usize)>, n: usize) -> Vec<(Name, usize)> {
+    counts.sort_by(|(lname, lhs), (rname, rhs)| rhs.cmp(lhs).then_with(|| lname.cmp(rname)));
+    counts.truncate(n);
+    counts
+}
+
+/// Состояние обхода для поиска компонент сильной связности
+struct Tarjan<'a> {
+    adjacency: &'a [Vec<usize>],
+    index: Vec<Option<usize>>,
+    lowlink: Vec<usize>,
+    on_stack: Vec<bool>,
+    stack: Vec<usize>,
+    next_index: usize,
+    components: Vec<Vec<usize>>,
+}
+
+impl<'a> Tarjan<'a> {
+    fn new(adjacency: &'a [Vec<usize>]) -> Self {
+        let n = adjacency.len();
+        Self {
+            adjacency,
+            index: vec![None; n],
+            lowlink: vec![0; n],
+            on_stack: vec![false; n],
+            stack: Vec::new(),
+            next_index: 0,
+            components: Vec::new(),
+        }
+    }
+
+    /// Обход в глубину из `root` без рекурсии: цепочка переводов может быть
+    /// сколь угодно длинной, а стек вызовов - нет. В `calls` лежат вершины
+    /// текущего пути и номер следующего ребра каждой из них
+    fn visit(&mut self, root: usize) {
+        let mut calls = vec![(root, 0)];
+        self.enter(root);
+        while let Some(&mut (v, ref mut edge)) = calls.last_mut() {
+            if let Some(&w) = self.adjacency[v].get(*edge) {
+                *edge += 1;
+                match self.index[w] {
+                    None => {
+                        self.enter(w);
+                        calls.push((w, 0));
+                    }
+                    Some(index) if self.on_stack[w] => {
+                        self.lowlink[v] = self.lowlink[v].min(index);
+                    }
+                    Some(_) => {}
+                }
+                continue;
+            }
+
+            // Все рёбра `v` пройдены: возвращаемся к вызвавшей вершине
+            calls.pop();
+            if let Some(&(parent, _)) = calls.last() {
+                self.lowlink[parent] = self.lowlink[parent].min(self.lowlink[v]);
+            }
+            if Some(self.lowlink[v]) == self.index[v] {
+                let mut component = Vec::new();
+                while let Some(w) = self.stack.pop() {
+                    self.on_stack[w] = false;
+                    component.push(w);
+                    if w == v {
+                        break;
+                    }
+                }
+                self.components.push(component);
+            }
+        }
+    }
+
+    fn enter(&mut self, v: usize) {
+        self.index[v] = Some(self.next_index);
+        self.lowlink[v] = self.next_index;
+        self.next_index += 1;
+        self.stack.push(v);
+        self.on_stack[v] = true;
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::storage::bank;
 
     #[test]
     fn test_compute_most_profitable() {
@@ -27,4 +639,186 @@ mod tests {
         assert_eq!(res.0, "Bob");
         assert_eq!(res.1, Balance::new(200));
     }
+
+    #[test]
+    fn test_top_n_ties() {
+        let storage = bank(&[("Carol", 200), ("Bob", 200), ("Alice", 50), ("Dan", 0)]);
+        let top: Vec<Name> = top_n(&storage, 3).into_iter().map(|(n, _)| n).collect();
+        assert_eq!(top, vec!["Bob", "Carol", "Alice"]);
+        assert_eq!(compute_most_profitable(&storage).unwrap().0, "Bob");
+        let bottom: Vec<Name> = bottom_n(&storage, 2).into_iter().map(|(n, _)| n).collect();
+        assert_eq!(bottom, vec!["Dan", "Alice"]);
+    }
+
+    #[test]
+    fn test_stats_and_histogram() {
+        let storage = bank(&[("A", 0), ("B", 10), ("C", 20), ("D", 30), ("E", 1000)]);
+        let stats = stats(&storage, &[50, 90, 100]).unwrap();
+        assert_eq!(stats.total, Balance::new(1060));
+        assert_eq!(stats.mean, 212.0);
+        assert_eq!(stats.median, 20.0);
+        assert_eq!(
+            stats.percentiles,
+            vec![
+                (50, Balance::new(20)),
+                (90, Balance::new(1000)),
+                (100, Balance::new(1000))
+            ]
+        );
+        assert_eq!(stats.zero, 1);
+        assert_eq!(stats.negative, 0);
+
+        let counts: Vec<usize> = histogram(&storage, &[100, 10])
+            .iter()
+            .map(|b| b.count)
+            .collect();
+        assert_eq!(counts, vec![1, 3, 1]);
+
+        let report = Report::new(&storage, 2, &[50], &[10, 100]);
+        assert!(report.to_string().contains("10..100"));
+        assert!(Report::new(&Storage::new(), 2, &[50], &[]).stats.is_none());
+    }
+
+    #[test]
+    fn test_concentration() {
+        let equal = bank(&[("A", 10), ("B", 10), ("C", 10), ("D", 10)]);
+        assert_eq!(gini(&equal), Ratio::new(0, 1));
+        assert_eq!(herfindahl(&equal), Ratio::new(1, 4));
+
+        let storage = bank(&[("A", 0), ("B", 0), ("C", 0), ("D", 100)]);
+        assert_eq!(gini(&storage), Ratio::new(3, 4));
+        assert_eq!(herfindahl(&storage), Ratio::new(1, 1));
+        assert_eq!(top_share(&storage, 1), Ratio::new(1, 1));
+
+        let storage = bank(&[("A", 10), ("B", 20), ("C", 30), ("D", 40)]);
+        assert_eq!(gini(&storage), Ratio::new(1, 4));
+        assert_eq!(top_share(&storage, 10), Ratio::new(2, 5));
+        assert_eq!(herfindahl(&storage), Ratio::new(3, 10));
+        let lorenz = lorenz(&storage);
+        assert_eq!(lorenz.len(), 5);
+        assert_eq!(lorenz[2], (Ratio::new(1, 2), Ratio::new(3, 10)));
+        assert_eq!(lorenz[4], (Ratio::new(1, 1), Ratio::new(1, 1)));
+        assert!(
+            Concentration::new(&storage)
+                .to_string()
+                .contains("gini: 0.2500")
+        );
+    }
+
+    #[test]
+    fn test_transfer_graph() {
+        use crate::transaction::Transfer;
+
+        let mut history = History::new();
+        let transfers = [
+            ("A", "B", 100),
+            ("B", "C", 80),
+            ("C", "A", 60),
+            ("A", "D", 10),
+            ("B", "A", 30),
+            ("E", "D", 5),
+        ];
+        for (from, to, amount) in transfers {
+            history.push(
+                Default::default(),
+                Transfer::new(from, to, Balance::new(amount)).into(),
+            );
+        }
+        let graph = TransferGraph::from_history(&history);
+        let name = |s: &str| s.to_owned();
+
+        assert_eq!(graph.net_flow(&name("A"), &name("B")), 70);
+        assert_eq!(graph.net_flow(&name("B"), &name("A")), -70);
+        assert_eq!(
+            graph.counterparties(&name("A"), 2),
+            vec![
+                (name("B"), Balance::new(130)),
+                (name("C"), Balance::new(60))
+            ]
+        );
+        assert_eq!(graph.fan_out(1), vec![(name("A"), 2)]);
+        assert_eq!(graph.fan_in(1), vec![(name("A"), 2)]);
+        assert_eq!(
+            graph.circular_groups(),
+            vec![vec![name("A"), name("B"), name("C")]]
+        );
+    }
+
+    #[test]
+    fn test_long_chain_does_not_overflow() {
+        use crate::transaction::Transfer;
+
+        // Цепочка в десятки тысяч переводов, замкнутая в кольцо, и хвост без кольца
+        let n = 50_000;
+        let name = |i: usize| format!("A{i}");
+        let mut history = History::new();
+        for i in 0..n {
+            let to = if i + 1 == n { name(0) } else { name(i + 1) };
+            history.push(
+                Default::default(),
+                Transfer::new(&name(i), &to, Balance::new(1)).into(),
+            );
+        }
+        history.push(
+            Default::default(),
+            Transfer::new(&name(0), "Tail", Balance::new(1)).into(),
+        );
+
+        let groups = TransferGraph::from_history(&history).circular_groups();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].len(), n);
+        assert!(!groups[0].contains(&"Tail".to_string()));
+    }
+
+    #[test]
+    fn test_aggregate_by_node() {
+        use crate::chart::{self, AccountType};
+        use crate::ledger::LedgerAccount;
+
+        let mut storage = Storage::new();
+        for name in ["Alice", "Bob", "Acme"] {
+            storage.add_user(name.to_owned());
+            storage
+                .deposit(&name.to_owned(), Balance::new(100))
+                .unwrap();
+        }
+        storage
+            .charge_fee(&"Bob".to_owned(), Balance::new(7))
+            .unwrap();
+
+        let mut chart = ChartOfAccounts::standard();
+        chart
+            .add_node(
+                "2110",
+                "Corporate deposits",
+                AccountType::Liability,
+                Some(chart::CUSTOMER_DEPOSITS),
+            )
+            .unwrap();
+        chart
+            .assign(LedgerAccount::Customer("Acme".to_owned()), "2110")
+            .unwrap();
+
+        let ledger = storage.ledger();
+        let total = aggregate_by_node(ledger, &chart, chart::CUSTOMER_DEPOSITS);
+        assert_eq!(total, Balance::new(293));
+        assert_eq!(aggregate_by_node(ledger, &chart, "2110"), Balance::new(100));
+        assert_eq!(
+            aggregate_by_node(ledger, &chart, chart::INCOME),
+            Balance::new(7)
+        );
+        assert_eq!(
+            aggregate_by_node(ledger, &chart, chart::ASSETS),
+            Balance::new(300)
+        );
+
+        let report = rollup(ledger, &chart);
+        assert_eq!(report.len(), 10);
+        assert!(report.contains(&(
+            chart::LIABILITIES.to_owned(),
+            "Liabilities".to_owned(),
+            0,
+            Balance::new(293)
+        )));
+    }
 }
@@ -1,221 +1,1120 @@
-use bank_system::storage::{Balance, Name, Storage};
-use bank_system::transaction::{Transaction, Deposit, Withdraw, Transfer};
+use bank_system::analytics::{Concentration, Report, TransferGraph};
+use bank_system::command::{self, Failure, Reply, Topic, amount, initial_balance, open_account};
+use bank_system::config::Config;
+use bank_system::errors::BankError;
+use bank_system::invariants;
+use bank_system::json;
+use bank_system::locale::{self, Lang};
+use bank_system::reconcile::{self, ManualMatches, StatementEntry};
+use bank_system::repl::{self, Editor, LineHistory};
+use bank_system::screening::{Outcome, ReviewQueue, Screener};
+use bank_system::script::{OnError, Script};
+use bank_system::storage::{self, Name, Storage};
+use bank_system::time::{Date, Timestamp};
+use bank_system::timeseries::{self, Granularity};
+use bank_system::transaction::{self, Deposit, Transaction, Transfer, TxKind, Withdraw};
+use bank_system::tui::{Dashboard, TerminalBackend};
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Display;
 use std::io::{self, BufRead, Write};
+use std::process::ExitCode;
 
-fn main() {
-    let mut storage = match Storage::load_data("balance.csv") {
-        Ok(val) => val,
+/// Формат вывода: текст для человека или по одному объекту JSON на строку
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Format {
+    Text,
+    Json,
+}
+
+fn main() -> ExitCode {
+    let lang = Lang::from_env().unwrap_or(Lang::Ru);
+    let args: Vec<String> = env::args().skip(1).collect();
+    let Some(options) = Options::parse(&args) else {
+        eprintln!("{}", locale::text(lang, "repl.usage"));
+        return ExitCode::from(2);
+    };
+    let format = options.format;
+
+    let config = match Config::load(
+        options.config.as_deref(),
+        options.profile.as_deref(),
+        &options.settings,
+    ) {
+        Ok(config) => config,
         Err(e) => {
-            eprintln!("Невозможно загрузить данные {e}");
-            return;
+            let failure = Failure::Context("load.config_failed", e);
+            print_result(format, lang, "start", &Err(failure));
+            return ExitCode::from(2);
+        }
+    };
+    // Язык из настроек важнее языка окружения
+    let lang = config.lang.unwrap_or(lang);
+
+    let create = options.create.then_some(options.seed.as_deref());
+    let mut session = match Session::start(lang, config, create) {
+        Ok(val) => val,
+        Err(failure) => {
+            print_result(format, lang, "start", &Err(failure));
+            return ExitCode::FAILURE;
         }
     };
 
-    println!("=== Bank CLI Utils ===");
-    println!("Команды:");
-    println!("  add <name> <balance>      - добавить пользователя");
-    println!("  remove <name>             - удалить пользователя");
-    println!("  deposit <name> <amount>   - пополнить баланс");
-    println!("  withdraw <name> <amount>  - снять со счёта");
-    println!("  balance <name>            - показать баланс");
-    println!("  exit                      - выйти");
+    if let Some(file) = &options.script {
+        return run_script(&mut session, file, &options);
+    }
+    if options.tui {
+        return run_dashboard(&mut session);
+    }
+
+    if format == Format::Text {
+        println!("{}", locale::text(lang, "repl.title"));
+        println!("{}", locale::text(lang, "repl.commands"));
+        for line in command::help_lines(lang, HELP, None) {
+            println!("{line}");
+        }
+        println!("{}", locale::text(lang, "repl.hint"));
+    }
+
+    // В терминале - редактор строки с историей прошлых сеансов и дополнением по Tab
+    let history_file = session.config.path(HISTORY_FILE);
+    let mut editor = match format {
+        Format::Text => LineHistory::load(&history_file, HISTORY_LIMIT)
+            .inspect_err(|e| session.warn("repl.history_failed", e))
+            .ok()
+            .and_then(Editor::for_terminal),
+        Format::Json => None,
+    };
 
     let stdin = io::stdin();
     let mut stdout = io::stdout();
+    let mut status = ExitCode::SUCCESS;
 
     loop {
-        print!("> ");
-        stdout.flush().unwrap(); // показываем приглашение
-
-        let mut input = String::new();
-        if stdin.lock().read_line(&mut input).unwrap() == 0 {
-            break; // EOF
-        }
+        let input = match &mut editor {
+            Some(editor) => {
+                let complete = |line: &str| {
+                    repl::complete(
+                        line,
+                        &command_names(),
+                        session.storage.names().map(String::as_str),
+                    )
+                };
+                match editor.read_line("> ", complete) {
+                    Ok(Some(line)) => line,
+                    Ok(None) => break, // Ctrl-D
+                    Err(e) => {
+                        session.warn("repl.history_failed", &e.into());
+                        break;
+                    }
+                }
+            }
+            None => {
+                let mut input = String::new();
+                let read = match format {
+                    Format::Text => {
+                        print!("> ");
+                        stdout.flush() // показываем приглашение
+                    }
+                    Format::Json => Ok(()),
+                }
+                .and_then(|()| stdin.lock().read_line(&mut input));
+                match read {
+                    Ok(0) => break, // EOF
+                    Ok(_) => input,
+                    // В том числе ввод не в UTF-8
+                    Err(e) => {
+                        session.warn("repl.input_failed", &e.into());
+                        status = ExitCode::FAILURE;
+                        break;
+                    }
+                }
+            }
+        };
 
         let args: Vec<&str> = input.split_whitespace().collect();
         if args.is_empty() {
             continue;
         }
+        if args[0] == "exit" {
+            break;
+        }
+
+        let result = session.execute(&args);
+        print_result(format, lang, args[0], &result);
+    }
+
+    if let Some(editor) = &editor
+        && let Err(e) = editor.history().save(&history_file)
+    {
+        session.warn("repl.history_failed", &e);
+    }
+    session.flush();
+    if format == Format::Text {
+        println!("{}", locale::text(lang, "repl.bye"));
+    }
+    status
+}
+
+/// Параметры запуска
+struct Options {
+    format: Format,
+    /// Файл настроек и профиль в нём
+    config: Option<String>,
+    profile: Option<String>,
+    /// Настройки `ключ=значение`, перекрывающие файл и окружение
+    settings: Vec<(String, String)>,
+    /// Создать банк, если его ещё нет, со счетами из файла `seed`
+    create: bool,
+    seed: Option<String>,
+    /// Панель оператора вместо командной строки
+    tui: bool,
+    /// Файл сценария. Без него команды читаются с stdin
+    script: Option<String>,
+    on_error: OnError,
+    /// Начальные значения переменных сценария
+    vars: HashMap<String, String>,
+}
+
+impl Options {
+    /// Разбирает аргументы; `None`, если они неверны
+    fn parse(args: &[String]) -> Option<Self> {
+        let mut options = Options {
+            format: Format::Text,
+            config: None,
+            profile: None,
+            settings: Vec::new(),
+            create: false,
+            seed: None,
+            tui: false,
+            script: None,
+            on_error: OnError::default(),
+            vars: HashMap::new(),
+        };
+        let mut args = args.iter();
+        while let Some(option) = args.next() {
+            // Флаги без значения
+            if option == "--create" {
+                options.create = true;
+                continue;
+            }
+            if option == "--tui" {
+                options.tui = true;
+                continue;
+            }
+            let value = args.next()?;
+            match option.as_str() {
+                "--format" => {
+                    options.format = match value.as_str() {
+                        "text" => Format::Text,
+                        "json" => Format::Json,
+                        _ => return None,
+                    }
+                }
+                "--config" => options.config = Some(value.clone()),
+                "--profile" => options.profile = Some(value.clone()),
+                "--set" => {
+                    let (key, value) = value.split_once('=')?;
+                    options.settings.push((key.to_owned(), value.to_owned()));
+                }
+                "--seed" => options.seed = Some(value.clone()),
+                "--script" => options.script = Some(value.clone()),
+                "--on-error" => options.on_error = value.parse().ok()?,
+                "--var" => {
+                    let (name, value) = value.split_once('=')?;
+                    options.vars.insert(name.to_owned(), value.to_owned());
+                }
+                _ => return None,
+            }
+        }
+        // Режим ошибок и переменные имеют смысл только для сценария
+        if options.script.is_none()
+            && (options.on_error != OnError::default() || !options.vars.is_empty())
+        {
+            return None;
+        }
+        if options.seed.is_some() && !options.create {
+            return None;
+        }
+        // Панель рисует текст на терминале, сценарий она не выполняет
+        if options.tui && (options.script.is_some() || options.format == Format::Json) {
+            return None;
+        }
+        Some(options)
+    }
+}
+
+/// Печатает ответ команды в выбранном формате
+fn print_result(format: Format, lang: Lang, command: &str, result: &Result<Reply, Failure>) {
+    match (result, format) {
+        (Ok(reply), Format::Text) => {
+            for line in &reply.lines {
+                println!("{line}");
+            }
+        }
+        (Ok(reply), Format::Json) => println!("{}", json::success(command, reply.result.clone())),
+        (Err(failure), Format::Text) => {
+            println!("{}", failure.text(lang));
+            if let Some(BankError::DataNotFound(_)) = failure.error() {
+                println!("{}", locale::text(lang, "repl.init_hint"));
+            }
+        }
+        (Err(failure), Format::Json) => println!("{}", failure.to_json(command, lang)),
+    }
+}
+
+/// Панель оператора на весь терминал. Изменения пишутся по политике записи,
+/// остальное - при выходе
+fn run_dashboard(session: &mut Session) -> ExitCode {
+    let lang = session.lang;
+    let Some(mut backend) = TerminalBackend::new() else {
+        eprintln!("{}", locale::text(lang, "tui.unavailable"));
+        return ExitCode::from(2);
+    };
+    let config = &session.config;
+    let mut dashboard = Dashboard::new(
+        lang,
+        &config.currency,
+        &mut session.storage,
+        &session.screener,
+        &mut session.queue,
+    );
+    let result = dashboard.run(&mut backend, |storage, queue| {
+        config.save_storage(storage)?;
+        config.save_queue(queue)
+    });
+    // Терминал возвращается в обычный режим до вывода ошибки
+    drop(backend);
+    session.flush();
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            session.warn("repl.error", &e.into());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Выполняет сценарий и печатает итог.
+///
+/// В атомарном режиме данные, очередь, отчёты и выгрузки пишутся на диск только
+/// после успешного завершения всего сценария.
+fn run_script(session: &mut Session, file: &str, options: &Options) -> ExitCode {
+    let lang = session.lang;
+    let format = options.format;
+    let script = match Script::load(file, &options.vars) {
+        Ok(script) => script,
+        Err(e) => {
+            print_result(
+                format,
+                lang,
+                "script",
+                &Err(Failure::Context("script.load_failed", e)),
+            );
+            return ExitCode::FAILURE;
+        }
+    };
+
+    // В атомарном режиме ничего не пишется на диск до конца скрипта: хранилище
+    // откатывается по точке отката, очередь и файлы отчётов остаются в памяти
+    let atomic = options.on_error == OnError::Atomic;
+    let savepoint = atomic.then(|| session.storage.savepoint());
+    session.autosave = !atomic;
+    session.outputs = atomic.then(Vec::new);
+
+    let mut succeeded: usize = 0;
+    let mut failed = Vec::new();
+    let mut executed = 0;
+    for command in script.commands() {
+        let args: Vec<&str> = command.args.iter().map(String::as_str).collect();
+        executed += 1;
+        if args[0] == "exit" {
+            succeeded += 1;
+            break;
+        }
+        if format == Format::Text {
+            println!("> {command}");
+        }
+        let result = session.execute(&args);
+        print_result(format, lang, args[0], &result);
+        match result {
+            Ok(_) => succeeded += 1,
+            Err(failure) => {
+                failed.push((command, failure));
+                if options.on_error != OnError::Continue {
+                    break;
+                }
+            }
+        }
+    }
+    let total = script.commands().len();
+    let skipped = total - executed;
+
+    session.autosave = true;
+    let rolled_back = atomic && !failed.is_empty();
+    if let Some(savepoint) = savepoint {
+        if rolled_back {
+            session.storage.rollback(savepoint);
+            // На диске очередь в том виде, какой была до скрипта
+            match session.config.load_queue() {
+                Ok(queue) => session.queue = queue,
+                Err(e) => session.warn("load.queue_failed", &e),
+            }
+        } else {
+            session.storage.commit(savepoint);
+            session.save_queue();
+        }
+    }
+    // Отложенные отчёты и выгрузки пишутся, только если сценарий не откатился
+    for (file, data) in session.outputs.take().unwrap_or_default() {
+        if !rolled_back && let Err(e) = storage::write_file(&file, &data) {
+            session.warn("save.report_failed", &e);
+        }
+    }
+    // Всё, что политика записи ещё держит в памяти
+    session.flush();
+
+    match format {
+        Format::Text => {
+            let args: [(&str, &dyn Display); 5] = [
+                ("file", &file),
+                ("succeeded", &succeeded),
+                ("total", &total),
+                ("failed", &failed.len()),
+                ("skipped", &skipped),
+            ];
+            println!("{}", locale::format(lang, "script.summary", &args));
+            for (command, failure) in &failed {
+                let error = failure.text(lang);
+                let args: [(&str, &dyn Display); 3] = [
+                    ("line", &command.line),
+                    ("command", command),
+                    ("error", &error),
+                ];
+                println!("{}", locale::format(lang, "script.failed", &args));
+            }
+            if rolled_back {
+                println!("{}", locale::text(lang, "script.rolled_back"));
+            }
+        }
+        Format::Json => {
+            let failures = failed.iter().map(|(command, failure)| {
+                json::object([
+                    ("line", command.line.into()),
+                    ("command", command.to_string().into()),
+                    ("response", failure.to_json(&command.args[0], lang)),
+                ])
+            });
+            let summary = json::object([
+                ("file", file.into()),
+                ("on_error", options.on_error.to_string().into()),
+                ("total", total.into()),
+                ("succeeded", succeeded.into()),
+                ("failed", json::array(failures)),
+                ("skipped", skipped.into()),
+                ("rolled_back", rolled_back.into()),
+            ]);
+            println!(
+                "{}",
+                json::object([
+                    ("ok", failed.is_empty().into()),
+                    ("command", "script".into()),
+                    ("result", summary),
+                ])
+            );
+        }
+    }
+
+    match failed.first() {
+        None => ExitCode::SUCCESS,
+        Some((_, failure)) => failure.exit_code(),
+    }
+}
+
+/// Файл истории команд в каталоге данных и сколько команд в нём хранить
+const HISTORY_FILE: &str = ".utils_history";
+const HISTORY_LIMIT: usize = 500;
+
+/// Команды для справки: имя, аргументы и ключ описания в каталоге
+const HELP: &[Topic] = &[
+    ("add", "<name> <balance>", "help.add"),
+    ("remove", "<name>", "help.remove"),
+    ("deposit", "<name> <amount>", "help.deposit"),
+    ("withdraw", "<name> <amount>", "help.withdraw"),
+    ("transfer", "<from> <to> <amount>", "help.transfer"),
+    ("balance", "<name>", "help.balance"),
+    (
+        "+",
+        "deposit <name> <amount> transfer <from> <to> <amount>",
+        "help.combined",
+    ),
+    ("report", "[n]", "help.report"),
+    ("flows", "<day|week|month> <file> [name]", "help.flows"),
+    ("running", "<name> <file>", "help.running"),
+    ("adb", "<from> <to> <file>", "help.adb"),
+    ("concentration", "", "help.concentration"),
+    ("network", "[name] [other]", "help.network"),
+    ("trial", "", "help.trial"),
+    ("close", "<YYYY-MM-DD>", "help.close"),
+    ("review", "", "help.review"),
+    ("approve", "<id>", "help.approve"),
+    ("reject", "<id>", "help.reject"),
+    ("reconcile", "<file>", "help.reconcile"),
+    ("confirm", "<file> <n> <id>", "help.confirm"),
+    ("verify", "<snapshot> <txs>", "help.verify"),
+    ("config", "", "help.config"),
+    ("help", "[command]", "help.help"),
+    ("exit", "", "help.exit"),
+];
+
+/// Имена команд для дополнения по Tab
+fn command_names() -> Vec<&'static str> {
+    HELP.iter().map(|(name, _, _)| *name).collect()
+}
+
+fn help(lang: Lang, command: Option<&str>) -> Result<Reply, Failure> {
+    if command::help_topics(HELP, command).next().is_none() {
+        return Err(unknown_command(lang, command.unwrap_or_default()));
+    }
+    Ok(command::help(lang, HELP, command))
+}
+
+fn unknown_command(lang: Lang, command: &str) -> Failure {
+    Failure::Invalid(locale::format(
+        lang,
+        "repl.unknown_hint",
+        &[("command", &command)],
+    ))
+}
+
+/// Неверное число аргументов: пример правильного вызова
+fn usage(lang: Lang, example: &str) -> Failure {
+    Failure::Usage(locale::format(
+        lang,
+        "repl.example",
+        &[("example", &example)],
+    ))
+}
+
+fn date(lang: Lang, arg: &str) -> Result<Date, Failure> {
+    arg.parse()
+        .map_err(|_| Failure::Invalid(locale::text(lang, "input.bad_date").to_owned()))
+}
+
+/// Данные банка и очередь на проверку, с которыми работает REPL
+struct Session {
+    lang: Lang,
+    config: Config,
+    storage: Storage,
+    screener: Screener,
+    queue: ReviewQueue,
+    /// Сохранять ли изменения сразу после каждой команды
+    autosave: bool,
+    /// Отчёты и выгрузки, отложенные до конца атомарного сценария: путь и содержимое
+    outputs: Option<Vec<(String, String)>>,
+}
+
+impl Session {
+    /// `create` - создать банк, если его нет, со счетами из файла (если он указан)
+    fn start(lang: Lang, config: Config, create: Option<Option<&str>>) -> Result<Self, Failure> {
+        let storage = match create {
+            Some(fixture) if !config.exists() => config.init_storage(fixture),
+            _ => config.load_storage(),
+        };
+        let mut storage = storage.map_err(|e| Failure::Context("load.data_failed", e))?;
+        // Часы банка нужны для журнала операций и лимитов
+        storage.set_time(Timestamp::now());
+
+        // Подозрительные операции ждут решения оператора в очереди на проверку
+        let queue = config
+            .load_queue()
+            .map_err(|e| Failure::Context("load.queue_failed", e))?;
+        Ok(Self {
+            lang,
+            config,
+            storage,
+            screener: Screener::standard(),
+            queue,
+            autosave: true,
+            outputs: None,
+        })
+    }
+
+    /// Сохраняет данные, если пора по политике записи.
+    /// Команда уже выполнена, поэтому ошибка - только предупреждение
+    fn save(&mut self) {
+        if !self.autosave {
+            return;
+        }
+        if let Err(e) = self.config.save_storage(&mut self.storage) {
+            self.warn("save.data_failed", &e);
+        }
+    }
+
+    /// Записывает все накопленные изменения перед выходом
+    fn flush(&mut self) {
+        if let Err(e) = self.config.flush_storage(&mut self.storage) {
+            self.warn("save.data_failed", &e);
+        }
+    }
+
+    fn save_queue(&self) {
+        if !self.autosave {
+            return;
+        }
+        if let Err(e) = self.config.save_queue(&self.queue) {
+            self.warn("save.queue_failed", &e);
+        }
+    }
 
+    /// Записывает файл отчёта или выгрузки, а в атомарном сценарии откладывает запись
+    fn write(&mut self, file: &str, data: String) -> Result<(), BankError> {
+        match &mut self.outputs {
+            Some(outputs) => {
+                outputs.retain(|(staged, _)| staged != file);
+                outputs.push((file.to_owned(), data));
+                Ok(())
+            }
+            None => storage::write_file(file, &data),
+        }
+    }
+
+    /// Ручные подтверждения сверки, включая ещё не записанные
+    fn manual_matches(&self) -> Result<ManualMatches, BankError> {
+        let file = self.config.path("reconcile.csv");
+        match self
+            .outputs
+            .iter()
+            .flatten()
+            .find(|(staged, _)| *staged == file)
+        {
+            Some((_, data)) => ManualMatches::from_csv(data, &file),
+            None => ManualMatches::load(&file),
+        }
+    }
+
+    /// Записывает выгрузку в CSV-файл
+    fn export(&mut self, file: &str, csv: String, rows: usize) -> Result<Reply, Failure> {
+        self.write(file, csv)?;
+        let result = json::object([("file", file.into()), ("rows", rows.into())]);
+        let line = locale::format(self.lang, "export.done", &[("file", &file)]);
+        Ok(Reply::new(result).line(line))
+    }
+
+    fn warn(&self, key: &str, e: &BankError) {
+        let error = e.message(self.lang);
+        eprintln!("{}", locale::format(self.lang, key, &[("error", &error)]));
+    }
+
+    fn execute(&mut self, args: &[&str]) -> Result<Reply, Failure> {
+        let lang = self.lang;
         match args[0] {
             "add" => {
-                if args.len() != 3 {
-                    println!("Пример: add John 100");
-                    continue;
-                }
-                let name: Name = args[1].to_string();
-                let balance = match args[2].parse::<i64>() {
-                    Ok(b) => Balance::new(b),
-                    Err(_) => {
-                        println!("Сумма должна быть числом");
-                        continue;
-                    }
+                let [_, name, balance] = args else {
+                    return Err(usage(lang, "add John 100"));
                 };
-                if storage.add_user(name.clone()).is_some() {
-                    let _ = storage.deposit(&name, balance);
-                    println!("Пользователь {} добавлен с балансом {}", name, balance);
-                    if let Err(e) = storage.save("balance.csv") {
-                        eprintln!("Невозможно сохранить данные: {e}");
-                    }
-                } else {
-                    println!("Пользователь {} уже существует", name);
-                }
+                let name: Name = name.to_string();
+                let balance = initial_balance(lang, balance)?;
+                open_account(&mut self.storage, &name, balance)?;
+                self.save();
+                let args: [(&str, &dyn Display); 2] = [("name", &name), ("balance", &balance)];
+                Ok(Reply::new((&name, balance).into()).line(locale::format(
+                    lang,
+                    "user.added",
+                    &args,
+                )))
             }
             "remove" => {
-                if args.len() != 2 {
-                    println!("Пример: remove John");
-                    continue;
-                }
-                let name = args[1];
-                if storage.remove_user(&name.to_string()).is_some() {
-                    println!("Пользователь {} удалён", name);
-                    if let Err(e) = storage.save("balance.csv") {
-                        eprintln!("Невозможно сохрнить данные: {e}");
-                    }
-                } else {
-                    println!("Пользователь {} не найден", name);
+                let [_, name] = args else {
+                    return Err(usage(lang, "remove John"));
+                };
+                let name: Name = name.to_string();
+                if self.storage.remove_user(&name).is_none() {
+                    return Err(BankError::UserNotFound(name).into());
                 }
+                self.save();
+                Ok(
+                    Reply::new(json::object([("account", (&name).into())])).line(locale::format(
+                        lang,
+                        "user.removed",
+                        &[("name", &name)],
+                    )),
+                )
             }
             "deposit" => {
-                if args.len() != 3 {
-                    println!("Пример: deposit John 100");
-                    continue;
-                }
-                let name = args[1].to_string();
-                let amount: i64 = match args[2].parse() {
-                    Ok(a) => a,
-                    Err(_) => {
-                        println!("Сумма должна быть числом");
-                        continue;
-                    }
+                let [_, name, arg] = args else {
+                    return Err(usage(lang, "deposit John 100"));
                 };
-
-                let tx = Deposit::new(&name, Balance::new(amount));
-                // Применяем транзакцию 
-                match tx.apply(&mut storage) {
-                    Ok(_) => {
-                        println!("Транзакция: депозит {} на {}", name, amount);
-                        if let Err(e) = storage.save("balance.csv") {
-                            eprintln!("Невозможно сохрнить данные: {e}");
-                        }
-                    }
-                    Err(e) => println!("Ошибка транзакции: {:?}", e),
-                }
+                let amount = amount(lang, arg)?;
+                let done = locale::format(
+                    lang,
+                    "tx.deposit_done",
+                    &[("name", name), ("amount", &amount)],
+                );
+                self.submit(Deposit::new(name, amount).into(), done)
             }
             "transfer" => {
-                if args.len() != 4 {
-                    println!("Пример: tx_transfer Alice Bob 50");
-                    continue;
-                }
-                let from = args[1].to_string();
-                let to = args[2].to_string();
-                let amount: i64 = match args[3].parse() {
-                    Ok(a) => a,
-                    Err(_) => {
-                        println!("Сумма должна быть числом");
-                        continue;
-                    }
+                let [_, from, to, arg] = args else {
+                    return Err(usage(lang, "transfer Alice Bob 50"));
                 };
+                let amount = amount(lang, arg)?;
+                let done = locale::format(
+                    lang,
+                    "tx.transfer_done",
+                    &[("amount", &amount), ("from", from), ("to", to)],
+                );
+                self.submit(Transfer::new(from, to, amount).into(), done)
+            }
+            "withdraw" => {
+                let [_, name, arg] = args else {
+                    return Err(usage(lang, "withdraw John 100"));
+                };
+                let amount = amount(lang, arg)?;
+                let done = locale::format(
+                    lang,
+                    "tx.withdraw_done",
+                    &[("name", name), ("amount", &amount)],
+                );
+                self.submit(Withdraw::new(name, amount).into(), done)
+            }
+            "balance" => {
+                let [_, name] = args else {
+                    return Err(usage(lang, "balance John"));
+                };
+                let name: Name = name.to_string();
+                let balance = self
+                    .storage
+                    .get_balance(&name)
+                    .ok_or_else(|| BankError::UserNotFound(name.clone()))?;
+                let args: [(&str, &dyn Display); 3] = [
+                    ("name", &name),
+                    ("balance", &balance),
+                    ("currency", &self.config.currency),
+                ];
+                Ok(Reply::new((&name, balance).into()).line(locale::format(
+                    lang,
+                    "user.balance",
+                    &args,
+                )))
+            }
+            "+" => {
+                let [_, "deposit", name, first, "transfer", from, to, second] = args else {
+                    return Err(usage(lang, "+ deposit Alice 100 transfer Alice Bob 30"));
+                };
+                let deposit = Deposit::new(name, amount(lang, first)?);
+                let transfer = Transfer::new(from, to, amount(lang, second)?);
+                for tx in [TxKind::from(deposit.clone()), transfer.clone().into()] {
+                    self.screener.admit(&tx, &self.storage)?;
+                }
 
-                let tx = Transfer::new(&from, &to, Balance::new(amount));
-                // Применяем транзакцию 
-                match tx.apply(&mut storage) {
-                    Ok(_) => {
-                        println!("Транзакция: перевод средств {} от {} на {}", amount, from, to);
-                        if let Err(e) = storage.save("balance.csv") {
-                            eprintln!("Невозможно сохрнить данные: {e}");
-                        }
+                // Здесь мы используем оператор +
+                let combined_tx = deposit + transfer;
+                // Связка выполняется целиком: если перевод не прошёл, пополнения тоже нет
+                self.storage
+                    .atomically(|storage| combined_tx.apply(storage))
+                    .map_err(|e| Failure::Context("tx.failed", e))?;
+                self.save();
+                Ok(Reply::new(json::object([("status", "applied".into())]))
+                    .line(locale::text(lang, "tx.combined_done")))
+            }
+            "report" => {
+                let n = match args {
+                    [_] => 5,
+                    [_, n] => n.parse().map_err(|_| usage(lang, "report 10"))?,
+                    _ => return Err(usage(lang, "report 10")),
+                };
+                let report = Report::new(
+                    &self.storage,
+                    n,
+                    &[10, 25, 75, 90, 99],
+                    &[0, 100, 1000, 10000],
+                );
+                Ok(Reply::new((&report).into()).line(report.message(lang)))
+            }
+            "flows" => {
+                let (granularity, file, name) = match args {
+                    [_, granularity, file] => (granularity, file, None),
+                    [_, granularity, file, name] => (granularity, file, Some(name.to_string())),
+                    _ => return Err(usage(lang, "flows month flows.csv [Alice]")),
+                };
+                let granularity: Granularity = granularity.parse().map_err(|_| {
+                    Failure::Invalid(locale::text(lang, "input.bad_period").to_owned())
+                })?;
+                let history = self.storage.history();
+                let rows = match name {
+                    Some(name) => timeseries::flows(history, granularity, Some(&name)),
+                    None => {
+                        // Сначала банк в целом, затем каждый счёт
+                        let mut rows = timeseries::flows(history, granularity, None);
+                        rows.extend(timeseries::flows_by_account(history, granularity));
+                        rows
                     }
-                    Err(e) => println!("Ошибка транзакции: {:?}", e),
+                };
+                self.export(file, timeseries::flows_to_csv(&rows), rows.len())
+            }
+            "running" => {
+                let [_, name, file] = args else {
+                    return Err(usage(lang, "running Alice alice.csv"));
+                };
+                let rows = timeseries::running_balances(&self.storage, &name.to_string());
+                self.export(file, timeseries::running_balances_to_csv(&rows), rows.len())
+            }
+            "adb" => {
+                let [_, from, to, file] = args else {
+                    return Err(usage(lang, "adb 2024-05-01 2024-05-31 adb.csv"));
+                };
+                let (from, to) = (date(lang, from)?, date(lang, to)?);
+                let rows = timeseries::average_daily_balances(&self.storage, from, to);
+                self.export(file, timeseries::averages_to_csv(&rows), rows.len())
+            }
+            "concentration" => {
+                let concentration = Concentration::new(&self.storage);
+                Ok(Reply::new((&concentration).into()).line(concentration.message(lang)))
+            }
+            "network" => self.network(&args[1..]),
+            "trial" => {
+                let trial = self.storage.ledger().trial_balance();
+                let mismatches = self.storage.ledger_mismatches();
+                let result = json::object([
+                    ("trial", (&trial).into()),
+                    (
+                        "mismatches",
+                        json::array(mismatches.iter().map(|(name, balance, booked)| {
+                            json::object([
+                                ("account", name.into()),
+                                ("balance", (*balance).into()),
+                                ("booked", (*booked).into()),
+                            ])
+                        })),
+                    ),
+                ]);
+                let mut reply = Reply::new(result).line(trial.message(lang));
+                if !trial.is_balanced() {
+                    reply = reply.line(locale::text(lang, "trial.unbalanced"));
+                }
+                for (name, balance, booked) in &mismatches {
+                    let args: [(&str, &dyn Display); 3] =
+                        [("name", name), ("balance", balance), ("booked", booked)];
+                    reply = reply.line(locale::format(lang, "trial.mismatch", &args));
                 }
+                Ok(reply)
             }
-            "withdraw" => {
-                if args.len() != 3 {
-                    println!("Пример: withdraw John 100");
-                    continue;
-                }
-                let name = args[1].to_string();
-                let amount = match args[2].parse::<i64>() {
-                    Ok(a) => Balance::new(a),
-                    Err(_) => {
-                        println!("Сумма должна быть числом");
-                        continue;
-                    }
+            "close" => {
+                let [_, arg] = args else {
+                    return Err(usage(lang, "close 2024-05-20"));
                 };
-                let tx = Withdraw::new(&name, amount);
-                // Применяем транзакцию 
-                match tx.apply(&mut storage) {
-                    Ok(_) => {
-                        println!("Транзакция: депозит {} на {}", name, amount);
-                        if let Err(e) = storage.save("balance.csv") {
-                            eprintln!("Невозможно сохрнить данные: {e}");
-                        }
-                    }
-                    Err(e) => println!("Ошибка транзакции: {:?}", e),
+                let date = date(lang, arg)?;
+                let close = self.storage.close_day(date)?;
+                let report = self.config.path(&format!("close-{date}.txt"));
+                if let Err(e) = self.write(&report, format!("{}\n", close.message(lang))) {
+                    self.warn("save.report_failed", &e);
                 }
+                self.save();
+                Ok(Reply::new((&close).into()).line(close.message(lang)))
             }
-            "balance" => {
-                if args.len() != 2 {
-                    println!("Пример: balance John");
-                    continue;
+            "review" => {
+                let pending: Vec<_> = self.queue.pending().collect();
+                let mut reply = Reply::new(json::object([(
+                    "pending",
+                    json::array(pending.iter().copied()),
+                )]));
+                for item in &pending {
+                    reply = reply.line(item.message(lang));
                 }
-                let name = args[1].to_string();
-                let amount = if let Some(val) = storage.get_balance(&name) {
-                    val
+                if pending.is_empty() {
+                    reply = reply.line(locale::text(lang, "review.empty"));
+                }
+                Ok(reply)
+            }
+            "approve" | "reject" => {
+                let [command, arg] = args else {
+                    return Err(usage(lang, &format!("{} 1", args[0])));
+                };
+                let id: u64 = arg
+                    .parse()
+                    .map_err(|_| Failure::Invalid(locale::text(lang, "input.bad_id").to_owned()))?;
+                if *command == "approve" {
+                    self.queue.approve(id, &mut self.storage)?;
                 } else {
-                    println!("Пользователь: {} не найден", name);
-                    continue;
+                    self.queue.reject(id)?;
+                }
+                self.save();
+                self.save_queue();
+                let result = json::object([("id", id.into()), ("decision", (*command).into())]);
+                Ok(Reply::new(result).line(locale::format(
+                    lang,
+                    "review.processed",
+                    &[("id", &id)],
+                )))
+            }
+            "reconcile" => {
+                let [_, file] = args else {
+                    return Err(usage(lang, "reconcile statement.csv"));
                 };
-                println!("Баланс пользователя {} = {}", name, amount);
+                self.reconcile(file)
             }
-            "+" => {
-                if args.len() != 8 {
-                    println!(
-                        "Пример: + deposit Alice 100 transfer Alice Bob 30: cur {}",
-                        args.len()
-                    );
-                    continue;
-                }
-
-                let name = args[2].to_string();
-                let amount: i64 = match args[3].parse() {
-                    Ok(a) => a,
-                    Err(_) => {
-                        println!("Сумма должна быть числом");
-                        continue;
-                    }
+            "confirm" => {
+                let [_, file, n, id] = args else {
+                    return Err(usage(lang, "confirm statement.csv 2 15"));
                 };
-
-                let deposit = Deposit::new(&name, Balance::new(amount));
-
-                let from = args[5].to_string();
-                let to = args[6].to_string();
-                let amount: i64 = match args[7].parse() {
-                    Ok(a) => a,
-                    Err(_) => {
-                        println!("Сумма должна быть числом");
-                        continue;
+                let statement = reconcile::load_statement(file)
+                    .map_err(|e| Failure::Context("reconcile.statement_failed", e))?;
+                let (Ok(n), Ok(id)) = (n.parse::<usize>(), id.parse::<u64>()) else {
+                    return Err(Failure::Invalid(
+                        locale::text(lang, "reconcile.bad_numbers").to_owned(),
+                    ));
+                };
+                let Some(entry) = n.checked_sub(1).and_then(|i| statement.get(i)) else {
+                    return Err(Failure::Invalid(locale::format(
+                        lang,
+                        "reconcile.no_line",
+                        &[("n", &n)],
+                    )));
+                };
+                let mut manual = self
+                    .manual_matches()
+                    .map_err(|e| Failure::Context("reconcile.matches_failed", e))?;
+                manual.confirm(entry, id);
+                self.write(&self.config.path("reconcile.csv"), manual.to_csv())
+                    .map_err(|e| Failure::Context("reconcile.matches_save_failed", e))?;
+                let args: [(&str, &dyn Display); 2] = [("n", &n), ("id", &id)];
+                let result = json::object([("line", n.into()), ("record", id.into())]);
+                Ok(Reply::new(result).line(locale::format(lang, "reconcile.confirmed", &args)))
+            }
+            "verify" => {
+                let [_, snapshot, file] = args else {
+                    return Err(usage(lang, "verify snapshot.csv txs.csv"));
+                };
+                let start = Storage::load_data(snapshot)
+                    .map_err(|e| Failure::Context("verify.snapshot_failed", e))?;
+                let txs: Vec<Box<dyn Transaction>> = transaction::load_txs(file)
+                    .map_err(|e| Failure::Context("verify.txs_failed", e))?
+                    .into_iter()
+                    .map(|tx| Box::new(tx) as Box<dyn Transaction>)
+                    .collect();
+                let violation = invariants::verify(&start, &txs).err();
+                let result = json::object([
+                    ("operations", txs.len().into()),
+                    ("violation", violation.as_ref().into()),
+                ]);
+                let line = match &violation {
+                    None => locale::format(lang, "verify.ok", &[("count", &txs.len())]),
+                    Some(violation) => {
+                        let violation = violation.message(lang);
+                        locale::format(lang, "verify.violation", &[("violation", &violation)])
                     }
                 };
+                Ok(Reply::new(result).line(line))
+            }
+            "config" => Ok(Reply::new((&self.config).into()).line(&self.config)),
+            "help" => match args {
+                [_] => help(lang, None),
+                [_, command] => help(lang, Some(command)),
+                _ => Err(usage(lang, "help transfer")),
+            },
+            command => Err(unknown_command(lang, command)),
+        }
+    }
 
-                let transfer = Transfer::new(&from, &to, Balance::new(amount));
+    /// Проверяет операцию и выполняет её либо отправляет в очередь на проверку
+    fn submit(&mut self, tx: TxKind, done: String) -> Result<Reply, Failure> {
+        let outcome = self
+            .screener
+            .submit(tx.clone(), &mut self.storage, &mut self.queue)
+            .map_err(|e| Failure::Context("tx.failed", e))?;
+        match outcome {
+            Outcome::Applied => {
+                self.save();
+                Ok(Reply::new(json::applied(&tx, &self.storage)).line(done))
+            }
+            Outcome::Queued(id, reason) => {
+                self.save_queue();
+                let reason = reason.message(self.lang);
+                let args: [(&str, &dyn Display); 2] = [("id", &id), ("reason", &reason)];
+                let line = locale::format(self.lang, "review.queued", &args);
+                Ok(Reply::new(json::queued(&tx, id, &reason)).line(line))
+            }
+        }
+    }
 
-                // Здесь мы используем оператор +
-                let combined_tx = deposit + transfer;
+    fn network(&self, args: &[&str]) -> Result<Reply, Failure> {
+        let lang = self.lang;
+        let graph = TransferGraph::from_history(self.storage.history());
+        match args {
+            [] => {
+                let groups = graph.circular_groups();
+                let fan_out = graph.fan_out(5);
+                let fan_in = graph.fan_in(5);
+                let counts = |rows: &[(Name, usize)]| {
+                    json::array(rows.iter().map(|(name, count)| {
+                        json::object([("account", name.into()), ("count", (*count).into())])
+                    }))
+                };
+                let result = json::object([
+                    ("groups", json::array(groups.iter().map(json::array))),
+                    ("fan_out", counts(&fan_out)),
+                    ("fan_in", counts(&fan_in)),
+                ]);
 
-                match combined_tx.apply(&mut storage) {
-                    Ok(_) => println!("Транзакции выполнены!"),
-                    Err(e) => println!("Ошибка при выполнении: {:?}", e),
+                let mut reply = Reply::new(result).line(locale::text(lang, "network.groups"));
+                for group in &groups {
+                    reply = reply.line(format!("  {}", group.join(" -> ")));
                 }
-
-                if let Err(e) = storage.save("balance.csv") {
-                    eprintln!("Невозможно сохрнить данные: {e}");
+                reply = reply.line(locale::text(lang, "network.fan_out"));
+                for (name, count) in &fan_out {
+                    reply = reply.line(format!("  {name}: {count}"));
+                }
+                reply = reply.line(locale::text(lang, "network.fan_in"));
+                for (name, count) in &fan_in {
+                    reply = reply.line(format!("  {name}: {count}"));
+                }
+                Ok(reply)
+            }
+            [name] => {
+                let name = name.to_string();
+                let counterparties = graph.counterparties(&name, 10);
+                let result = json::object([
+                    ("account", (&name).into()),
+                    ("counterparties", json::array(&counterparties)),
+                ]);
+                let mut reply = Reply::new(result).line(locale::format(
+                    lang,
+                    "network.counterparties",
+                    &[("name", &name)],
+                ));
+                for (other, amount) in &counterparties {
+                    reply = reply.line(format!("  {other}: {amount}"));
                 }
+                Ok(reply)
             }
-            "exit" => break,
-            _ => println!("Неизвестная команда"),
+            [a, b] => {
+                let net = graph.net_flow(&a.to_string(), &b.to_string());
+                let args: [(&str, &dyn Display); 3] = [("from", a), ("to", b), ("net", &net)];
+                let result = json::object([
+                    ("from", (*a).into()),
+                    ("to", (*b).into()),
+                    ("net", net.into()),
+                ]);
+                Ok(Reply::new(result).line(locale::format(lang, "network.net_flow", &args)))
+            }
+            _ => Err(usage(lang, "network Alice Bob")),
         }
     }
 
-    println!("Выход из CLI, все изменения сохранены.");
+    fn reconcile(&self, file: &str) -> Result<Reply, Failure> {
+        let lang = self.lang;
+        let statement = reconcile::load_statement(file)
+            .map_err(|e| Failure::Context("reconcile.statement_failed", e))?;
+        let manual = self
+            .manual_matches()
+            .map_err(|e| Failure::Context("reconcile.matches_failed", e))?;
+        let res = reconcile::reconcile(&statement, self.storage.history(), &manual, 3);
+
+        // Номер строки выписки нужен для команды confirm
+        let line =
+            |entry: &StatementEntry| statement.iter().position(|e| e == entry).unwrap_or(0) + 1;
+        let result = json::object([
+            (
+                "matched",
+                json::array(res.matched.iter().map(|(entry, id)| {
+                    json::object([
+                        ("line", line(entry).into()),
+                        ("entry", entry.into()),
+                        ("record", (*id).into()),
+                    ])
+                })),
+            ),
+            ("unmatched_ours", json::array(&res.unmatched_ours)),
+            (
+                "unmatched_theirs",
+                json::array(res.unmatched_theirs.iter().map(|entry| {
+                    json::object([("line", line(entry).into()), ("entry", entry.into())])
+                })),
+            ),
+        ]);
+
+        let mut reply = Reply::new(result).line(locale::format(
+            lang,
+            "reconcile.matched",
+            &[("count", &res.matched.len())],
+        ));
+        for (entry, id) in &res.matched {
+            reply = reply.line(format!("  {entry} = #{id}"));
+        }
+        reply = reply.line(locale::format(
+            lang,
+            "reconcile.unmatched_ours",
+            &[("count", &res.unmatched_ours.len())],
+        ));
+        for record in &res.unmatched_ours {
+            reply = reply.line(format!("  #{} {}", record.id(), record.tx()));
+        }
+        reply = reply.line(locale::format(
+            lang,
+            "reconcile.unmatched_theirs",
+            &[("count", &res.unmatched_theirs.len())],
+        ));
+        for entry in &res.unmatched_theirs {
+            reply = reply.line(format!("  {}: {entry}", line(entry)));
+        }
+        Ok(reply)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn test_atomic_script_defers_files() {
+        let dir = env::temp_dir().join("bank_utils_atomic_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = |file: &str| dir.join(file).to_string_lossy().into_owned();
+        fs::write(path("statement.csv"), "2024-05-20,100,\n").unwrap();
+        let commands = format!(
+            "add alice 100\nclose 2024-05-20\nconfirm {} 1 1\nflows day {}\n",
+            path("statement.csv"),
+            path("flows.csv"),
+        );
+        let outputs = ["close-2024-05-20.txt", "reconcile.csv", "flows.csv"];
+
+        let settings = [("data_dir".to_owned(), path(""))];
+        let config = Config::resolve(None, None, |_| None, &settings).unwrap();
+        let Ok(mut session) = Session::start(Lang::En, config, Some(None)) else {
+            panic!("cannot start session");
+        };
+        let script = path("script.txt");
+        let args: Vec<String> = ["--script", &script, "--on-error", "atomic"]
+            .map(str::to_owned)
+            .into();
+        let options = Options::parse(&args).unwrap();
+
+        // Последняя команда не выполняется, и сценарий откатывается целиком
+        fs::write(
+            path("script.txt"),
+            format!("{commands}withdraw alice 1000\n"),
+        )
+        .unwrap();
+        assert_ne!(
+            run_script(&mut session, &script, &options),
+            ExitCode::SUCCESS
+        );
+        assert!(session.storage.get_balance(&"alice".to_string()).is_none());
+        for file in outputs {
+            assert!(!Path::new(&path(file)).exists(), "{file}");
+        }
+
+        fs::write(&script, commands).unwrap();
+        assert_eq!(
+            run_script(&mut session, &script, &options),
+            ExitCode::SUCCESS
+        );
+        for file in outputs {
+            assert!(Path::new(&path(file)).exists(), "{file}");
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,266 @@
+use super::errors::BankError;
+use super::ledger::LedgerAccount;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+
+/// Тип счёта в бухгалтерском учёте
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AccountType {
+    Asset,
+    Liability,
+    Equity,
+    Income,
+    Expense,
+}
+
+impl AccountType {
+    /// Растёт ли сальдо счёта по дебету (активы и расходы) или по кредиту
+    pub fn is_debit_normal(&self) -> bool {
+        matches!(self, AccountType::Asset | AccountType::Expense)
+    }
+}
+
+impl Display for AccountType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AccountType::Asset => write!(f, "asset"),
+            AccountType::Liability => write!(f, "liability"),
+            AccountType::Equity => write!(f, "equity"),
+            AccountType::Income => write!(f, "income"),
+            AccountType::Expense => write!(f, "expense"),
+        }
+    }
+}
+
+/// Узел плана счетов
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChartNode {
+    code: String,
+    name: String,
+    kind: AccountType,
+    parent: Option<String>,
+}
+
+impl ChartNode {
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn kind(&self) -> AccountType {
+        self.kind
+    }
+
+    pub fn parent(&self) -> Option<&str> {
+        self.parent.as_deref()
+    }
+}
+
+pub const ASSETS: &str = "1000";
+pub const CASH: &str = "1100";
+pub const LIABILITIES: &str = "2000";
+pub const CUSTOMER_DEPOSITS: &str = "2100";
+pub const EQUITY: &str = "3000";
+pub const INCOME: &str = "4000";
+pub const FEE_INCOME: &str = "4100";
+pub const EXPENSES: &str = "5000";
+pub const INTEREST_EXPENSE: &str = "5100";
+
+/// Иерархический план счетов. Счета главной книги привязываются к его узлам
+#[derive(Clone, Default, Debug)]
+pub struct ChartOfAccounts {
+    nodes: BTreeMap<String, ChartNode>,
+    /// Привязка отдельных счетов, перекрывает привязку по умолчанию
+    assignments: HashMap<LedgerAccount, String>,
+}
+
+impl ChartOfAccounts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Стандартный план: клиентские счета входят в "Customer deposits",
+    /// комиссии - в "Fee income"
+    pub fn standard() -> Self {
+        let mut chart = ChartOfAccounts::new();
+        let nodes = [
+            (ASSETS, "Assets", AccountType::Asset, None),
+            (CASH, "Cash", AccountType::Asset, Some(ASSETS)),
+            (LIABILITIES, "Liabilities", AccountType::Liability, None),
+            (
+                CUSTOMER_DEPOSITS,
+                "Customer deposits",
+                AccountType::Liability,
+                Some(LIABILITIES),
+            ),
+            (EQUITY, "Equity", AccountType::Equity, None),
+            (INCOME, "Income", AccountType::Income, None),
+            (FEE_INCOME, "Fee income", AccountType::Income, Some(INCOME)),
+            (EXPENSES, "Expenses", AccountType::Expense, None),
+            (
+                INTEREST_EXPENSE,
+                "Interest expense",
+                AccountType::Expense,
+                Some(EXPENSES),
+            ),
+        ];
+        for (code, name, kind, parent) in nodes {
+            // Узлы стандартного плана заведомо корректны
+            let _ = chart.add_node(code, name, kind, parent);
+        }
+        chart
+    }
+
+    /// Добавляет узел. Родитель должен существовать и иметь тот же тип
+    pub fn add_node(
+        &mut self,
+        code: &str,
+        name: &str,
+        kind: AccountType,
+        parent: Option<&str>,
+    ) -> Result<(), BankError> {
+        if self.nodes.contains_key(code) {
+            return Err(BankError::ChartNodeExists(code.to_owned()));
+        }
+        if let Some(parent) = parent {
+            match self.nodes.get(parent) {
+                Some(node) if node.kind == kind => {}
+                Some(_) => {
+                    return Err(BankError::ChartTypeMismatch {
+                        code: code.to_owned(),
+                        parent: parent.to_owned(),
+                    });
+                }
+                None => {
+                    return Err(BankError::ChartNodeNotFound(parent.to_owned()));
+                }
+            }
+        }
+        self.nodes.insert(
+            code.to_owned(),
+            ChartNode {
+                code: code.to_owned(),
+                name: name.to_owned(),
+                kind,
+                parent: parent.map(str::to_owned),
+            },
+        );
+        Ok(())
+    }
+
+    pub fn node(&self, code: &str) -> Option<&ChartNode> {
+        self.nodes.get(code)
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = &ChartNode> {
+        self.nodes.values()
+    }
+
+    /// Привязывает счёт главной книги к узлу, например отдельного клиента
+    /// к узлу "корпоративные вклады"
+    pub fn assign(&mut self, account: LedgerAccount, code: &str) -> Result<(), BankError> {
+        if !self.nodes.contains_key(code) {
+            return Err(BankError::ChartNodeNotFound(code.to_owned()));
+        }
+        self.assignments.insert(account, code.to_owned());
+        Ok(())
+    }
+
+    /// Узел, к которому относится счёт главной книги
+    pub fn node_of(&self, account: &LedgerAccount) -> Option<&ChartNode> {
+        let code = match self.assignments.get(account) {
+            Some(code) => code.as_str(),
+            None => match account {
+                LedgerAccount::Customer(_) => CUSTOMER_DEPOSITS,
+                LedgerAccount::Cash => CASH,
+                LedgerAccount::FeeIncome => FEE_INCOME,
+                LedgerAccount::InterestExpense => INTEREST_EXPENSE,
+            },
+        };
+        self.nodes.get(code)
+    }
+
+    /// Входит ли узел `code` в поддерево узла `ancestor` (включая сам узел)
+    pub fn is_under(&self, code: &str, ancestor: &str) -> bool {
+        let mut current = self.nodes.get(code);
+        while let Some(node) = current {
+            if node.code == ancestor {
+                return true;
+            }
+            current = node.parent.as_deref().and_then(|p| self.nodes.get(p));
+        }
+        false
+    }
+
+    /// Глубина узла: у корневых узлов 0
+    pub fn depth(&self, code: &str) -> usize {
+        let mut depth = 0;
+        let mut current = self.nodes.get(code).and_then(|n| n.parent.as_deref());
+        while let Some(parent) = current {
+            depth += 1;
+            current = self.nodes.get(parent).and_then(|n| n.parent.as_deref());
+        }
+        depth
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::locale::Lang;
+
+    #[test]
+    fn test_standard_chart() {
+        let chart = ChartOfAccounts::standard();
+        let alice = LedgerAccount::Customer("Alice".to_string());
+        assert_eq!(chart.node_of(&alice).unwrap().code(), CUSTOMER_DEPOSITS);
+        assert!(chart.is_under(CUSTOMER_DEPOSITS, LIABILITIES));
+        assert!(!chart.is_under(CUSTOMER_DEPOSITS, ASSETS));
+        assert_eq!(chart.depth(FEE_INCOME), 1);
+    }
+
+    #[test]
+    fn test_add_node_and_assign() {
+        let mut chart = ChartOfAccounts::standard();
+        let err = chart
+            .add_node(
+                "2110",
+                "Corporate",
+                AccountType::Asset,
+                Some(CUSTOMER_DEPOSITS),
+            )
+            .unwrap_err();
+        assert_eq!(err.name(), "chart_type_mismatch");
+        assert_eq!(
+            err.message(Lang::Ru),
+            format!("Узел плана счетов 2110 должен быть того же типа, что и {CUSTOMER_DEPOSITS}")
+        );
+        let err = chart
+            .add_node("2110", "Corporate", AccountType::Liability, Some("9999"))
+            .unwrap_err();
+        assert!(matches!(err, BankError::ChartNodeNotFound(ref code) if code == "9999"));
+        chart
+            .add_node(
+                "2110",
+                "Corporate",
+                AccountType::Liability,
+                Some(CUSTOMER_DEPOSITS),
+            )
+            .unwrap();
+
+        let acme = LedgerAccount::Customer("Acme".to_string());
+        chart.assign(acme.clone(), "2110").unwrap();
+        assert_eq!(chart.node_of(&acme).unwrap().code(), "2110");
+        assert!(chart.is_under("2110", LIABILITIES));
+        assert_eq!(chart.depth("2110"), 2);
+        let err = chart
+            .add_node("2110", "Corporate", AccountType::Liability, None)
+            .unwrap_err();
+        assert_eq!((err.code(), err.name()), (3003, "chart_node_exists"));
+        let err = chart.assign(acme, "9999").unwrap_err();
+        assert_eq!(err.to_string(), "Chart node 9999 not found");
+    }
+}
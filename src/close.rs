@@ -0,0 +1,126 @@
+use super::errors::{BankError, FileContext};
+use super::history::History;
+use super::locale::{self, Lang};
+use super::storage::{Balance, Name};
+use super::time::Date;
+use super::transaction::TxKind;
+use std::fmt::Display;
+use std::fs;
+use std::str::FromStr;
+
+/// Что делать с операцией, датированной закрытым днём
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum LockPolicy {
+    /// Отклонять операцию
+    #[default]
+    Reject,
+    /// Проводить её корректировкой в первом открытом дне
+    Adjust,
+}
+
+impl Display for LockPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LockPolicy::Reject => write!(f, "reject"),
+            LockPolicy::Adjust => write!(f, "adjust"),
+        }
+    }
+}
+
+impl FromStr for LockPolicy {
+    type Err = BankError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "reject" => Ok(LockPolicy::Reject),
+            "adjust" => Ok(LockPolicy::Adjust),
+            _ => Err(BankError::InvalidFormat(format!(
+                "unknown lock policy: {s}"
+            ))),
+        }
+    }
+}
+
+/// Обороты за день по видам операций
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DailyTotals {
+    pub deposits: Balance,
+    pub withdrawals: Balance,
+    pub transfers: Balance,
+    pub fees: Balance,
+    pub interest: Balance,
+    pub operations: usize,
+}
+
+impl DailyTotals {
+    /// Считает обороты по журналу за указанную дату
+    pub fn for_date(history: &History, date: Date) -> Self {
+        let mut totals = DailyTotals::default();
+        // between() берёт промежуток (from, to], а сутки - это [начало, начало следующих)
+        let from = date.start() - 1;
+        let to = date.add_days(1).start() - 1;
+        for record in history.between(from, to) {
+            let amount = record.tx().amount();
+            match record.tx() {
+                TxKind::Deposit(_) => totals.deposits += amount,
+                TxKind::Withdraw(_) => totals.withdrawals += amount,
+                TxKind::Transfer(_) => totals.transfers += amount,
+                TxKind::Fee(_) => totals.fees += amount,
+                TxKind::Interest(_) => totals.interest += amount,
+            }
+            totals.operations += 1;
+        }
+        totals
+    }
+}
+
+/// Итоги закрытого дня: снимок балансов и обороты
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DayClose {
+    pub date: Date,
+    pub balances: Vec<(Name, Balance)>,
+    pub totals: DailyTotals,
+}
+
+impl DayClose {
+    pub fn total_balance(&self) -> Balance {
+        let mut total = Balance::default();
+        for (_, balance) in &self.balances {
+            total += *balance;
+        }
+        total
+    }
+
+    /// Записывает отчёт о закрытии дня в файл на выбранном языке
+    pub fn save(&self, file: &str, lang: Lang) -> Result<(), BankError> {
+        fs::write(file, format!("{}\n", self.message(lang))).in_file(file)?;
+        Ok(())
+    }
+
+    /// Текст отчёта на выбранном языке
+    pub fn message(&self, lang: Lang) -> String {
+        let line = |key: &str, value: &dyn Display| locale::format(lang, key, &[("value", value)]);
+        let totals = &self.totals;
+        let mut lines = vec![
+            line("close.title", &self.date),
+            line("close.operations", &totals.operations),
+            line("close.deposits", &totals.deposits),
+            line("close.withdrawals", &totals.withdrawals),
+            line("close.transfers", &totals.transfers),
+            line("close.fees", &totals.fees),
+            line("close.interest", &totals.interest),
+            locale::text(lang, "close.balances").to_owned(),
+        ];
+        for (name, balance) in &self.balances {
+            lines.push(format!("{name},{balance}"));
+        }
+        lines.push(line("close.total", &self.total_balance()));
+        lines.join("\n")
+    }
+}
+
+impl Display for DayClose {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message(Lang::En))
+    }
+}
@@ -0,0 +1,219 @@
+use super::errors::BankError;
+use super::json::{self, Json};
+use super::locale::{self, Lang};
+use super::storage::{Balance, Name, Storage};
+use std::fmt::Display;
+use std::process::ExitCode;
+
+/// Команда для справки: имя, аргументы и ключ описания в каталоге
+pub type Topic = (&'static str, &'static str, &'static str);
+
+/// Ответ команды утилиты: строки для человека и результат для режима JSON
+pub struct Reply {
+    pub lines: Vec<String>,
+    pub result: Json,
+}
+
+impl Reply {
+    pub fn new(result: Json) -> Self {
+        Self {
+            lines: Vec::new(),
+            result,
+        }
+    }
+
+    pub fn line(mut self, line: impl Display) -> Self {
+        self.lines.push(line.to_string());
+        self
+    }
+}
+
+/// Почему команда утилиты не выполнена
+pub enum Failure {
+    /// Неверное число аргументов: справка или пример вызова, уже на нужном языке
+    Usage(String),
+    /// Аргумент в неверном формате, сообщение уже на нужном языке
+    Invalid(String),
+    Bank(BankError),
+    /// Ошибка с пояснением из каталога, в шаблоне которого есть `{error}`
+    Context(&'static str, BankError),
+    /// Операция импорта с номером (с единицы) не выполнилась
+    Import(usize, BankError),
+}
+
+impl From<BankError> for Failure {
+    fn from(value: BankError) -> Self {
+        Failure::Bank(value)
+    }
+}
+
+impl Failure {
+    /// Код выхода: 2 - неверные аргументы, 1 - операция не выполнена
+    pub fn exit_code(&self) -> ExitCode {
+        match self {
+            Failure::Usage(_)
+            | Failure::Invalid(_)
+            | Failure::Bank(BankError::InvalidArgument(_)) => ExitCode::from(2),
+            Failure::Bank(_) | Failure::Context(..) | Failure::Import(..) => ExitCode::FAILURE,
+        }
+    }
+
+    /// Ошибка банка, из-за которой команда не выполнена
+    pub fn error(&self) -> Option<&BankError> {
+        match self {
+            Failure::Usage(_) | Failure::Invalid(_) => None,
+            Failure::Bank(e) | Failure::Context(_, e) | Failure::Import(_, e) => Some(e),
+        }
+    }
+
+    pub fn text(&self, lang: Lang) -> String {
+        match self {
+            Failure::Usage(message) | Failure::Invalid(message) => message.clone(),
+            Failure::Bank(e) => {
+                let error = e.message(lang);
+                locale::format(lang, "repl.error", &[("error", &error)])
+            }
+            Failure::Context(key, e) => {
+                let error = e.message(lang);
+                locale::format(lang, key, &[("error", &error)])
+            }
+            Failure::Import(n, e) => {
+                let error = e.message(lang);
+                let args: [(&str, &dyn Display); 2] = [("n", n), ("error", &error)];
+                locale::format(lang, "cli.import_failed", &args)
+            }
+        }
+    }
+
+    pub fn to_json(&self, command: &str, lang: Lang) -> Json {
+        match self {
+            Failure::Bank(e) => json::failure(command, e, lang),
+            Failure::Usage(_) | Failure::Invalid(_) => {
+                json::failure(command, &BankError::InvalidArgument(self.text(lang)), lang)
+            }
+            // Код и имя - от исходной ошибки, сообщение - с пояснением
+            Failure::Context(_, e) | Failure::Import(_, e) => {
+                json::failure_with(command, e, self.text(lang))
+            }
+        }
+    }
+}
+
+/// Команды из таблицы `table` для справки: одна или все
+pub fn help_topics<'a>(
+    table: &'static [Topic],
+    command: Option<&'a str>,
+) -> impl Iterator<Item = &'static Topic> + use<'a> {
+    table
+        .iter()
+        .filter(move |(name, _, _)| command.is_none_or(|command| *name == command))
+}
+
+/// Использование команды: имя и аргументы
+pub fn usage_line((name, args, _): &Topic) -> String {
+    format!("{name} {args}").trim_end().to_owned()
+}
+
+/// Справка текстом: по одной команде или по всем
+pub fn help_lines(lang: Lang, table: &'static [Topic], command: Option<&str>) -> Vec<String> {
+    help_topics(table, command)
+        .map(|topic| {
+            let usage = usage_line(topic);
+            format!("  {usage:<30} - {}", locale::text(lang, topic.2))
+        })
+        .collect()
+}
+
+/// Справка для ответа: строки и JSON `{"commands":[...]}`
+pub fn help(lang: Lang, table: &'static [Topic], command: Option<&str>) -> Reply {
+    let usages: Vec<(&str, String, &str)> = help_topics(table, command)
+        .map(|topic| (topic.0, usage_line(topic), locale::text(lang, topic.2)))
+        .collect();
+    let topics = usages
+        .iter()
+        .map(|(name, usage, description)| (*name, usage.as_str(), *description));
+    let mut reply = Reply::new(json::help(topics));
+    for line in help_lines(lang, table, command) {
+        reply = reply.line(line);
+    }
+    reply
+}
+
+/// Сумма операции: только положительное число
+pub fn amount(lang: Lang, arg: &str) -> Result<Balance, Failure> {
+    let amount = number(lang, arg)?;
+    if amount <= 0 {
+        return Err(Failure::Invalid(
+            locale::text(lang, "input.amount_not_positive").to_owned(),
+        ));
+    }
+    Ok(Balance::new(amount))
+}
+
+/// Начальный баланс счёта: ноль или положительное число
+pub fn initial_balance(lang: Lang, arg: &str) -> Result<Balance, Failure> {
+    let balance = number(lang, arg)?;
+    if balance < 0 {
+        return Err(Failure::Invalid(
+            locale::text(lang, "input.balance_negative").to_owned(),
+        ));
+    }
+    Ok(Balance::new(balance))
+}
+
+fn number(lang: Lang, arg: &str) -> Result<i64, Failure> {
+    arg.parse()
+        .map_err(|_| Failure::Invalid(locale::text(lang, "input.amount_nan").to_owned()))
+}
+
+/// Открывает счёт с начальным балансом. Если баланс не зачислился,
+/// счёт тоже не открывается
+pub fn open_account(storage: &mut Storage, name: &Name, balance: Balance) -> Result<(), BankError> {
+    storage.atomically(|storage| {
+        if storage.add_user(name.clone()).is_none() {
+            return Err(BankError::UserExists(name.clone()));
+        }
+        if balance.value() != 0 {
+            storage.deposit(name, balance)?;
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::Date;
+
+    #[test]
+    fn test_open_account_is_atomic() {
+        let mut storage = Storage::new();
+        let name = "Alice".to_string();
+        open_account(&mut storage, &name, Balance::new(100)).unwrap();
+        assert_eq!(storage.get_balance(&name), Some(Balance::new(100)));
+        assert!(matches!(
+            open_account(&mut storage, &name, Balance::new(0)),
+            Err(BankError::UserExists(_))
+        ));
+
+        // День закрыт, депозит отклонён - счёт не открыт
+        let date: Date = "2024-05-20".parse().unwrap();
+        storage.set_time(date.start());
+        storage.close_day(date).unwrap();
+        let bob = "Bob".to_string();
+        assert!(open_account(&mut storage, &bob, Balance::new(10)).is_err());
+        assert_eq!(storage.get_balance(&bob), None);
+    }
+
+    #[test]
+    fn test_amounts() {
+        assert_eq!(amount(Lang::En, "5").ok(), Some(Balance::new(5)));
+        assert!(matches!(amount(Lang::En, "0"), Err(Failure::Invalid(_))));
+        assert!(matches!(amount(Lang::En, "x"), Err(Failure::Invalid(_))));
+        assert_eq!(initial_balance(Lang::En, "0").ok(), Some(Balance::new(0)));
+        assert!(matches!(
+            initial_balance(Lang::En, "-1"),
+            Err(Failure::Invalid(_))
+        ));
+    }
+}
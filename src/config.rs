@@ -0,0 +1,505 @@
+use super::errors::{BankError, FileContext};
+use super::limits::Rule;
+use super::locale::Lang;
+use super::screening::ReviewQueue;
+use super::storage::{Balance, Storage, WritePolicy};
+use std::env;
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// Файл настроек, который ищется в текущем каталоге, если другой не указан
+pub const DEFAULT_FILE: &str = "bank.conf";
+
+/// Ключи настроек. В окружении каждому соответствует переменная
+/// `BANK_` + ключ в верхнем регистре с `_` вместо `.`, например `BANK_DATA_DIR`
+pub const KEYS: &[&str] = &[
+    "data_dir",
+    "backend",
+    "currency",
+    "lang",
+    "limit.max_withdrawn",
+    "limit.max_transfers",
+    "limit.max_single_transfer",
+    "flush.changes",
+    "flush.interval",
+    "flush.journal",
+];
+
+/// Где хранятся данные банка
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// CSV-файлы в каталоге данных
+    #[default]
+    Csv,
+    /// Только в памяти: ничего не читается и не пишется
+    Memory,
+}
+
+impl Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Backend::Csv => write!(f, "csv"),
+            Backend::Memory => write!(f, "memory"),
+        }
+    }
+}
+
+impl FromStr for Backend {
+    type Err = BankError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "csv" => Ok(Backend::Csv),
+            "memory" => Ok(Backend::Memory),
+            _ => Err(BankError::InvalidFormat(format!("unknown backend: {s}"))),
+        }
+    }
+}
+
+/// Настройки банка.
+///
+/// Собираются по слоям, каждый следующий перекрывает предыдущий: умолчания,
+/// общая часть файла, секция `[профиль]` файла, переменные окружения, явные значения
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    /// Выбранный профиль, например `test` или `prod`
+    pub profile: Option<String>,
+    /// Каталог с файлами банка
+    pub data_dir: String,
+    pub backend: Backend,
+    pub currency: String,
+    /// Язык сообщений. Если не задан, программа определяет его по окружению
+    pub lang: Option<Lang>,
+    /// Лимиты для всех счетов: сумма снятий за период в секундах
+    pub max_withdrawn: Option<(Balance, i64)>,
+    /// Число исходящих переводов за период в секундах
+    pub max_transfers: Option<(u32, i64)>,
+    pub max_single_transfer: Option<Balance>,
+    /// Когда записывать изменения: число изменений, интервал в секундах, журнал
+    pub write_policy: WritePolicy,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            profile: None,
+            data_dir: ".".to_owned(),
+            backend: Backend::default(),
+            currency: "RUB".to_owned(),
+            lang: None,
+            max_withdrawn: None,
+            max_transfers: None,
+            max_single_transfer: None,
+            write_policy: WritePolicy::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Настройки для программы. Файл берётся из `file`, `BANK_CONFIG` или `bank.conf`
+    /// (если он есть), профиль - из `profile` или `BANK_PROFILE`
+    pub fn load(
+        file: Option<&str>,
+        profile: Option<&str>,
+        overrides: &[(String, String)],
+    ) -> Result<Self, BankError> {
+        let var = |name: &str| env::var(name).ok().filter(|value| !value.is_empty());
+        let profile = profile.map(str::to_owned).or_else(|| var("BANK_PROFILE"));
+        let path = match file.map(str::to_owned).or_else(|| var("BANK_CONFIG")) {
+            Some(path) => Some(path),
+            None if Path::new(DEFAULT_FILE).exists() => Some(DEFAULT_FILE.to_owned()),
+            None => None,
+        };
+        let text = match &path {
+            Some(path) => Some(fs::read_to_string(path).in_file(path)?),
+            None => None,
+        };
+        let file = path.as_deref().zip(text.as_deref());
+        Self::resolve(file, profile.as_deref(), var, overrides)
+    }
+
+    /// Собирает настройки из файла `(путь, текст)`, окружения `var` и явных значений
+    pub fn resolve(
+        file: Option<(&str, &str)>,
+        profile: Option<&str>,
+        var: impl Fn(&str) -> Option<String>,
+        overrides: &[(String, String)],
+    ) -> Result<Self, BankError> {
+        let mut config = Config {
+            profile: profile.map(str::to_owned),
+            ..Config::default()
+        };
+
+        let mut found = profile.is_none();
+        if let Some((path, text)) = file {
+            // Сначала общая часть, затем секция профиля, где бы она ни стояла
+            let mut global = Vec::new();
+            let mut selected = Vec::new();
+            let mut section: Option<&str> = None;
+            for (i, line) in text.lines().enumerate() {
+                let line = line.split('#').next().unwrap_or_default().trim();
+                if line.is_empty() {
+                    continue;
+                }
+                if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                    let name = name.trim();
+                    found |= Some(name) == profile;
+                    section = Some(name);
+                    continue;
+                }
+                let Some((key, value)) = line.split_once('=') else {
+                    return Err(BankError::InvalidFormat(format!("settings line: {line}")))
+                        .at_line(path, i);
+                };
+                let entry = (i, key.trim(), value.trim());
+                match section {
+                    None => global.push(entry),
+                    Some(name) if Some(name) == profile => selected.push(entry),
+                    Some(_) => {}
+                }
+            }
+            for (i, key, value) in global.into_iter().chain(selected) {
+                config.set(key, value).at_line(path, i)?;
+            }
+        }
+        if let (false, Some(profile)) = (found, profile) {
+            return Err(BankError::InvalidFormat(format!(
+                "unknown profile: {profile}"
+            )));
+        }
+
+        for key in KEYS {
+            let name = format!("BANK_{}", key.to_uppercase().replace('.', "_"));
+            if let Some(value) = var(&name) {
+                config.set(key, &value)?;
+            }
+        }
+        for (key, value) in overrides {
+            config.set(key, value)?;
+        }
+        Ok(config)
+    }
+
+    /// Меняет одну настройку. Лимит и порог записи выключаются значением `off`, язык - `auto`
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), BankError> {
+        let bad = || BankError::InvalidFormat(format!("bad value for {key}: {value}"));
+        let value = value.trim();
+        match key {
+            "data_dir" if !value.is_empty() => self.data_dir = value.to_owned(),
+            "backend" => self.backend = value.parse()?,
+            "currency" if !value.is_empty() => self.currency = value.to_owned(),
+            "lang" if value == "auto" => self.lang = None,
+            "lang" => self.lang = Some(value.parse()?),
+            "limit.max_withdrawn" => {
+                self.max_withdrawn = off_or(value, |v| {
+                    let (amount, period) = per_period(v)?;
+                    Some((Balance::new(amount), period))
+                })
+                .ok_or_else(bad)?;
+            }
+            "limit.max_transfers" => {
+                self.max_transfers = off_or(value, |v| {
+                    let (count, period) = per_period(v)?;
+                    Some((u32::try_from(count).ok()?, period))
+                })
+                .ok_or_else(bad)?;
+            }
+            "limit.max_single_transfer" => {
+                self.max_single_transfer =
+                    off_or(value, |v| v.parse().ok().map(Balance::new)).ok_or_else(bad)?;
+            }
+            "flush.changes" => {
+                self.write_policy.every_changes =
+                    off_or(value, |v| v.parse().ok().filter(|n| *n > 0)).ok_or_else(bad)?;
+            }
+            "flush.interval" => {
+                self.write_policy.interval = off_or(value, |v| {
+                    v.parse().ok().filter(|s| *s > 0).map(Duration::from_secs)
+                })
+                .ok_or_else(bad)?;
+            }
+            "flush.journal" => {
+                self.write_policy.journal = match value {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err(bad()),
+                }
+            }
+            _ if KEYS.contains(&key) => return Err(bad()),
+            _ => return Err(BankError::InvalidFormat(format!("unknown setting: {key}"))),
+        }
+        Ok(())
+    }
+
+    /// Значение настройки в том виде, в каком его принимает `set`
+    pub fn get(&self, key: &str) -> Option<String> {
+        let off = |value: Option<String>| value.unwrap_or_else(|| "off".to_owned());
+        let value = match key {
+            "data_dir" => self.data_dir.clone(),
+            "backend" => self.backend.to_string(),
+            "currency" => self.currency.clone(),
+            "lang" => self
+                .lang
+                .map_or_else(|| "auto".to_owned(), |l| l.to_string()),
+            "limit.max_withdrawn" => off(self.max_withdrawn.map(|(a, p)| format!("{a}/{p}"))),
+            "limit.max_transfers" => off(self.max_transfers.map(|(c, p)| format!("{c}/{p}"))),
+            "limit.max_single_transfer" => off(self.max_single_transfer.map(|a| a.to_string())),
+            "flush.changes" => off(self.write_policy.every_changes.map(|n| n.to_string())),
+            "flush.interval" => off(self.write_policy.interval.map(|i| i.as_secs().to_string())),
+            "flush.journal" => if self.write_policy.journal {
+                "on"
+            } else {
+                "off"
+            }
+            .to_owned(),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// Путь к файлу в каталоге данных
+    pub fn path(&self, file: &str) -> String {
+        if self.data_dir == "." {
+            return file.to_owned();
+        }
+        Path::new(&self.data_dir)
+            .join(file)
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Файл с балансами банка
+    pub fn data_file(&self) -> String {
+        self.path("balance.csv")
+    }
+
+    /// Лимиты из настроек, действующие на все счета
+    pub fn rules(&self) -> Vec<Rule> {
+        let mut rules = Vec::new();
+        if let Some((amount, period)) = self.max_withdrawn {
+            rules.push(Rule::MaxWithdrawn { amount, period });
+        }
+        if let Some((count, period)) = self.max_transfers {
+            rules.push(Rule::MaxTransfers { count, period });
+        }
+        if let Some(amount) = self.max_single_transfer {
+            rules.push(Rule::MaxSingleTransfer(amount));
+        }
+        rules
+    }
+
+    /// Есть ли уже данные банка. В памяти их нет, пока банк не создан
+    pub fn exists(&self) -> bool {
+        match self.backend {
+            Backend::Csv => Path::new(&self.data_file()).exists(),
+            Backend::Memory => false,
+        }
+    }
+
+    /// Загружает данные банка и включает лимиты из настроек.
+    /// Если данных нет - ошибка `DataNotFound`, создаёт банк `init_storage`
+    pub fn load_storage(&self) -> Result<Storage, BankError> {
+        let storage = match self.backend {
+            Backend::Csv => Storage::load_data(&self.data_file())?,
+            Backend::Memory => Storage::new(),
+        };
+        Ok(self.configure(storage))
+    }
+
+    /// Создаёт новый банк, пустой или со счетами из файла `fixture`
+    pub fn init_storage(&self, fixture: Option<&str>) -> Result<Storage, BankError> {
+        let storage = match self.backend {
+            Backend::Csv => {
+                fs::create_dir_all(&self.data_dir).in_file(&self.data_dir)?;
+                Storage::create(&self.data_file(), fixture)?
+            }
+            Backend::Memory => Storage::seeded(fixture)?,
+        };
+        Ok(self.configure(storage))
+    }
+
+    fn configure(&self, mut storage: Storage) -> Storage {
+        for rule in self.rules() {
+            storage.limits_mut().add_rule(rule);
+        }
+        storage.set_write_policy(self.write_policy);
+        storage
+    }
+
+    /// Записывает изменения, если пора по политике записи (см. `WritePolicy`)
+    pub fn save_storage(&self, storage: &mut Storage) -> Result<(), BankError> {
+        match self.backend {
+            Backend::Csv => storage.flush_if_due(&self.data_file(), Instant::now())?,
+            Backend::Memory => false,
+        };
+        Ok(())
+    }
+
+    /// Записывает все накопленные изменения. Вызывается при завершении работы
+    pub fn flush_storage(&self, storage: &mut Storage) -> Result<(), BankError> {
+        match self.backend {
+            Backend::Csv => storage.flush(&self.data_file())?,
+            Backend::Memory => false,
+        };
+        Ok(())
+    }
+
+    /// Очередь операций на проверку
+    pub fn load_queue(&self) -> Result<ReviewQueue, BankError> {
+        match self.backend {
+            Backend::Csv => ReviewQueue::load(&self.path("review.csv")),
+            Backend::Memory => Ok(ReviewQueue::default()),
+        }
+    }
+
+    pub fn save_queue(&self, queue: &ReviewQueue) -> Result<(), BankError> {
+        match self.backend {
+            Backend::Csv => queue.save(&self.path("review.csv")),
+            Backend::Memory => Ok(()),
+        }
+    }
+}
+
+/// `off` - `Some(None)`, иначе разобранное значение; `None`, если разобрать не удалось
+fn off_or<T>(value: &str, parse: impl Fn(&str) -> Option<T>) -> Option<Option<T>> {
+    if value == "off" {
+        Some(None)
+    } else {
+        parse(value).map(Some)
+    }
+}
+
+/// Разбирает "количество/период", например "1000/86400"
+fn per_period(value: &str) -> Option<(i64, i64)> {
+    let (amount, period) = value.split_once('/')?;
+    let period: i64 = period.trim().parse().ok()?;
+    (period > 0).then_some(())?;
+    Some((amount.trim().parse().ok()?, period))
+}
+
+impl Display for Config {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(profile) = &self.profile {
+            writeln!(f, "profile = {profile}")?;
+        }
+        for (i, key) in KEYS.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{key} = {}", self.get(key).unwrap_or_default())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE: &str = "\
+# общие настройки
+data_dir = data
+currency = RUB
+limit.max_single_transfer = 1000
+
+[test]
+data_dir = qa   # отдельный банк для тестирования
+backend = memory
+
+[prod]
+limit.max_withdrawn = 50000/86400
+";
+
+    fn no_env(_: &str) -> Option<String> {
+        None
+    }
+
+    #[test]
+    fn test_layers() {
+        let config = Config::resolve(None, None, no_env, &[]).unwrap();
+        assert_eq!(config, Config::default());
+        assert_eq!(config.data_file(), "balance.csv");
+
+        let file = Some(("bank.conf", FILE));
+        let config = Config::resolve(file, None, no_env, &[]).unwrap();
+        assert_eq!(
+            config.data_file(),
+            Path::new("data").join("balance.csv").to_string_lossy()
+        );
+        assert_eq!(config.backend, Backend::Csv);
+        assert_eq!(
+            config.rules(),
+            vec![Rule::MaxSingleTransfer(Balance::new(1000))]
+        );
+
+        let config = Config::resolve(file, Some("test"), no_env, &[]).unwrap();
+        assert_eq!(config.data_dir, "qa");
+        assert_eq!(config.backend, Backend::Memory);
+
+        // Окружение перекрывает файл, явные значения - окружение
+        let env = |name: &str| match name {
+            "BANK_DATA_DIR" => Some("/srv/bank".to_owned()),
+            "BANK_CURRENCY" => Some("USD".to_owned()),
+            _ => None,
+        };
+        let overrides = [
+            ("currency".to_owned(), "EUR".to_owned()),
+            ("limit.max_single_transfer".to_owned(), "off".to_owned()),
+        ];
+        let config = Config::resolve(file, Some("prod"), env, &overrides).unwrap();
+        assert_eq!(config.data_dir, "/srv/bank");
+        assert_eq!(config.currency, "EUR");
+        assert_eq!(
+            config.rules(),
+            vec![Rule::MaxWithdrawn {
+                amount: Balance::new(50000),
+                period: 86400
+            }]
+        );
+        assert_eq!(config.get("limit.max_single_transfer").unwrap(), "off");
+    }
+
+    #[test]
+    fn test_errors() {
+        let file = Some(("bank.conf", FILE));
+        assert!(Config::resolve(file, Some("stage"), no_env, &[]).is_err());
+        assert!(Config::resolve(None, Some("test"), no_env, &[]).is_err());
+
+        let err = Config::resolve(
+            Some(("bank.conf", "lang = en\nbackend = sql")),
+            None,
+            no_env,
+            &[],
+        )
+        .unwrap_err();
+        assert!(
+            matches!(err, BankError::File { line: Some(2), .. }),
+            "{err:?}"
+        );
+
+        let mut config = Config::default();
+        for (key, value) in [
+            ("colour", "red"),
+            ("limit.max_transfers", "10"),
+            ("limit.max_withdrawn", "100/0"),
+            ("data_dir", ""),
+            ("flush.changes", "0"),
+            ("flush.journal", "yes"),
+        ] {
+            assert!(config.set(key, value).is_err(), "{key} = {value}");
+        }
+        // Всё, что показывает `get`, принимается обратно `set`
+        config.set("limit.max_transfers", "3/3600").unwrap();
+        config.set("lang", "ru").unwrap();
+        config.set("flush.interval", "30").unwrap();
+        config.set("flush.journal", "on").unwrap();
+        let copy = config.clone();
+        for key in KEYS {
+            config.set(key, &copy.get(key).unwrap()).unwrap();
+        }
+        assert_eq!(config, copy);
+    }
+}
@@ -1,41 +1,266 @@
+use super::limits::Rule;
+use super::locale::{self, Lang};
+use super::screening::{Reason, ReviewId};
+use super::storage::{Balance, HoldId, Name};
+use super::time::Date;
 use std::error::Error;
 use std::fmt::Display;
+use std::io;
+use std::num::ParseIntError;
 
 #[derive(Debug)]
 pub enum BankError {
-    UserNotFound,
-    FundsLimit,
-    System(String),
+    UserNotFound(Name),
+    UserExists(Name),
+    FundsLimit {
+        account: Name,
+        requested: Balance,
+        available: Balance,
+    },
+    HoldNotFound(HoldId),
+    ReviewNotFound(ReviewId),
+    Blocked(Reason),
+    UnbalancedEntry(String),
+    /// Узла с таким кодом нет в плане счетов
+    ChartNodeNotFound(String),
+    ChartNodeExists(String),
+    /// Узел и его родитель в плане счетов разного типа
+    ChartTypeMismatch {
+        code: String,
+        parent: String,
+    },
+    PeriodClosed(Date),
+    LimitExceeded {
+        account: Name,
+        rule: Rule,
+    },
+    ConditionNotMet(String),
+    Io(io::Error),
+    Parse(ParseIntError),
+    /// Строка или значение в неожиданном формате
+    InvalidFormat(String),
+    /// Неверные аргументы команды, описание уже на языке пользователя
+    InvalidArgument(String),
+    /// Файла данных банка нет, новый банк нужно создать явно
+    DataNotFound(String),
+    /// Данные банка уже есть, создавать новый поверх них нельзя
+    DataExists(String),
+    /// Ошибка при чтении файла: где она произошла и что именно случилось
+    File {
+        path: String,
+        line: Option<usize>,
+        source: Box<BankError>,
+    },
 }
 
-impl Display for BankError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl BankError {
+    /// Стабильный числовой код для клиентов API. Для ошибок в файле - код причины
+    pub fn code(&self) -> u32 {
+        match self {
+            BankError::UserNotFound(_) => 1001,
+            BankError::FundsLimit { .. } => 1002,
+            BankError::HoldNotFound(_) => 1003,
+            BankError::ReviewNotFound(_) => 1004,
+            BankError::UserExists(_) => 1005,
+            BankError::LimitExceeded { .. } => 2001,
+            BankError::Blocked(_) => 2002,
+            BankError::ConditionNotMet(_) => 2003,
+            BankError::PeriodClosed(_) => 2004,
+            BankError::UnbalancedEntry(_) => 3001,
+            BankError::ChartNodeNotFound(_) => 3002,
+            BankError::ChartNodeExists(_) => 3003,
+            BankError::ChartTypeMismatch { .. } => 3004,
+            BankError::Io(_) => 4001,
+            BankError::Parse(_) => 4002,
+            BankError::InvalidFormat(_) => 4003,
+            BankError::InvalidArgument(_) => 4004,
+            BankError::DataNotFound(_) => 4005,
+            BankError::DataExists(_) => 4006,
+            BankError::File { source, .. } => source.code(),
+        }
+    }
+
+    /// Стабильный строковый код для клиентов API
+    pub fn name(&self) -> &'static str {
         match self {
-            BankError::UserNotFound => {
-                write!(f, "User not found")
+            BankError::UserNotFound(_) => "user_not_found",
+            BankError::FundsLimit { .. } => "insufficient_funds",
+            BankError::HoldNotFound(_) => "hold_not_found",
+            BankError::ReviewNotFound(_) => "review_not_found",
+            BankError::UserExists(_) => "user_exists",
+            BankError::LimitExceeded { .. } => "limit_exceeded",
+            BankError::Blocked(_) => "blocked",
+            BankError::ConditionNotMet(_) => "condition_not_met",
+            BankError::PeriodClosed(_) => "period_closed",
+            BankError::UnbalancedEntry(_) => "unbalanced_entry",
+            BankError::ChartNodeNotFound(_) => "chart_node_not_found",
+            BankError::ChartNodeExists(_) => "chart_node_exists",
+            BankError::ChartTypeMismatch { .. } => "chart_type_mismatch",
+            BankError::Io(_) => "io_error",
+            BankError::Parse(_) => "parse_error",
+            BankError::InvalidFormat(_) => "invalid_format",
+            BankError::InvalidArgument(_) => "invalid_argument",
+            BankError::DataNotFound(_) => "data_not_found",
+            BankError::DataExists(_) => "data_exists",
+            BankError::File { source, .. } => source.name(),
+        }
+    }
+
+    /// Сообщение на выбранном языке из каталога `locale`
+    pub fn message(&self, lang: Lang) -> String {
+        let key = format!("error.{}", self.name());
+        match self {
+            BankError::UserNotFound(name) | BankError::UserExists(name) => {
+                locale::format(lang, &key, &[("name", name)])
+            }
+            BankError::FundsLimit {
+                account,
+                requested,
+                available,
+            } => locale::format(
+                lang,
+                &key,
+                &[
+                    ("account", account),
+                    ("requested", requested),
+                    ("available", available),
+                ],
+            ),
+            BankError::HoldNotFound(id) => locale::format(lang, &key, &[("id", id)]),
+            BankError::ReviewNotFound(id) => locale::format(lang, &key, &[("id", id)]),
+            BankError::Blocked(reason) => {
+                let reason = reason.message(lang);
+                locale::format(lang, &key, &[("reason", &reason)])
             }
-            BankError::FundsLimit => {
-                write!(f, "Isn't enought money")
+            BankError::UnbalancedEntry(memo) => locale::format(lang, &key, &[("memo", memo)]),
+            BankError::ChartNodeNotFound(code) | BankError::ChartNodeExists(code) => {
+                locale::format(lang, &key, &[("code", code)])
             }
-            BankError::System(description) => {
-                write!(f, "System error: {description}")
+            BankError::ChartTypeMismatch { code, parent } => {
+                locale::format(lang, &key, &[("code", code), ("parent", parent)])
             }
+            BankError::PeriodClosed(date) => locale::format(lang, &key, &[("date", date)]),
+            BankError::LimitExceeded { account, rule } => {
+                let rule = rule.message(lang);
+                locale::format(lang, &key, &[("account", account), ("rule", &rule)])
+            }
+            BankError::DataNotFound(path) | BankError::DataExists(path) => {
+                locale::format(lang, &key, &[("path", path)])
+            }
+            BankError::Io(e) => locale::format(lang, &key, &[("error", e)]),
+            BankError::Parse(e) => locale::format(lang, &key, &[("error", e)]),
+            BankError::ConditionNotMet(description)
+            | BankError::InvalidFormat(description)
+            | BankError::InvalidArgument(description) => {
+                locale::format(lang, &key, &[("description", description)])
+            }
+            BankError::File { path, line, source } => {
+                let error = source.message(lang);
+                match line {
+                    Some(line) => locale::format(
+                        lang,
+                        "error.file_line",
+                        &[("path", path), ("line", line), ("error", &error)],
+                    ),
+                    None => {
+                        locale::format(lang, "error.file", &[("path", path), ("error", &error)])
+                    }
+                }
+            }
+        }
+    }
+
+    /// Добавляет к ошибке файл и номер строки (с единицы)
+    pub fn in_file(self, path: &str, line: Option<usize>) -> Self {
+        BankError::File {
+            path: path.to_owned(),
+            line,
+            source: Box::new(self),
         }
     }
 }
 
-impl Error for BankError {}
+impl Display for BankError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message(Lang::En))
+    }
+}
+
+impl Error for BankError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BankError::Io(e) => Some(e),
+            BankError::Parse(e) => Some(e),
+            BankError::File { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for BankError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<ParseIntError> for BankError {
+    fn from(value: ParseIntError) -> Self {
+        Self::Parse(value)
+    }
+}
+
+/// Добавление к ошибкам чтения файлов пути и номера строки
+pub trait FileContext<T> {
+    fn in_file(self, path: &str) -> Result<T, BankError>;
+    /// `line` считается с нуля, как в `enumerate()`, а в сообщении - с единицы
+    fn at_line(self, path: &str, line: usize) -> Result<T, BankError>;
+}
+
+impl<T, E: Into<BankError>> FileContext<T> for Result<T, E> {
+    fn in_file(self, path: &str) -> Result<T, BankError> {
+        self.map_err(|e| e.into().in_file(path, None))
+    }
 
-impl From<std::io::Error> for BankError {
-    fn from(value: std::io::Error) -> Self {
-        let reason = format!("{value}");
-        Self::System(reason)
+    fn at_line(self, path: &str, line: usize) -> Result<T, BankError> {
+        self.map_err(|e| e.into().in_file(path, Some(line + 1)))
     }
 }
 
-impl From<std::num::ParseIntError> for BankError {
-    fn from(value: std::num::ParseIntError) -> Self {
-        let reason = format!("{value}");
-        Self::System(reason)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes_and_source_chain() {
+        let err: BankError = "x".parse::<i64>().unwrap_err().into();
+        let err = err.in_file("balance.csv", Some(3));
+        assert_eq!(err.code(), 4002);
+        assert_eq!(err.name(), "parse_error");
+        assert!(
+            err.to_string()
+                .starts_with("balance.csv, line 3: Invalid number")
+        );
+
+        let inner = err.source().unwrap();
+        assert!(inner.to_string().starts_with("Invalid number"));
+        assert!(inner.source().unwrap().is::<ParseIntError>());
+    }
+
+    #[test]
+    fn test_localized_messages() {
+        let err = BankError::FundsLimit {
+            account: "Alice".to_owned(),
+            requested: Balance::new(100),
+            available: Balance::new(40),
+        };
+        assert_eq!(
+            err.message(Lang::En),
+            "Not enough money on Alice: requested 100, available 40"
+        );
+        assert_eq!(
+            err.message(Lang::Ru),
+            "Недостаточно денег на счёте Alice: запрошено 100, доступно 40"
+        );
+        assert_eq!(err.code(), 1002);
     }
 }
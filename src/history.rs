@@ -0,0 +1,310 @@
+use super::errors::{BankError, FileContext};
+use super::storage::{Name, write_file};
+use super::time::Timestamp;
+use super::transaction::TxKind;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+pub type RecordId = u64;
+
+/// Запись о выполненной операции
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Record {
+    id: RecordId,
+    time: Timestamp,
+    tx: TxKind,
+}
+
+impl Record {
+    pub fn id(&self) -> RecordId {
+        self.id
+    }
+
+    pub fn time(&self) -> Timestamp {
+        self.time
+    }
+
+    pub fn tx(&self) -> &TxKind {
+        &self.tx
+    }
+
+    /// Касается ли операция счёта `name`
+    pub fn involves(&self, name: &Name) -> bool {
+        self.tx.accounts().contains(&name)
+    }
+
+    /// Разбирает строку журнала "Id,Time,<операция>"
+    fn parse(line: &str) -> Result<Record, BankError> {
+        let parts: Vec<&str> = line.splitn(3, ',').collect();
+        if parts.len() != 3 {
+            return Err(BankError::InvalidFormat(format!("history line: {line}")));
+        }
+        Ok(Record {
+            id: parts[0].parse()?,
+            time: parts[1].parse()?,
+            tx: parts[2].parse()?,
+        })
+    }
+}
+
+impl Display for Record {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{},{}", self.id, self.time, self.tx)
+    }
+}
+
+/// Журнал выполненных операций в порядке их выполнения
+#[derive(Clone, Default, Debug)]
+pub struct History {
+    records: Vec<Record>,
+    next_id: RecordId,
+    /// Позиции операций каждого счёта в `records`
+    index: HashMap<Name, Vec<usize>>,
+    /// Время операций где-то идёт назад (часы перевели, операцию перенесли),
+    /// поэтому искать по времени двоичным поиском нельзя
+    unordered: bool,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, time: Timestamp, tx: TxKind) -> RecordId {
+        self.next_id += 1;
+        self.insert(Record {
+            id: self.next_id,
+            time,
+            tx,
+        });
+        self.next_id
+    }
+
+    fn insert(&mut self, record: Record) {
+        let position = self.records.len();
+        self.unordered |= self
+            .records
+            .last()
+            .is_some_and(|last| last.time > record.time);
+        for name in record.tx.accounts() {
+            let positions = self.index.entry(name.clone()).or_default();
+            // Перевод самому себе учитывается один раз
+            if positions.last() != Some(&position) {
+                positions.push(position);
+            }
+        }
+        self.records.push(record);
+    }
+
+    pub fn records(&self) -> &[Record] {
+        &self.records
+    }
+
+    /// Операции по счёту `name`, по индексу, без обхода всего журнала
+    pub fn for_account<'a>(
+        &'a self,
+        name: &Name,
+    ) -> impl DoubleEndedIterator<Item = &'a Record> + use<'a> {
+        self.positions(name).iter().map(|&i| &self.records[i])
+    }
+
+    /// Операции, выполненные в промежутке (from, to]
+    pub fn between(&self, from: Timestamp, to: Timestamp) -> impl Iterator<Item = &Record> {
+        let records = if self.unordered {
+            &self.records[..]
+        } else {
+            let start = self.records.partition_point(|r| r.time <= from);
+            let end = self.records.partition_point(|r| r.time <= to);
+            &self.records[start..end]
+        };
+        records
+            .iter()
+            .filter(move |r| r.time > from && r.time <= to)
+    }
+
+    /// Операции по счёту `name` в промежутке (from, to]
+    pub fn for_account_between<'a>(
+        &'a self,
+        name: &Name,
+        from: Timestamp,
+        to: Timestamp,
+    ) -> impl Iterator<Item = &'a Record> + use<'a> {
+        let mut positions = self.positions(name);
+        if !self.unordered {
+            let start = positions.partition_point(|&i| self.records[i].time <= from);
+            let end = positions.partition_point(|&i| self.records[i].time <= to);
+            positions = &positions[start..end];
+        }
+        positions
+            .iter()
+            .map(|&i| &self.records[i])
+            .filter(move |r| r.time > from && r.time <= to)
+    }
+
+    fn positions(&self, name: &Name) -> &[usize] {
+        self.index.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    /// Загружает журнал из CSV-файла формата "Id,Time,<операция>"
+    pub fn load(file: &str) -> Result<History, BankError> {
+        let mut history = History::new();
+        if !Path::new(file).exists() {
+            return Ok(history);
+        }
+
+        let reader = BufReader::new(File::open(file).in_file(file)?);
+        for (i, line) in reader.lines().enumerate() {
+            let line = line.at_line(file, i)?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record = Record::parse(line.trim()).at_line(file, i)?;
+            history.next_id = history.next_id.max(record.id);
+            history.insert(record);
+        }
+        Ok(history)
+    }
+
+    /// Оставляет только первые `len` операций, номера продолжатся с места обрезки
+    pub fn truncate(&mut self, len: usize) {
+        let removed = self.records.len().saturating_sub(len);
+        for record in self.records.iter().skip(len) {
+            for name in record.tx.accounts() {
+                if let Some(positions) = self.index.get_mut(name) {
+                    while positions.last().is_some_and(|&i| i >= len) {
+                        positions.pop();
+                    }
+                }
+            }
+        }
+        self.records.truncate(len);
+        self.next_id -= removed as RecordId;
+    }
+
+    /// Номер последней операции, 0 - если журнал пуст
+    pub fn last_id(&self) -> RecordId {
+        self.records.last().map_or(0, |r| r.id)
+    }
+
+    /// Операции с номером больше `id`
+    pub fn after(&self, id: RecordId) -> &[Record] {
+        let start = self.records.partition_point(|r| r.id <= id);
+        &self.records[start..]
+    }
+
+    pub fn save(&self, file: &str) -> Result<(), BankError> {
+        let mut data = String::new();
+        for record in &self.records {
+            data.push_str(&format!("{record}\n"));
+        }
+        write_file(file, &data)
+    }
+
+    /// Дописывает операции с номером больше `id` в конец файла журнала
+    pub fn append(&self, file: &str, id: RecordId) -> Result<(), BankError> {
+        let mut data = String::new();
+        for record in self.after(id) {
+            data.push_str(&format!("{record}\n"));
+        }
+        if data.is_empty() {
+            return Ok(());
+        }
+        let mut journal = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(file)
+            .in_file(file)?;
+        journal.write_all(data.as_bytes()).in_file(file)?;
+        journal.sync_data().in_file(file)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Balance;
+    use crate::transaction::{Deposit, Transfer};
+    use std::fs;
+
+    #[test]
+    fn test_save_and_load() {
+        let mut history = History::new();
+        history.push(
+            Timestamp::new(10),
+            Deposit::new("Alice", Balance::new(100)).into(),
+        );
+        history.push(
+            Timestamp::new(20),
+            Transfer::new("Alice", "Bob", Balance::new(30)).into(),
+        );
+
+        let path = std::env::temp_dir().join("bank_history_test.csv");
+        let path = path.to_str().unwrap();
+        history.save(path).unwrap();
+        let mut loaded = History::load(path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(loaded.records(), history.records());
+        assert_eq!(
+            loaded.push(
+                Timestamp::new(30),
+                Deposit::new("Bob", Balance::new(1)).into()
+            ),
+            3
+        );
+        assert_eq!(loaded.for_account(&"Bob".to_string()).count(), 2);
+        assert_eq!(
+            loaded
+                .between(Timestamp::new(10), Timestamp::new(30))
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_account_index_and_windows() {
+        let alice = "Alice".to_string();
+        let mut history = History::new();
+        for (time, tx) in [
+            (10, Deposit::new("Alice", Balance::new(100)).into()),
+            (20, Deposit::new("Bob", Balance::new(50)).into()),
+            (30, Transfer::new("Alice", "Bob", Balance::new(10)).into()),
+            (40, Transfer::new("Alice", "Alice", Balance::new(1)).into()),
+        ] {
+            history.push(Timestamp::new(time), tx);
+        }
+        let ids = |records: Vec<&Record>| records.iter().map(|r| r.id()).collect::<Vec<_>>();
+        assert_eq!(ids(history.for_account(&alice).collect()), vec![1, 3, 4]);
+        assert_eq!(
+            ids(history.for_account(&alice).rev().collect()),
+            vec![4, 3, 1]
+        );
+        let window = history.for_account_between(&alice, Timestamp::new(10), Timestamp::new(30));
+        assert_eq!(ids(window.collect()), vec![3]);
+        let window = history.between(Timestamp::new(15), Timestamp::new(40));
+        assert_eq!(ids(window.collect()), vec![2, 3, 4]);
+
+        // Время пошло назад: поиск по времени остаётся верным
+        history.push(
+            Timestamp::new(25),
+            Deposit::new("Alice", Balance::new(1)).into(),
+        );
+        let window = history.for_account_between(&alice, Timestamp::new(20), Timestamp::new(30));
+        assert_eq!(ids(window.collect()), vec![3, 5]);
+
+        history.truncate(2);
+        assert_eq!(ids(history.for_account(&alice).collect()), vec![1]);
+        assert_eq!(
+            history.push(
+                Timestamp::new(50),
+                Deposit::new("Alice", Balance::new(1)).into()
+            ),
+            3
+        );
+        assert_eq!(ids(history.for_account(&alice).collect()), vec![1, 3]);
+    }
+}
@@ -0,0 +1,226 @@
+use super::errors::BankError;
+use super::locale::{self, Lang};
+use super::storage::{Name, Storage};
+use super::transaction::{Transaction, TxKind};
+use std::fmt::Display;
+
+/// Какой инвариант нарушила операция
+#[derive(Debug)]
+pub enum Invariant {
+    /// Операцию не удалось провести на снимке
+    NotApplicable(Box<BankError>),
+    /// Операция нарушает лимит счёта
+    Limit(Box<BankError>),
+    /// Сумма денег изменилась не так, как должна была
+    Conservation { expected: i64, actual: i64 },
+    /// Баланс счёта ушёл в минус
+    NegativeBalance(Name),
+    /// Баланс счёта разошёлся с главной книгой
+    LedgerMismatch(Name),
+}
+
+impl Invariant {
+    /// Описание нарушения на выбранном языке
+    pub fn message(&self, lang: Lang) -> String {
+        match self {
+            Invariant::NotApplicable(e) => {
+                let error = e.message(lang);
+                locale::format(lang, "invariant.not_applicable", &[("error", &error)])
+            }
+            Invariant::Limit(e) => e.message(lang),
+            Invariant::Conservation { expected, actual } => locale::format(
+                lang,
+                "invariant.conservation",
+                &[("expected", expected), ("actual", actual)],
+            ),
+            Invariant::NegativeBalance(name) => {
+                locale::format(lang, "invariant.negative_balance", &[("name", name)])
+            }
+            Invariant::LedgerMismatch(name) => {
+                locale::format(lang, "invariant.ledger_mismatch", &[("name", name)])
+            }
+        }
+    }
+}
+
+impl Display for Invariant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message(Lang::En))
+    }
+}
+
+/// Первая операция, нарушившая инвариант
+#[derive(Debug)]
+pub struct Violation {
+    /// Номер операции в последовательности, с нуля
+    pub index: usize,
+    /// Записи журнала, которые операция успела сделать
+    pub records: Vec<TxKind>,
+    pub invariant: Invariant,
+}
+
+impl Violation {
+    /// Номер, записи операции и нарушение на выбранном языке
+    pub fn message(&self, lang: Lang) -> String {
+        let mut text = format!("#{}", self.index + 1);
+        for (i, tx) in self.records.iter().enumerate() {
+            text.push_str(if i == 0 { " " } else { " + " });
+            text.push_str(&tx.to_string());
+        }
+        format!("{text}: {}", self.invariant.message(lang))
+    }
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message(Lang::En))
+    }
+}
+
+/// Сумма денег на всех клиентских счетах
+pub fn total_money(storage: &Storage) -> i64 {
+    storage.accounts().map(|(_, b)| b.value()).sum()
+}
+
+/// Насколько операция должна изменить сумму денег клиентов. Деньги приходят
+/// и уходят только через внешние операции и операции самого банка, переводы
+/// сумму не меняют
+fn external_delta(tx: &TxKind) -> i64 {
+    let amount = tx.amount().value();
+    match tx {
+        TxKind::Deposit(_) | TxKind::Interest(_) => amount,
+        TxKind::Withdraw(_) | TxKind::Fee(_) => -amount,
+        TxKind::Transfer(_) => 0,
+    }
+}
+
+/// Проводит операции по копии снимка `start` и проверяет после каждой, что
+/// сумма денег изменилась ровно на внешние операции из записей журнала,
+/// которые она сделала, лимиты соблюдены, балансы не отрицательны и сходятся
+/// с главной книгой. Операцией может быть и комбинатор. Возвращает первое нарушение
+pub fn verify(start: &Storage, txs: &[Box<dyn Transaction>]) -> Result<(), Violation> {
+    let mut storage = start.clone();
+    for (index, tx) in txs.iter().enumerate() {
+        let before = total_money(&storage);
+        let recorded = storage.history().records().len();
+        let result = tx.apply(&mut storage);
+        let records: Vec<TxKind> = storage.history().records()[recorded..]
+            .iter()
+            .map(|record| record.tx().clone())
+            .collect();
+        let violation = |invariant| {
+            Err(Violation {
+                index,
+                records: records.clone(),
+                invariant,
+            })
+        };
+
+        match result {
+            Ok(()) => {}
+            Err(e @ BankError::LimitExceeded { .. }) => {
+                return violation(Invariant::Limit(Box::new(e)));
+            }
+            Err(e) => return violation(Invariant::NotApplicable(Box::new(e))),
+        }
+        let actual = total_money(&storage) - before;
+        let expected = records.iter().map(external_delta).sum();
+        if actual != expected {
+            return violation(Invariant::Conservation { expected, actual });
+        }
+
+        for name in records.iter().flat_map(|tx| tx.accounts()) {
+            if storage.get_balance(name).is_some_and(|b| b.value() < 0) {
+                return violation(Invariant::NegativeBalance(name.clone()));
+            }
+        }
+        if let Some((name, _, _)) = storage.ledger_mismatches().into_iter().next() {
+            return violation(Invariant::LedgerMismatch(name));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::limits::Rule;
+    use crate::storage::{Balance, bank};
+    use crate::transaction::{BalanceAtLeast, Deposit, Fallback, OnlyIf, Transfer, Withdraw};
+
+    /// Закрывает счёт без записи в журнал: деньги клиента пропадают
+    struct Vanish(&'static str);
+
+    impl Transaction for Vanish {
+        fn apply(&self, storage: &mut Storage) -> Result<(), BankError> {
+            storage.remove_user(&self.0.to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_verify_ok() {
+        let txs: Vec<Box<dyn Transaction>> = vec![
+            Box::new(Transfer::new("Alice", "Bob", Balance::new(30))),
+            Box::new(Withdraw::new("Bob", Balance::new(10))),
+            Box::new(Deposit::new("Alice", Balance::new(5))),
+            Box::new(
+                Deposit::new("Bob", Balance::new(20))
+                    + Transfer::new("Bob", "Alice", Balance::new(5)),
+            ),
+            Box::new(OnlyIf::new(
+                BalanceAtLeast::new("Alice", Balance::new(50)),
+                Withdraw::new("Alice", Balance::new(50)),
+            )),
+            Box::new(Fallback::new(
+                Withdraw::new("Bob", Balance::new(1000)),
+                Withdraw::new("Bob", Balance::new(1)),
+            )),
+        ];
+        assert!(verify(&bank(&[("Alice", 100), ("Bob", 0)]), &txs).is_ok());
+    }
+
+    #[test]
+    fn test_verify_reports_first_violation() {
+        let mut start = bank(&[("Alice", 100), ("Bob", 0)]);
+        start.limits_mut().add_account_rule(
+            &"Alice".to_string(),
+            Rule::MaxSingleTransfer(Balance::new(50)),
+        );
+        let transfer = || Transfer::new("Alice", "Bob", Balance::new(30));
+        let txs: Vec<Box<dyn Transaction>> = vec![
+            Box::new(transfer()),
+            Box::new(Transfer::new("Alice", "Bob", Balance::new(60))),
+        ];
+        let violation = verify(&start, &txs).unwrap_err();
+        assert_eq!(violation.index, 1);
+        assert!(matches!(violation.invariant, Invariant::Limit(_)));
+
+        let txs: Vec<Box<dyn Transaction>> = vec![
+            Box::new(transfer()),
+            Box::new(Withdraw::new("Bob", Balance::new(500))),
+        ];
+        let violation = verify(&start, &txs).unwrap_err();
+        assert_eq!(violation.index, 1);
+        assert!(matches!(violation.invariant, Invariant::NotApplicable(_)));
+    }
+
+    #[test]
+    fn test_verify_checks_combinators_by_records() {
+        // Записан только депозит, а денег стало меньше на весь баланс Alice
+        let txs: Vec<Box<dyn Transaction>> = vec![
+            Box::new(Transfer::new("Alice", "Bob", Balance::new(10))),
+            Box::new(Deposit::new("Alice", Balance::new(5)) + Vanish("Alice")),
+        ];
+        let violation = verify(&bank(&[("Alice", 100), ("Bob", 0)]), &txs).unwrap_err();
+        assert_eq!(violation.index, 1);
+        assert_eq!(violation.records.len(), 1);
+        assert!(matches!(
+            violation.invariant,
+            Invariant::Conservation {
+                expected: 5,
+                actual: -90
+            }
+        ));
+    }
+}
@@ -0,0 +1,525 @@
+use super::analytics::{Bucket, Concentration, Report, Stats};
+use super::close::DayClose;
+use super::config::{self, Config};
+use super::errors::BankError;
+use super::history::Record;
+use super::invariants::Violation;
+use super::ledger::TrialBalance;
+use super::locale::Lang;
+use super::reconcile::StatementEntry;
+use super::screening::{ReviewId, ReviewItem};
+use super::storage::{Balance, Name, Storage};
+use super::time::{Date, Timestamp};
+use super::transaction::TxKind;
+use std::fmt::{Display, Write};
+
+/// Значение JSON для машинного вывода утилит. Поля объекта выводятся
+/// в порядке добавления, поэтому вывод стабилен и его удобно сравнивать
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+/// Объект из пар "поле - значение"
+pub fn object<const N: usize>(fields: [(&str, Json); N]) -> Json {
+    Json::Object(
+        fields
+            .into_iter()
+            .map(|(key, value)| (key.to_owned(), value))
+            .collect(),
+    )
+}
+
+/// Массив из значений, которые умеют превращаться в JSON
+pub fn array<T: Into<Json>>(items: impl IntoIterator<Item = T>) -> Json {
+    Json::Array(items.into_iter().map(Into::into).collect())
+}
+
+/// Ответ на успешную команду: `{"ok":true,"command":...,"result":...}`
+pub fn success(command: &str, result: Json) -> Json {
+    object([
+        ("ok", true.into()),
+        ("command", command.into()),
+        ("result", result),
+    ])
+}
+
+/// Ответ на неудачную команду: `{"ok":false,"command":...,"error":{"code","name","message"}}`
+pub fn failure(command: &str, error: &BankError, lang: Lang) -> Json {
+    failure_with(command, error, error.message(lang))
+}
+
+/// То же, что `failure`, но с собственным сообщением, например с пояснением,
+/// на каком шаге случилась ошибка
+pub fn failure_with(command: &str, error: &BankError, message: String) -> Json {
+    object([
+        ("ok", false.into()),
+        ("command", command.into()),
+        (
+            "error",
+            object([
+                ("code", error.code().into()),
+                ("name", error.name().into()),
+                ("message", message.into()),
+            ]),
+        ),
+    ])
+}
+
+/// Ответ на выполненную операцию с деньгами: `{"status":"applied","tx","balances"}`
+/// с балансами затронутых счетов после неё. Один и тот же в обеих утилитах
+pub fn applied(tx: &TxKind, storage: &Storage) -> Json {
+    let balances = tx
+        .accounts()
+        .into_iter()
+        .filter_map(|name| storage.get_balance(name).map(|b| (name, b)));
+    object([
+        ("status", "applied".into()),
+        ("tx", tx.into()),
+        ("balances", array(balances)),
+    ])
+}
+
+/// Ответ на операцию, отложенную на проверку: `{"status":"queued","tx","review","reason"}`
+pub fn queued(tx: &TxKind, review: ReviewId, reason: &str) -> Json {
+    object([
+        ("status", "queued".into()),
+        ("tx", tx.into()),
+        ("review", review.into()),
+        ("reason", reason.into()),
+    ])
+}
+
+/// Справка: `{"commands":[{"name","usage","description"}]}`
+pub fn help<'a>(topics: impl IntoIterator<Item = (&'a str, &'a str, &'a str)>) -> Json {
+    let commands = topics.into_iter().map(|(name, usage, description)| {
+        object([
+            ("name", name.into()),
+            ("usage", usage.into()),
+            ("description", description.into()),
+        ])
+    });
+    object([("commands", array(commands))])
+}
+
+impl Display for Json {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{value}"),
+            Json::Int(value) => write!(f, "{value}"),
+            // В JSON нет NaN и бесконечностей
+            Json::Float(value) if value.is_finite() => write!(f, "{value}"),
+            Json::Float(_) => write!(f, "null"),
+            Json::Str(value) => write_str(f, value),
+            Json::Array(items) => {
+                f.write_char('[')?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                f.write_char(']')
+            }
+            Json::Object(fields) => {
+                f.write_char('{')?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_str(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+fn write_str(f: &mut std::fmt::Formatter<'_>, value: &str) -> std::fmt::Result {
+    f.write_char('"')?;
+    for c in value.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Self {
+        Json::Bool(value)
+    }
+}
+
+impl From<i64> for Json {
+    fn from(value: i64) -> Self {
+        Json::Int(value)
+    }
+}
+
+impl From<u32> for Json {
+    fn from(value: u32) -> Self {
+        Json::Int(value.into())
+    }
+}
+
+impl From<u64> for Json {
+    fn from(value: u64) -> Self {
+        Json::Int(value as i64)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(value: usize) -> Self {
+        Json::Int(value as i64)
+    }
+}
+
+impl From<f64> for Json {
+    fn from(value: f64) -> Self {
+        Json::Float(value)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(value: &str) -> Self {
+        Json::Str(value.to_owned())
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Self {
+        Json::Str(value)
+    }
+}
+
+impl From<&String> for Json {
+    fn from(value: &String) -> Self {
+        Json::Str(value.clone())
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Json::Null, Into::into)
+    }
+}
+
+impl From<Balance> for Json {
+    fn from(value: Balance) -> Self {
+        Json::Int(value.value())
+    }
+}
+
+impl From<Timestamp> for Json {
+    fn from(value: Timestamp) -> Self {
+        Json::Int(value.value())
+    }
+}
+
+impl From<Date> for Json {
+    fn from(value: Date) -> Self {
+        Json::Str(value.to_string())
+    }
+}
+
+/// Операция в формате журнала: "deposit,Name,Amount"
+impl From<&TxKind> for Json {
+    fn from(value: &TxKind) -> Self {
+        Json::Str(value.to_string())
+    }
+}
+
+/// Пара "счёт - сумма"
+impl From<(&Name, Balance)> for Json {
+    fn from((account, balance): (&Name, Balance)) -> Self {
+        object([("account", account.into()), ("balance", balance.into())])
+    }
+}
+
+impl From<&(Name, Balance)> for Json {
+    fn from((account, balance): &(Name, Balance)) -> Self {
+        (account, *balance).into()
+    }
+}
+
+impl From<&Record> for Json {
+    fn from(value: &Record) -> Self {
+        object([
+            ("id", value.id().into()),
+            ("time", value.time().into()),
+            ("date", value.time().date().into()),
+            ("tx", value.tx().into()),
+            ("accounts", array(value.tx().accounts())),
+            ("amount", value.tx().amount().into()),
+        ])
+    }
+}
+
+impl From<&ReviewItem> for Json {
+    fn from(value: &ReviewItem) -> Self {
+        object([
+            ("id", value.id().into()),
+            ("time", value.time().into()),
+            ("reason", value.reason().to_string().into()),
+            ("tx", value.tx().into()),
+        ])
+    }
+}
+
+impl From<&StatementEntry> for Json {
+    fn from(value: &StatementEntry) -> Self {
+        object([
+            ("date", value.date.into()),
+            ("amount", value.amount.into()),
+            ("reference", value.reference.as_str().into()),
+        ])
+    }
+}
+
+impl From<&Stats> for Json {
+    fn from(value: &Stats) -> Self {
+        let percentiles = value.percentiles.iter().map(|(p, balance)| {
+            object([("p", u32::from(*p).into()), ("value", (*balance).into())])
+        });
+        object([
+            ("count", value.count.into()),
+            ("total", value.total.into()),
+            ("mean", value.mean.into()),
+            ("median", value.median.into()),
+            ("min", value.min.into()),
+            ("max", value.max.into()),
+            ("percentiles", array(percentiles)),
+            ("zero", value.zero.into()),
+            ("negative", value.negative.into()),
+        ])
+    }
+}
+
+/// Корзина гистограммы; открытая граница - null
+impl From<&Bucket> for Json {
+    fn from(value: &Bucket) -> Self {
+        object([
+            ("from", value.from.into()),
+            ("to", value.to.into()),
+            ("count", value.count.into()),
+        ])
+    }
+}
+
+impl From<&Report> for Json {
+    fn from(value: &Report) -> Self {
+        object([
+            ("top", array(&value.top)),
+            ("bottom", array(&value.bottom)),
+            ("stats", value.stats.as_ref().into()),
+            ("histogram", array(&value.histogram)),
+        ])
+    }
+}
+
+/// Доли выводятся числами с плавающей точкой, точные дроби - в текстовом отчёте
+impl From<&Concentration> for Json {
+    fn from(value: &Concentration) -> Self {
+        let lorenz = value.lorenz.iter().map(|(accounts, money)| {
+            object([
+                ("accounts", accounts.to_f64().into()),
+                ("money", money.to_f64().into()),
+            ])
+        });
+        object([
+            ("gini", value.gini.to_f64().into()),
+            ("top1", value.top1.to_f64().into()),
+            ("top10", value.top10.to_f64().into()),
+            ("herfindahl", value.herfindahl.to_f64().into()),
+            ("lorenz", array(lorenz)),
+        ])
+    }
+}
+
+impl From<&TrialBalance> for Json {
+    fn from(value: &TrialBalance) -> Self {
+        let rows = value.rows.iter().map(|row| {
+            object([
+                ("account", row.account.to_string().into()),
+                ("debit", row.debit.into()),
+                ("credit", row.credit.into()),
+            ])
+        });
+        object([
+            ("rows", array(rows)),
+            ("total_debit", value.total_debit.into()),
+            ("total_credit", value.total_credit.into()),
+            ("balanced", value.is_balanced().into()),
+        ])
+    }
+}
+
+impl From<&DayClose> for Json {
+    fn from(value: &DayClose) -> Self {
+        let totals = &value.totals;
+        object([
+            ("date", value.date.into()),
+            ("operations", totals.operations.into()),
+            ("deposits", totals.deposits.into()),
+            ("withdrawals", totals.withdrawals.into()),
+            ("transfers", totals.transfers.into()),
+            ("fees", totals.fees.into()),
+            ("interest", totals.interest.into()),
+            ("balances", array(&value.balances)),
+            ("total", value.total_balance().into()),
+        ])
+    }
+}
+
+/// Номер операции считается с единицы, как в текстовом выводе
+impl From<&Violation> for Json {
+    fn from(value: &Violation) -> Self {
+        object([
+            ("index", (value.index + 1).into()),
+            ("records", array(&value.records)),
+            ("invariant", value.invariant.to_string().into()),
+        ])
+    }
+}
+
+/// Настройки в том же виде, что и в файле: значения - строки
+impl From<&Config> for Json {
+    fn from(value: &Config) -> Self {
+        let mut fields = vec![("profile".to_owned(), value.profile.as_ref().into())];
+        for key in config::KEYS {
+            fields.push(((*key).to_owned(), value.get(key).into()));
+        }
+        Json::Object(fields)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analytics::Report;
+    use crate::storage::bank;
+    use crate::transaction::{Deposit, Transaction, Transfer, Withdraw};
+
+    #[test]
+    fn test_escaping_and_numbers() {
+        let value = object([
+            ("text", "a\"b\\c\nd\u{1}я".into()),
+            ("float", 1.5.into()),
+            ("nan", f64::NAN.into()),
+            ("none", Option::<i64>::None.into()),
+            ("list", array([1i64, -2])),
+        ]);
+        assert_eq!(
+            value.to_string(),
+            r#"{"text":"a\"b\\c\nd\u0001я","float":1.5,"nan":null,"none":null,"list":[1,-2]}"#
+        );
+    }
+
+    /// Схема ответов утилит в режиме `--format json`. Поля и их порядок - часть
+    /// контракта со скриптами, менять их можно только с новой версией схемы
+    #[test]
+    fn test_schema() {
+        let ok = success(
+            "balance",
+            object([
+                ("account", "Alice".into()),
+                ("balance", Balance::new(40).into()),
+            ]),
+        );
+        assert_eq!(
+            ok.to_string(),
+            r#"{"ok":true,"command":"balance","result":{"account":"Alice","balance":40}}"#
+        );
+
+        let err = BankError::FundsLimit {
+            account: "Alice".to_owned(),
+            requested: Balance::new(100),
+            available: Balance::new(40),
+        };
+        assert_eq!(
+            failure("withdraw", &err, Lang::En).to_string(),
+            r#"{"ok":false,"command":"withdraw","error":{"code":1002,"name":"insufficient_funds","message":"Not enough money on Alice: requested 100, available 40"}}"#
+        );
+
+        let mut storage = Storage::new();
+        storage.add_user("Alice".to_owned());
+        storage.set_time(Timestamp::new(86_400));
+        Deposit::new("Alice", Balance::new(40))
+            .apply(&mut storage)
+            .unwrap();
+        let record = &storage.history().records()[0];
+        assert_eq!(
+            Json::from(record).to_string(),
+            r#"{"id":1,"time":86400,"date":"1970-01-02","tx":"deposit,Alice,40","accounts":["Alice"],"amount":40}"#
+        );
+
+        let report = Report::new(&storage, 1, &[50], &[0]);
+        assert_eq!(
+            Json::from(&report).to_string(),
+            concat!(
+                r#"{"top":[{"account":"Alice","balance":40}],"bottom":[{"account":"Alice","balance":40}],"#,
+                r#""stats":{"count":1,"total":40,"mean":40,"median":40,"min":40,"max":40,"#,
+                r#""percentiles":[{"p":50,"value":40}],"zero":0,"negative":0},"#,
+                r#""histogram":[{"from":null,"to":0,"count":0},{"from":0,"to":null,"count":1}]}"#
+            )
+        );
+    }
+
+    /// Ответы deposit, withdraw и transfer в cli и utils, в том числе отложенные на проверку
+    #[test]
+    fn test_operation_schemas() {
+        let cases: [(TxKind, &str, &str); 3] = [
+            (
+                Deposit::new("Alice", Balance::new(5)).into(),
+                "deposit,Alice,5",
+                r#"[{"account":"Alice","balance":105}]"#,
+            ),
+            (
+                Withdraw::new("Alice", Balance::new(5)).into(),
+                "withdraw,Alice,5",
+                r#"[{"account":"Alice","balance":95}]"#,
+            ),
+            (
+                Transfer::new("Alice", "Bob", Balance::new(5)).into(),
+                "transfer,Alice,Bob,5",
+                r#"[{"account":"Alice","balance":95},{"account":"Bob","balance":105}]"#,
+            ),
+        ];
+        for (tx, text, balances) in cases {
+            let mut storage = bank(&[("Alice", 100), ("Bob", 100)]);
+            tx.apply(&mut storage).unwrap();
+            assert_eq!(
+                applied(&tx, &storage).to_string(),
+                format!(r#"{{"status":"applied","tx":"{text}","balances":{balances}}}"#)
+            );
+            assert_eq!(
+                queued(&tx, 3, "round trip").to_string(),
+                format!(r#"{{"status":"queued","tx":"{text}","review":3,"reason":"round trip"}}"#)
+            );
+        }
+    }
+
+    /// Ответ help в cli и utils
+    #[test]
+    fn test_help_schema() {
+        assert_eq!(
+            help([("balance", "balance <name>", "show balance")]).to_string(),
+            r#"{"commands":[{"name":"balance","usage":"balance <name>","description":"show balance"}]}"#
+        );
+    }
+}
@@ -0,0 +1,358 @@
+use super::chart::AccountType;
+use super::errors::BankError;
+use super::locale::{self, Lang};
+use super::storage::{Balance, Name};
+use super::time::Timestamp;
+use super::transaction::TxKind;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+pub type EntryId = u64;
+
+/// Счёт главной книги: счёт клиента или внутренний счёт банка
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum LedgerAccount {
+    /// Деньги клиента - обязательство банка перед ним
+    Customer(Name),
+    /// Наличные и корреспондентские счета банка
+    Cash,
+    /// Доход от комиссий
+    FeeIncome,
+    /// Расходы на выплату процентов
+    InterestExpense,
+}
+
+impl LedgerAccount {
+    pub fn account_type(&self) -> AccountType {
+        match self {
+            LedgerAccount::Customer(_) => AccountType::Liability,
+            LedgerAccount::Cash => AccountType::Asset,
+            LedgerAccount::FeeIncome => AccountType::Income,
+            LedgerAccount::InterestExpense => AccountType::Expense,
+        }
+    }
+}
+
+impl Display for LedgerAccount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LedgerAccount::Customer(name) => write!(f, "customer:{name}"),
+            LedgerAccount::Cash => write!(f, "cash"),
+            LedgerAccount::FeeIncome => write!(f, "fee_income"),
+            LedgerAccount::InterestExpense => write!(f, "interest_expense"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Side {
+    Debit,
+    Credit,
+}
+
+/// Одна проводка: дебет или кредит счёта на сумму
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Posting {
+    account: LedgerAccount,
+    side: Side,
+    amount: Balance,
+}
+
+impl Posting {
+    pub fn debit(account: LedgerAccount, amount: Balance) -> Self {
+        Self {
+            account,
+            side: Side::Debit,
+            amount,
+        }
+    }
+
+    pub fn credit(account: LedgerAccount, amount: Balance) -> Self {
+        Self {
+            account,
+            side: Side::Credit,
+            amount,
+        }
+    }
+
+    pub fn account(&self) -> &LedgerAccount {
+        &self.account
+    }
+
+    pub fn side(&self) -> Side {
+        self.side
+    }
+
+    pub fn amount(&self) -> Balance {
+        self.amount
+    }
+}
+
+/// Запись журнала: набор проводок, где сумма дебетов равна сумме кредитов
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JournalEntry {
+    id: EntryId,
+    time: Timestamp,
+    memo: String,
+    postings: Vec<Posting>,
+}
+
+impl JournalEntry {
+    pub fn id(&self) -> EntryId {
+        self.id
+    }
+
+    pub fn time(&self) -> Timestamp {
+        self.time
+    }
+
+    pub fn memo(&self) -> &str {
+        &self.memo
+    }
+
+    pub fn postings(&self) -> &[Posting] {
+        &self.postings
+    }
+}
+
+/// Обороты и сальдо одного счёта в оборотной ведомости
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TrialBalanceRow {
+    pub account: LedgerAccount,
+    pub debit: Balance,
+    pub credit: Balance,
+}
+
+/// Оборотная ведомость: доказывает, что дебеты равны кредитам
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TrialBalance {
+    pub rows: Vec<TrialBalanceRow>,
+    pub total_debit: Balance,
+    pub total_credit: Balance,
+}
+
+impl TrialBalance {
+    pub fn is_balanced(&self) -> bool {
+        self.total_debit == self.total_credit
+    }
+}
+
+impl TrialBalance {
+    /// Ведомость с заголовками на выбранном языке
+    pub fn message(&self, lang: Lang) -> String {
+        let row = |account: &dyn Display, debit: &dyn Display, credit: &dyn Display| {
+            format!(
+                "{:<30} {:>15} {:>15}",
+                account.to_string(),
+                debit.to_string(),
+                credit.to_string()
+            )
+        };
+        let mut lines = vec![row(
+            &locale::text(lang, "trial.account"),
+            &locale::text(lang, "trial.debit"),
+            &locale::text(lang, "trial.credit"),
+        )];
+        for r in &self.rows {
+            lines.push(row(&r.account, &r.debit, &r.credit));
+        }
+        lines.push(row(
+            &locale::text(lang, "trial.total"),
+            &self.total_debit,
+            &self.total_credit,
+        ));
+        lines.join("\n")
+    }
+}
+
+impl Display for TrialBalance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message(Lang::En))
+    }
+}
+
+/// Главная книга с двойной записью
+#[derive(Clone, Default, Debug)]
+pub struct Ledger {
+    entries: Vec<JournalEntry>,
+    next_id: EntryId,
+    /// Обороты по счетам: (дебет, кредит)
+    turnover: BTreeMap<LedgerAccount, (Balance, Balance)>,
+}
+
+impl Ledger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Проводит запись. Несбалансированная запись не проводится
+    pub fn post(
+        &mut self,
+        time: Timestamp,
+        memo: &str,
+        postings: Vec<Posting>,
+    ) -> Result<EntryId, BankError> {
+        let mut debit = Balance::default();
+        let mut credit = Balance::default();
+        for posting in &postings {
+            match posting.side {
+                Side::Debit => debit += posting.amount,
+                Side::Credit => credit += posting.amount,
+            }
+        }
+        if debit != credit {
+            return Err(BankError::UnbalancedEntry(memo.to_owned()));
+        }
+
+        for posting in &postings {
+            let turnover = self.turnover.entry(posting.account.clone()).or_default();
+            match posting.side {
+                Side::Debit => turnover.0 += posting.amount,
+                Side::Credit => turnover.1 += posting.amount,
+            }
+        }
+        self.next_id += 1;
+        self.entries.push(JournalEntry {
+            id: self.next_id,
+            time,
+            memo: memo.to_owned(),
+            postings,
+        });
+        Ok(self.next_id)
+    }
+
+    /// Отменяет записи после первых `len`, вместе с их оборотами
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.entries.len() {
+            return;
+        }
+        for entry in self.entries.drain(len..) {
+            for posting in &entry.postings {
+                if let Some(turnover) = self.turnover.get_mut(&posting.account) {
+                    match posting.side {
+                        Side::Debit => turnover.0 -= posting.amount,
+                        Side::Credit => turnover.1 -= posting.amount,
+                    }
+                }
+            }
+            self.next_id -= 1;
+        }
+    }
+
+    /// Проводки для операции клиента
+    pub fn postings_for(tx: &TxKind) -> Vec<Posting> {
+        let customer = |name: &Name| LedgerAccount::Customer(name.clone());
+        let amount = tx.amount();
+        match tx {
+            TxKind::Deposit(tx) => vec![
+                Posting::debit(LedgerAccount::Cash, amount),
+                Posting::credit(customer(tx.account()), amount),
+            ],
+            TxKind::Withdraw(tx) => vec![
+                Posting::debit(customer(tx.account()), amount),
+                Posting::credit(LedgerAccount::Cash, amount),
+            ],
+            TxKind::Transfer(tx) => vec![
+                Posting::debit(customer(tx.from()), amount),
+                Posting::credit(customer(tx.to()), amount),
+            ],
+            TxKind::Fee(tx) => vec![
+                Posting::debit(customer(tx.account()), amount),
+                Posting::credit(LedgerAccount::FeeIncome, amount),
+            ],
+            TxKind::Interest(tx) => vec![
+                Posting::debit(LedgerAccount::InterestExpense, amount),
+                Posting::credit(customer(tx.account()), amount),
+            ],
+        }
+    }
+
+    pub fn post_tx(&mut self, time: Timestamp, tx: &TxKind) -> Result<EntryId, BankError> {
+        self.post(time, &tx.to_string(), Self::postings_for(tx))
+    }
+
+    pub fn entries(&self) -> &[JournalEntry] {
+        &self.entries
+    }
+
+    /// Сальдо счёта в его естественную сторону: для активов и расходов это
+    /// дебет минус кредит, для остальных - кредит минус дебет
+    pub fn balance(&self, account: &LedgerAccount) -> Balance {
+        let (debit, credit) = self.turnover.get(account).copied().unwrap_or_default();
+        if account.account_type().is_debit_normal() {
+            Balance::new(debit.value() - credit.value())
+        } else {
+            Balance::new(credit.value() - debit.value())
+        }
+    }
+
+    /// Все счета, по которым были обороты
+    pub fn accounts(&self) -> impl Iterator<Item = &LedgerAccount> {
+        self.turnover.keys()
+    }
+
+    pub fn trial_balance(&self) -> TrialBalance {
+        let mut total_debit = Balance::default();
+        let mut total_credit = Balance::default();
+        let rows = self
+            .turnover
+            .iter()
+            .map(|(account, (debit, credit))| {
+                total_debit += *debit;
+                total_credit += *credit;
+                TrialBalanceRow {
+                    account: account.clone(),
+                    debit: *debit,
+                    credit: *credit,
+                }
+            })
+            .collect();
+        TrialBalance {
+            rows,
+            total_debit,
+            total_credit,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::{Deposit, Fee, Interest, Transfer};
+
+    #[test]
+    fn test_unbalanced_entry_is_rejected() {
+        let mut ledger = Ledger::new();
+        let res = ledger.post(
+            Timestamp::default(),
+            "broken",
+            vec![Posting::debit(LedgerAccount::Cash, Balance::new(10))],
+        );
+        assert!(res.is_err());
+        assert!(ledger.entries().is_empty());
+    }
+
+    #[test]
+    fn test_trial_balance() {
+        let mut ledger = Ledger::new();
+        let time = Timestamp::default();
+        let txs: Vec<TxKind> = vec![
+            Deposit::new("Alice", Balance::new(100)).into(),
+            Transfer::new("Alice", "Bob", Balance::new(30)).into(),
+            Fee::new("Bob", Balance::new(5)).into(),
+            Interest::new("Alice", Balance::new(2)).into(),
+        ];
+        for tx in &txs {
+            ledger.post_tx(time, tx).unwrap();
+        }
+
+        let trial = ledger.trial_balance();
+        assert!(trial.is_balanced());
+        assert_eq!(trial.total_debit, Balance::new(137));
+        let alice = LedgerAccount::Customer("Alice".to_string());
+        assert_eq!(ledger.balance(&alice), Balance::new(72));
+        assert_eq!(ledger.balance(&LedgerAccount::FeeIncome), Balance::new(5));
+        assert_eq!(ledger.balance(&LedgerAccount::Cash), Balance::new(100));
+        assert!(trial.to_string().contains("fee_income"));
+    }
+}
@@ -1,4 +1,22 @@
 pub mod analytics;
+pub mod chart;
+pub mod close;
+pub mod command;
+pub mod config;
 pub mod errors;
+pub mod history;
+pub mod invariants;
+pub mod json;
+pub mod ledger;
+pub mod limits;
+pub mod locale;
+pub mod reconcile;
+pub mod repl;
+pub mod scheduler;
+pub mod screening;
+pub mod script;
 pub mod storage;
+pub mod time;
+pub mod timeseries;
 pub mod transaction;
+pub mod tui;
@@ -0,0 +1,159 @@
+use super::errors::BankError;
+use super::history::History;
+use super::locale::{self, Lang};
+use super::storage::{Balance, Name};
+use super::time::Timestamp;
+use super::transaction::TxKind;
+use std::collections::HashMap;
+use std::fmt::Display;
+
+/// Ограничение на операции по счёту. Периоды задаются в секундах
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Rule {
+    /// Сумма снятий за период не больше `amount`
+    MaxWithdrawn { amount: Balance, period: i64 },
+    /// Исходящих переводов за период не больше `count`
+    MaxTransfers { count: u32, period: i64 },
+    /// Один перевод не больше указанной суммы
+    MaxSingleTransfer(Balance),
+}
+
+impl Rule {
+    /// Проверяет, что операция `tx` со счёта `account` не нарушит правило.
+    /// Счётчики считаются по журналу операций, поэтому переживают перезапуск
+    fn check(
+        &self,
+        account: &Name,
+        tx: &TxKind,
+        history: &History,
+        now: Timestamp,
+    ) -> Result<(), BankError> {
+        let breached = match (self, tx) {
+            (Rule::MaxWithdrawn { amount, period }, TxKind::Withdraw(tx)) => {
+                let mut total = tx.amount();
+                for record in history.for_account_between(account, now - *period, now) {
+                    if let TxKind::Withdraw(prev) = record.tx()
+                        && prev.account() == account
+                    {
+                        total += prev.amount();
+                    }
+                }
+                total > *amount
+            }
+            (Rule::MaxTransfers { count, period }, TxKind::Transfer(_)) => {
+                let done = history
+                    .for_account_between(account, now - *period, now)
+                    .filter(|r| matches!(r.tx(), TxKind::Transfer(prev) if prev.from() == account))
+                    .count();
+                done + 1 > *count as usize
+            }
+            (Rule::MaxSingleTransfer(limit), TxKind::Transfer(tx)) => tx.amount() > *limit,
+            _ => false,
+        };
+
+        if breached {
+            Err(BankError::LimitExceeded {
+                account: account.clone(),
+                rule: self.clone(),
+            })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Rule {
+    /// Описание правила на выбранном языке
+    pub fn message(&self, lang: Lang) -> String {
+        match self {
+            Rule::MaxWithdrawn { amount, period } => locale::format(
+                lang,
+                "rule.max_withdrawn",
+                &[("amount", amount), ("period", period)],
+            ),
+            Rule::MaxTransfers { count, period } => locale::format(
+                lang,
+                "rule.max_transfers",
+                &[("count", count), ("period", period)],
+            ),
+            Rule::MaxSingleTransfer(amount) => {
+                locale::format(lang, "rule.max_single_transfer", &[("amount", amount)])
+            }
+        }
+    }
+}
+
+impl Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message(Lang::En))
+    }
+}
+
+/// Лимиты, заданные для всех счетов, для отдельных счетов и для классов счетов
+#[derive(Clone, Default, Debug)]
+pub struct Limits {
+    rules: Vec<Rule>,
+    account_rules: HashMap<Name, Vec<Rule>>,
+    class_rules: HashMap<String, Vec<Rule>>,
+    classes: HashMap<Name, String>,
+}
+
+impl Limits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Правило для всех счетов банка
+    pub fn add_rule(&mut self, rule: Rule) {
+        self.rules.push(rule);
+    }
+
+    pub fn add_account_rule(&mut self, account: &Name, rule: Rule) {
+        self.account_rules
+            .entry(account.clone())
+            .or_default()
+            .push(rule);
+    }
+
+    pub fn add_class_rule(&mut self, class: &str, rule: Rule) {
+        self.class_rules
+            .entry(class.to_owned())
+            .or_default()
+            .push(rule);
+    }
+
+    /// Относит счёт к классу, правила класса начинают действовать на счёт
+    pub fn set_class(&mut self, account: &Name, class: &str) {
+        self.classes.insert(account.clone(), class.to_owned());
+    }
+
+    pub fn class_of(&self, account: &Name) -> Option<&String> {
+        self.classes.get(account)
+    }
+
+    /// Все правила, действующие на счёт: общие, собственные и правила его класса
+    pub fn rules_for<'a>(&'a self, account: &Name) -> impl Iterator<Item = &'a Rule> {
+        let own = self.account_rules.get(account).into_iter().flatten();
+        let class = self
+            .classes
+            .get(account)
+            .and_then(|class| self.class_rules.get(class))
+            .into_iter()
+            .flatten();
+        self.rules.iter().chain(own).chain(class)
+    }
+
+    /// Проверяет операцию по всем правилам счёта, с которого списываются деньги
+    pub fn check(&self, tx: &TxKind, history: &History, now: Timestamp) -> Result<(), BankError> {
+        let account = match tx {
+            TxKind::Withdraw(tx) => tx.account(),
+            TxKind::Transfer(tx) => tx.from(),
+            // Зачисления и операции самого банка клиентскими лимитами не ограничиваются
+            TxKind::Deposit(_) | TxKind::Fee(_) | TxKind::Interest(_) => return Ok(()),
+        };
+        for rule in self.rules_for(account) {
+            rule.check(account, tx, history, now)?;
+        }
+        Ok(())
+    }
+}
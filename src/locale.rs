@@ -0,0 +1,832 @@
+use super::errors::BankError;
+use std::env;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// Язык сообщений для пользователя
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Lang {
+    #[default]
+    En,
+    Ru,
+}
+
+impl Lang {
+    pub const ALL: [Lang; 2] = [Lang::En, Lang::Ru];
+
+    /// Язык из окружения: сначала BANK_LANG, затем LC_ALL, LC_MESSAGES и LANG.
+    /// None, если ни одна переменная не задаёт известный язык
+    pub fn from_env() -> Option<Lang> {
+        ["BANK_LANG", "LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| env::var(var).ok())
+            .find_map(|value| value.parse().ok())
+    }
+
+    fn catalog(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Lang::En => EN,
+            Lang::Ru => RU,
+        }
+    }
+}
+
+impl Display for Lang {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Lang::En => write!(f, "en"),
+            Lang::Ru => write!(f, "ru"),
+        }
+    }
+}
+
+impl FromStr for Lang {
+    type Err = BankError;
+
+    /// Принимает "en", "ru" и локали вида "ru_RU.UTF-8"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let code = s.trim().to_lowercase();
+        match code.get(..2) {
+            Some("en") => Ok(Lang::En),
+            Some("ru") => Ok(Lang::Ru),
+            _ => Err(BankError::InvalidFormat(format!("unknown language: {s}"))),
+        }
+    }
+}
+
+/// Шаблон сообщения. Если ключа нет в каталоге языка, берётся английский,
+/// а если нет и там - сам ключ
+pub fn text(lang: Lang, key: &str) -> &str {
+    [lang, Lang::En]
+        .iter()
+        .find_map(|lang| {
+            lang.catalog()
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| *v)
+        })
+        .unwrap_or(key)
+}
+
+/// Сообщение с подстановкой аргументов вместо `{имя}` в шаблоне
+pub fn format(lang: Lang, key: &str, args: &[(&str, &dyn Display)]) -> String {
+    let mut message = text(lang, key).to_owned();
+    for (name, value) in args {
+        message = message.replace(&format!("{{{name}}}"), &value.to_string());
+    }
+    message
+}
+
+/// Все ключи каталога языка
+pub fn keys(lang: Lang) -> impl Iterator<Item = &'static str> {
+    lang.catalog().iter().map(|(k, _)| *k)
+}
+
+const EN: &[(&str, &str)] = &[
+    // Ошибки, ключ - строковый код ошибки
+    ("error.user_not_found", "User {name} not found"),
+    ("error.user_exists", "User {name} already exists"),
+    (
+        "error.insufficient_funds",
+        "Not enough money on {account}: requested {requested}, available {available}",
+    ),
+    ("error.hold_not_found", "Hold #{id} not found"),
+    ("error.review_not_found", "Review item #{id} not found"),
+    ("error.blocked", "Transaction blocked: {reason}"),
+    ("error.unbalanced_entry", "Unbalanced ledger entry: {memo}"),
+    ("error.chart_node_not_found", "Chart node {code} not found"),
+    (
+        "error.chart_node_exists",
+        "Chart node {code} already exists",
+    ),
+    (
+        "error.chart_type_mismatch",
+        "Chart node {code} must have the same type as {parent}",
+    ),
+    ("error.period_closed", "Period is closed up to {date}"),
+    (
+        "error.limit_exceeded",
+        "Limit exceeded for {account}: {rule}",
+    ),
+    (
+        "error.condition_not_met",
+        "Condition not met: {description}",
+    ),
+    ("error.io_error", "I/O error: {error}"),
+    ("error.parse_error", "Invalid number: {error}"),
+    ("error.invalid_format", "Invalid format: {description}"),
+    ("error.invalid_argument", "{description}"),
+    ("error.data_not_found", "Bank data file {path} not found"),
+    ("error.data_exists", "Bank data already exists: {path}"),
+    ("error.file", "{path}: {error}"),
+    ("error.file_line", "{path}, line {line}: {error}"),
+    // Общие сообщения утилит
+    ("repl.title", "=== Bank CLI Utils ==="),
+    ("repl.commands", "Commands:"),
+    ("repl.example", "Example: {example}"),
+    ("tui.title", "Bank dashboard"),
+    (
+        "tui.summary",
+        "Accounts: {count}, total: {total} {currency}",
+    ),
+    ("tui.top", "Top:"),
+    ("tui.no_accounts", "No accounts"),
+    ("tui.history", "History:"),
+    ("tui.no_history", "No operations"),
+    ("tui.form", "Transfer from {from} to {to} amount {amount}"),
+    (
+        "tui.keys",
+        "Up/Down - account, Tab - transfer form, Enter - send, Esc - back, q - quit",
+    ),
+    ("tui.too_small", "Terminal too small"),
+    (
+        "tui.unavailable",
+        "The dashboard needs an interactive terminal",
+    ),
+    ("repl.unknown_command", "Unknown command"),
+    (
+        "repl.unknown_hint",
+        "Unknown command {command}, type help to list commands",
+    ),
+    (
+        "repl.hint",
+        "help <command> - details, Tab - completion, Up/Down - history",
+    ),
+    (
+        "repl.history_failed",
+        "Command history unavailable: {error}",
+    ),
+    ("repl.input_failed", "Cannot read commands: {error}"),
+    ("repl.bye", "Exiting CLI, all changes saved."),
+    ("repl.error", "Error: {error}"),
+    (
+        "repl.usage",
+        "Usage: utils [--format text|json] [--config <file>] [--profile <name>] [--set key=value]... [--create [--seed <file>]] [--tui] [--script <file> [--on-error stop|continue|atomic] [--var name=value]...]",
+    ),
+    (
+        "repl.init_hint",
+        "To create a new bank: utils --create [--seed <file>]",
+    ),
+    ("script.load_failed", "Cannot read script: {error}"),
+    (
+        "script.summary",
+        "Script {file}: {succeeded} of {total} commands succeeded, {failed} failed, {skipped} skipped",
+    ),
+    ("script.failed", "  line {line}: {command}: {error}"),
+    ("script.rolled_back", "Script rolled back, no changes saved"),
+    ("cli.usage", "Usage:"),
+    (
+        "cli.options",
+        "Options for any command: --format text|json, --config <file>, --profile <name>, --set key=value",
+    ),
+    ("cli.deposited", "Deposited {amount} to {name}"),
+    ("cli.withdrawn", "Withdrawn {amount} from {name}"),
+    ("cli.balance", "Balance of {name}: {balance} {currency}"),
+    (
+        "cli.created",
+        "Account {name} created with balance {balance}",
+    ),
+    (
+        "cli.transferred",
+        "Transferred: {amount} from {from} to {to}",
+    ),
+    (
+        "cli.accounts_total",
+        "Accounts: {count}, total: {total} {currency}",
+    ),
+    ("cli.no_history", "No operations"),
+    (
+        "cli.initialized",
+        "Bank created in {path}, accounts: {count}",
+    ),
+    (
+        "cli.init_hint",
+        "To create a new bank: init [--seed <file>]",
+    ),
+    ("cli.imported", "Imported: {count}"),
+    ("cli.import_queued", "Sent for review: {count}"),
+    (
+        "cli.import_failed",
+        "Operation {n} failed, nothing imported: {error}",
+    ),
+    ("cli.bad_option", "Unknown option: {option}"),
+    ("cli.bad_value", "Invalid value of {option}: {value}"),
+    (
+        "cli.help_hint",
+        "Details: help <command>. Exit codes: 0 - success, 1 - operation failed, 2 - invalid arguments",
+    ),
+    ("load.data_failed", "Cannot load data: {error}"),
+    ("load.queue_failed", "Cannot load review queue: {error}"),
+    ("load.config_failed", "Cannot load settings: {error}"),
+    ("save.data_failed", "Cannot save data: {error}"),
+    ("save.queue_failed", "Cannot save review queue: {error}"),
+    ("save.report_failed", "Cannot save report: {error}"),
+    ("export.done", "Exported to {file}"),
+    ("input.amount_nan", "Amount must be a number"),
+    ("input.amount_not_positive", "Amount must be positive"),
+    ("input.balance_negative", "Balance cannot be negative"),
+    ("input.bad_date", "Date must be in YYYY-MM-DD format"),
+    ("input.bad_id", "Operation number must be a number"),
+    ("input.bad_period", "Period: day, week or month"),
+    // Справка по командам
+    ("help.add", "add a user"),
+    ("help.remove", "remove a user"),
+    ("help.deposit", "deposit money"),
+    ("help.withdraw", "withdraw money"),
+    ("help.transfer", "transfer money"),
+    ("help.balance", "show balance"),
+    ("help.combined", "deposit and transfer as one operation"),
+    ("help.report", "balance analytics"),
+    ("help.flows", "flows per period to CSV"),
+    ("help.running", "account balance history to CSV"),
+    ("help.adb", "average daily balance to CSV"),
+    ("help.concentration", "concentration of money"),
+    ("help.network", "transfer network analysis"),
+    ("help.trial", "trial balance"),
+    ("help.close", "close the business day"),
+    ("help.review", "operations awaiting review"),
+    ("help.approve", "approve an operation"),
+    ("help.reject", "reject an operation"),
+    ("help.reconcile", "reconcile with a bank statement"),
+    ("help.confirm", "match statement line n with record id"),
+    ("help.verify", "check money conservation"),
+    ("help.exit", "exit"),
+    ("help.create", "open an account"),
+    ("help.list", "list accounts"),
+    ("help.history", "operation history"),
+    (
+        "help.import",
+        "import accounts or operations (all or nothing)",
+    ),
+    ("help.export", "export accounts or operations"),
+    ("help.help", "help on commands"),
+    (
+        "help.init",
+        "create a new bank, optionally with accounts from a Name,Balance file",
+    ),
+    ("help.config", "show the effective settings"),
+    // Счета и операции
+    ("user.added", "User {name} added with balance {balance}"),
+    ("user.exists", "User {name} already exists"),
+    ("user.removed", "User {name} removed"),
+    ("user.not_found", "User {name} not found"),
+    ("user.balance", "Balance of {name} = {balance} {currency}"),
+    ("tx.deposit_done", "Transaction: deposit {amount} to {name}"),
+    (
+        "tx.withdraw_done",
+        "Transaction: withdrawal {amount} from {name}",
+    ),
+    (
+        "tx.transfer_done",
+        "Transaction: transfer {amount} from {from} to {to}",
+    ),
+    ("tx.combined_done", "Transactions applied!"),
+    ("tx.failed", "Transaction error: {error}"),
+    ("review.queued", "Operation #{id} sent for review: {reason}"),
+    ("review.empty", "No operations awaiting review"),
+    ("review.processed", "Operation #{id} processed"),
+    ("trial.unbalanced", "Debit does not equal credit!"),
+    (
+        "trial.mismatch",
+        "Mismatch {name}: balance {balance}, ledger {booked}",
+    ),
+    ("network.groups", "Circular groups:"),
+    ("network.fan_out", "Most recipients:"),
+    ("network.fan_in", "Most senders:"),
+    ("network.counterparties", "Counterparties of {name}:"),
+    ("network.net_flow", "Net flow {from} -> {to}: {net}"),
+    (
+        "reconcile.statement_failed",
+        "Cannot load statement: {error}",
+    ),
+    ("reconcile.matches_failed", "Cannot load matches: {error}"),
+    (
+        "reconcile.matches_save_failed",
+        "Cannot save matches: {error}",
+    ),
+    ("reconcile.matched", "Matched: {count}"),
+    (
+        "reconcile.unmatched_ours",
+        "Missing from statement: {count}",
+    ),
+    ("reconcile.unmatched_theirs", "Missing on our side: {count}"),
+    (
+        "reconcile.bad_numbers",
+        "Line and record numbers must be numbers",
+    ),
+    ("reconcile.no_line", "Statement has no line {n}"),
+    ("reconcile.confirmed", "Line {n} matched with record #{id}"),
+    ("verify.snapshot_failed", "Cannot load snapshot: {error}"),
+    ("verify.txs_failed", "Cannot load operations: {error}"),
+    ("verify.ok", "Operations checked: {count}, no violations"),
+    ("verify.violation", "Violation: {violation}"),
+    // Отчёты
+    ("report.no_accounts", "No accounts"),
+    ("report.accounts", "accounts: {value}"),
+    ("report.total", "total: {value}"),
+    ("report.mean", "mean: {value}"),
+    ("report.median", "median: {value}"),
+    ("report.min", "min: {value}"),
+    ("report.max", "max: {value}"),
+    ("report.zero", "zero: {value}"),
+    ("report.negative", "negative: {value}"),
+    ("report.top", "top:"),
+    ("report.bottom", "bottom:"),
+    ("report.histogram", "histogram:"),
+    ("report.all", "all"),
+    ("concentration.gini", "gini: {value}"),
+    ("concentration.top1", "top 1% share: {value}"),
+    ("concentration.top10", "top 10% share: {value}"),
+    ("concentration.herfindahl", "herfindahl: {value}"),
+    ("concentration.lorenz", "lorenz:"),
+    ("trial.account", "account"),
+    ("trial.debit", "debit"),
+    ("trial.credit", "credit"),
+    ("trial.total", "total"),
+    ("close.title", "Closing report {value}"),
+    ("close.operations", "operations: {value}"),
+    ("close.deposits", "deposits: {value}"),
+    ("close.withdrawals", "withdrawals: {value}"),
+    ("close.transfers", "transfers: {value}"),
+    ("close.fees", "fees: {value}"),
+    ("close.interest", "interest: {value}"),
+    ("close.balances", "balances:"),
+    ("close.total", "total: {value}"),
+    ("invariant.not_applicable", "cannot be applied: {error}"),
+    (
+        "invariant.conservation",
+        "total changed by {actual}, expected {expected}",
+    ),
+    ("invariant.negative_balance", "negative balance of {name}"),
+    ("invariant.ledger_mismatch", "ledger mismatch for {name}"),
+    // Лимиты и проверка операций
+    ("rule.max_withdrawn", "max withdrawn {amount} per {period}s"),
+    ("rule.max_transfers", "max {count} transfers per {period}s"),
+    ("rule.max_single_transfer", "max single transfer {amount}"),
+    (
+        "screening.large_amount",
+        "amount {amount} of {account} is over {factor}x typical {typical}",
+    ),
+    (
+        "screening.fan_out",
+        "{from} transferred to {count} new counterparties in {period}s",
+    ),
+    (
+        "screening.round_trip",
+        "round trip between {from} and {to} in {period}s",
+    ),
+];
+
+const RU: &[(&str, &str)] = &[
+    ("error.user_not_found", "Пользователь {name} не найден"),
+    ("error.user_exists", "Пользователь {name} уже существует"),
+    (
+        "error.insufficient_funds",
+        "Недостаточно денег на счёте {account}: запрошено {requested}, доступно {available}",
+    ),
+    ("error.hold_not_found", "Блокировка #{id} не найдена"),
+    (
+        "error.review_not_found",
+        "Операция #{id} не найдена в очереди проверки",
+    ),
+    ("error.blocked", "Операция заблокирована: {reason}"),
+    (
+        "error.unbalanced_entry",
+        "Несбалансированная проводка: {memo}",
+    ),
+    (
+        "error.chart_node_not_found",
+        "Узел плана счетов {code} не найден",
+    ),
+    (
+        "error.chart_node_exists",
+        "Узел плана счетов {code} уже есть",
+    ),
+    (
+        "error.chart_type_mismatch",
+        "Узел плана счетов {code} должен быть того же типа, что и {parent}",
+    ),
+    ("error.period_closed", "Период закрыт по {date}"),
+    (
+        "error.limit_exceeded",
+        "Превышен лимит счёта {account}: {rule}",
+    ),
+    (
+        "error.condition_not_met",
+        "Условие не выполнено: {description}",
+    ),
+    ("error.io_error", "Ошибка ввода-вывода: {error}"),
+    ("error.parse_error", "Некорректное число: {error}"),
+    ("error.invalid_format", "Неверный формат: {description}"),
+    ("error.invalid_argument", "{description}"),
+    ("error.data_not_found", "Файл данных банка {path} не найден"),
+    ("error.data_exists", "Данные банка уже есть: {path}"),
+    ("error.file", "{path}: {error}"),
+    ("error.file_line", "{path}, строка {line}: {error}"),
+    ("repl.title", "=== Bank CLI Utils ==="),
+    ("repl.commands", "Команды:"),
+    ("repl.example", "Пример: {example}"),
+    ("tui.title", "Панель банка"),
+    ("tui.summary", "Счетов: {count}, всего: {total} {currency}"),
+    ("tui.top", "Крупнейшие:"),
+    ("tui.no_accounts", "Счетов нет"),
+    ("tui.history", "История:"),
+    ("tui.no_history", "Операций нет"),
+    ("tui.form", "Перевод со счёта {from} на {to} сумма {amount}"),
+    (
+        "tui.keys",
+        "вверх/вниз - счёт, Tab - форма перевода, Enter - отправить, Esc - назад, q - выход",
+    ),
+    ("tui.too_small", "Терминал слишком мал"),
+    ("tui.unavailable", "Для панели нужен интерактивный терминал"),
+    ("repl.unknown_command", "Неизвестная команда"),
+    (
+        "repl.unknown_hint",
+        "Неизвестная команда {command}, список команд - help",
+    ),
+    (
+        "repl.hint",
+        "help <команда> - подробнее, Tab - дополнение, вверх/вниз - история",
+    ),
+    ("repl.history_failed", "История команд недоступна: {error}"),
+    ("repl.input_failed", "Не удалось прочитать команды: {error}"),
+    ("repl.bye", "Выход из CLI, все изменения сохранены."),
+    ("repl.error", "Ошибка: {error}"),
+    (
+        "repl.usage",
+        "Использование: utils [--format text|json] [--config <файл>] [--profile <имя>] [--set ключ=значение]... [--create [--seed <файл>]] [--tui] [--script <файл> [--on-error stop|continue|atomic] [--var имя=значение]...]",
+    ),
+    (
+        "repl.init_hint",
+        "Создать новый банк: utils --create [--seed <файл>]",
+    ),
+    (
+        "script.load_failed",
+        "Невозможно прочитать сценарий: {error}",
+    ),
+    (
+        "script.summary",
+        "Сценарий {file}: успешно {succeeded} из {total} команд, с ошибкой {failed}, пропущено {skipped}",
+    ),
+    ("script.failed", "  строка {line}: {command}: {error}"),
+    (
+        "script.rolled_back",
+        "Сценарий отменён, изменения не сохранены",
+    ),
+    ("cli.usage", "Использование:"),
+    (
+        "cli.options",
+        "Параметры любой команды: --format text|json, --config <файл>, --profile <имя>, --set ключ=значение",
+    ),
+    ("cli.deposited", "Пополнено: {name} на {amount}"),
+    ("cli.withdrawn", "Снято: {name} на {amount}"),
+    ("cli.balance", "Баланс {name}: {balance} {currency}"),
+    ("cli.created", "Счёт {name} открыт с балансом {balance}"),
+    ("cli.transferred", "Переведено: {amount} от {from} на {to}"),
+    (
+        "cli.accounts_total",
+        "Счетов: {count}, всего: {total} {currency}",
+    ),
+    ("cli.no_history", "Операций нет"),
+    ("cli.initialized", "Банк создан в {path}, счетов: {count}"),
+    ("cli.init_hint", "Создать новый банк: init [--seed <файл>]"),
+    ("cli.imported", "Импортировано: {count}"),
+    ("cli.import_queued", "Отправлено на проверку: {count}"),
+    (
+        "cli.import_failed",
+        "Операция {n} не выполнена, ничего не импортировано: {error}",
+    ),
+    ("cli.bad_option", "Неизвестный параметр: {option}"),
+    ("cli.bad_value", "Неверное значение {option}: {value}"),
+    (
+        "cli.help_hint",
+        "Подробнее: help <команда>. Коды выхода: 0 - успех, 1 - операция не выполнена, 2 - неверные аргументы",
+    ),
+    ("load.data_failed", "Невозможно загрузить данные: {error}"),
+    (
+        "load.queue_failed",
+        "Невозможно загрузить очередь проверки: {error}",
+    ),
+    (
+        "load.config_failed",
+        "Невозможно загрузить настройки: {error}",
+    ),
+    ("save.data_failed", "Невозможно сохранить данные: {error}"),
+    (
+        "save.queue_failed",
+        "Невозможно сохранить очередь проверки: {error}",
+    ),
+    ("save.report_failed", "Невозможно сохранить отчёт: {error}"),
+    ("export.done", "Выгружено в {file}"),
+    ("input.amount_nan", "Сумма должна быть числом"),
+    (
+        "input.amount_not_positive",
+        "Сумма должна быть положительной",
+    ),
+    (
+        "input.balance_negative",
+        "Баланс не может быть отрицательным",
+    ),
+    ("input.bad_date", "Дата должна быть в формате YYYY-MM-DD"),
+    ("input.bad_id", "Номер операции должен быть числом"),
+    ("input.bad_period", "Период: day, week или month"),
+    ("help.add", "добавить пользователя"),
+    ("help.remove", "удалить пользователя"),
+    ("help.deposit", "пополнить баланс"),
+    ("help.withdraw", "снять со счёта"),
+    ("help.transfer", "перевести деньги"),
+    ("help.balance", "показать баланс"),
+    ("help.combined", "пополнение и перевод одной операцией"),
+    ("help.report", "аналитика по балансам"),
+    ("help.flows", "обороты по периодам в CSV"),
+    ("help.running", "история баланса счёта в CSV"),
+    ("help.adb", "средний дневной остаток в CSV"),
+    ("help.concentration", "концентрация денег на счетах"),
+    ("help.network", "анализ сети переводов"),
+    ("help.trial", "оборотная ведомость"),
+    ("help.close", "закрыть операционный день"),
+    ("help.review", "операции на проверке"),
+    ("help.approve", "одобрить операцию"),
+    ("help.reject", "отклонить операцию"),
+    ("help.reconcile", "сверить с выпиской банка"),
+    ("help.confirm", "сопоставить строку n выписки с записью id"),
+    ("help.verify", "проверить сохранение денег"),
+    ("help.exit", "выйти"),
+    ("help.create", "открыть счёт"),
+    ("help.list", "список счетов"),
+    ("help.history", "история операций"),
+    ("help.import", "импорт счетов или операций (всё или ничего)"),
+    ("help.export", "выгрузка счетов или операций"),
+    ("help.help", "справка по командам"),
+    (
+        "help.init",
+        "создать новый банк, можно со счетами из файла Name,Balance",
+    ),
+    ("help.config", "показать действующие настройки"),
+    (
+        "user.added",
+        "Пользователь {name} добавлен с балансом {balance}",
+    ),
+    ("user.exists", "Пользователь {name} уже существует"),
+    ("user.removed", "Пользователь {name} удалён"),
+    ("user.not_found", "Пользователь {name} не найден"),
+    (
+        "user.balance",
+        "Баланс пользователя {name} = {balance} {currency}",
+    ),
+    ("tx.deposit_done", "Транзакция: депозит {name} на {amount}"),
+    ("tx.withdraw_done", "Транзакция: снятие {amount} с {name}"),
+    (
+        "tx.transfer_done",
+        "Транзакция: перевод средств {amount} от {from} на {to}",
+    ),
+    ("tx.combined_done", "Транзакции выполнены!"),
+    ("tx.failed", "Ошибка транзакции: {error}"),
+    (
+        "review.queued",
+        "Операция #{id} отправлена на проверку: {reason}",
+    ),
+    ("review.empty", "Нет операций на проверке"),
+    ("review.processed", "Операция #{id} обработана"),
+    ("trial.unbalanced", "Дебет не равен кредиту!"),
+    (
+        "trial.mismatch",
+        "Расхождение {name}: баланс {balance}, в книге {booked}",
+    ),
+    ("network.groups", "Круговые группы:"),
+    ("network.fan_out", "Больше всего получателей:"),
+    ("network.fan_in", "Больше всего отправителей:"),
+    ("network.counterparties", "Контрагенты {name}:"),
+    ("network.net_flow", "Чистый поток {from} -> {to}: {net}"),
+    (
+        "reconcile.statement_failed",
+        "Невозможно загрузить выписку: {error}",
+    ),
+    (
+        "reconcile.matches_failed",
+        "Невозможно загрузить сопоставления: {error}",
+    ),
+    (
+        "reconcile.matches_save_failed",
+        "Невозможно сохранить сопоставления: {error}",
+    ),
+    ("reconcile.matched", "Сопоставлено: {count}"),
+    ("reconcile.unmatched_ours", "Нет в выписке: {count}"),
+    ("reconcile.unmatched_theirs", "Нет у нас: {count}"),
+    (
+        "reconcile.bad_numbers",
+        "Номер строки и записи должны быть числами",
+    ),
+    ("reconcile.no_line", "В выписке нет строки {n}"),
+    (
+        "reconcile.confirmed",
+        "Строка {n} сопоставлена с записью #{id}",
+    ),
+    (
+        "verify.snapshot_failed",
+        "Невозможно загрузить снимок: {error}",
+    ),
+    (
+        "verify.txs_failed",
+        "Невозможно загрузить операции: {error}",
+    ),
+    ("verify.ok", "Проверено операций: {count}, нарушений нет"),
+    ("verify.violation", "Нарушение: {violation}"),
+    // Отчёты
+    ("report.no_accounts", "Нет счетов"),
+    ("report.accounts", "счетов: {value}"),
+    ("report.total", "всего: {value}"),
+    ("report.mean", "среднее: {value}"),
+    ("report.median", "медиана: {value}"),
+    ("report.min", "минимум: {value}"),
+    ("report.max", "максимум: {value}"),
+    ("report.zero", "нулевых: {value}"),
+    ("report.negative", "отрицательных: {value}"),
+    ("report.top", "лидеры:"),
+    ("report.bottom", "отстающие:"),
+    ("report.histogram", "распределение:"),
+    ("report.all", "все"),
+    ("concentration.gini", "индекс Джини: {value}"),
+    ("concentration.top1", "доля верхнего 1%: {value}"),
+    ("concentration.top10", "доля верхних 10%: {value}"),
+    ("concentration.herfindahl", "индекс Херфиндаля: {value}"),
+    ("concentration.lorenz", "кривая Лоренца:"),
+    ("trial.account", "счёт"),
+    ("trial.debit", "дебет"),
+    ("trial.credit", "кредит"),
+    ("trial.total", "итого"),
+    ("close.title", "Закрытие дня {value}"),
+    ("close.operations", "операций: {value}"),
+    ("close.deposits", "зачисления: {value}"),
+    ("close.withdrawals", "снятия: {value}"),
+    ("close.transfers", "переводы: {value}"),
+    ("close.fees", "комиссии: {value}"),
+    ("close.interest", "проценты: {value}"),
+    ("close.balances", "балансы:"),
+    ("close.total", "итого: {value}"),
+    ("invariant.not_applicable", "не удалось провести: {error}"),
+    (
+        "invariant.conservation",
+        "сумма изменилась на {actual}, ожидалось {expected}",
+    ),
+    ("invariant.negative_balance", "отрицательный баланс {name}"),
+    (
+        "invariant.ledger_mismatch",
+        "баланс {name} расходится с главной книгой",
+    ),
+    // Лимиты и проверка операций
+    (
+        "rule.max_withdrawn",
+        "снятий не больше {amount} за {period} с",
+    ),
+    (
+        "rule.max_transfers",
+        "не больше {count} переводов за {period} с",
+    ),
+    ("rule.max_single_transfer", "перевод не больше {amount}"),
+    (
+        "screening.large_amount",
+        "сумма {amount} по счёту {account} больше типичной {typical} более чем в {factor} раз",
+    ),
+    (
+        "screening.fan_out",
+        "{from} перевёл новым получателям: {count} за {period} с",
+    ),
+    (
+        "screening.round_trip",
+        "встречный перевод между {from} и {to} за {period} с",
+    ),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    /// Имена подстановок в шаблоне
+    fn placeholders(template: &str) -> BTreeSet<&str> {
+        template
+            .split('{')
+            .skip(1)
+            .filter_map(|part| part.split_once('}').map(|(name, _)| name))
+            .collect()
+    }
+
+    #[test]
+    fn test_every_key_in_every_language() {
+        for lang in Lang::ALL {
+            for other in Lang::ALL {
+                for key in keys(lang) {
+                    assert!(
+                        keys(other).any(|k| k == key),
+                        "key {key} from {lang} is missing in {other}"
+                    );
+                    assert_eq!(
+                        placeholders(text(lang, key)),
+                        placeholders(text(other, key)),
+                        "placeholders of {key} differ between {lang} and {other}"
+                    );
+                }
+            }
+            let unique: BTreeSet<&str> = keys(lang).collect();
+            assert_eq!(unique.len(), keys(lang).count(), "duplicate keys in {lang}");
+        }
+    }
+
+    /// Отчёты и причины берут тексты из каталога: без ключа пользователь увидел
+    /// бы сам ключ, поэтому проверяем, что отрисовка на каждом языке его не содержит
+    #[test]
+    fn test_reports_use_catalog() {
+        use crate::analytics::{Concentration, Report};
+        use crate::close::{DailyTotals, DayClose};
+        use crate::invariants::Invariant;
+        use crate::limits::Rule;
+        use crate::screening::Reason;
+        use crate::storage::{Balance, Storage};
+        use crate::time::Date;
+
+        let mut storage = Storage::new();
+        storage.add_user("Alice".to_string());
+        storage
+            .deposit(&"Alice".to_string(), Balance::new(10))
+            .unwrap();
+        let close = DayClose {
+            date: "2024-05-20".parse::<Date>().unwrap(),
+            balances: vec![("Alice".to_string(), Balance::new(10))],
+            totals: DailyTotals::default(),
+        };
+        let name = "Alice".to_string();
+        for lang in Lang::ALL {
+            let rendered = [
+                Report::new(&storage, 1, &[50], &[]).message(lang),
+                Report::new(&Storage::new(), 1, &[], &[]).message(lang),
+                Concentration::new(&storage).message(lang),
+                storage.ledger().trial_balance().message(lang),
+                close.message(lang),
+                Invariant::Conservation {
+                    expected: 0,
+                    actual: 1,
+                }
+                .message(lang),
+                Invariant::NegativeBalance(name.clone()).message(lang),
+                Invariant::LedgerMismatch(name.clone()).message(lang),
+                Rule::MaxWithdrawn {
+                    amount: Balance::new(1),
+                    period: 1,
+                }
+                .message(lang),
+                Rule::MaxTransfers {
+                    count: 1,
+                    period: 1,
+                }
+                .message(lang),
+                Rule::MaxSingleTransfer(Balance::new(1)).message(lang),
+                Reason::LargeAmount {
+                    account: name.clone(),
+                    amount: 100,
+                    factor: 10,
+                    typical: 1,
+                }
+                .message(lang),
+                Reason::FanOut {
+                    from: name.clone(),
+                    count: 6,
+                    period: 1,
+                }
+                .message(lang),
+                Reason::RoundTrip {
+                    from: name.clone(),
+                    to: name.clone(),
+                    period: 1,
+                }
+                .message(lang),
+            ];
+            for text in rendered {
+                for key in keys(lang) {
+                    assert!(
+                        !text.contains(key),
+                        "{key} is not rendered in {lang}: {text}"
+                    );
+                }
+                assert!(
+                    !text.contains('{'),
+                    "unfilled placeholder in {lang}: {text}"
+                );
+            }
+        }
+        let ru = Concentration::new(&storage).message(Lang::Ru);
+        assert_ne!(ru, Concentration::new(&storage).message(Lang::En));
+    }
+
+    #[test]
+    fn test_format() {
+        let message = format(
+            Lang::Ru,
+            "user.added",
+            &[("name", &"Alice"), ("balance", &10)],
+        );
+        assert_eq!(message, "Пользователь Alice добавлен с балансом 10");
+        assert_eq!(text(Lang::En, "no.such.key"), "no.such.key");
+        assert_eq!("ru_RU.UTF-8".parse::<Lang>().unwrap(), Lang::Ru);
+    }
+}
@@ -0,0 +1,315 @@
+use super::errors::{BankError, FileContext};
+use super::history::{History, Record, RecordId};
+use super::storage::write_file;
+use super::time::{Date, SECS_PER_DAY};
+use super::transaction::TxKind;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+
+/// Строка выписки банка-корреспондента.
+/// Положительная сумма - поступление к нам, отрицательная - списание
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct StatementEntry {
+    pub date: Date,
+    pub amount: i64,
+    pub reference: String,
+}
+
+impl StatementEntry {
+    /// Разбирает строку выписки "Date,Amount,Reference"
+    fn parse(line: &str) -> Result<StatementEntry, BankError> {
+        let parts: Vec<&str> = line.splitn(3, ',').collect();
+        if parts.len() < 2 {
+            return Err(BankError::InvalidFormat(format!("statement line: {line}")));
+        }
+        Ok(StatementEntry {
+            date: parts[0].parse()?,
+            amount: parts[1].trim().parse()?,
+            reference: parts.get(2).unwrap_or(&"").trim().to_string(),
+        })
+    }
+
+    /// Ключ, по которому строка выписки узнаётся при следующем запуске
+    pub fn key(&self) -> String {
+        format!("{}|{}|{}", self.date, self.amount, self.reference)
+    }
+}
+
+impl Display for StatementEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{},{}", self.date, self.amount, self.reference)
+    }
+}
+
+/// Читает выписку в формате CSV "Date,Amount,Reference". Строка заголовка пропускается
+pub fn load_statement(file: &str) -> Result<Vec<StatementEntry>, BankError> {
+    let mut entries = Vec::new();
+    for (i, line) in fs::read_to_string(file).in_file(file)?.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || (i == 0 && line.to_lowercase().starts_with("date")) {
+            continue;
+        }
+        entries.push(StatementEntry::parse(line).at_line(file, i)?);
+    }
+    Ok(entries)
+}
+
+/// Сумма операции с точки зрения внешнего банка. Внутренние операции
+/// (переводы, комиссии, проценты) в выписку не попадают
+fn external_amount(record: &Record) -> Option<i64> {
+    match record.tx() {
+        TxKind::Deposit(tx) => Some(tx.amount().value()),
+        TxKind::Withdraw(tx) => Some(-tx.amount().value()),
+        _ => None,
+    }
+}
+
+/// Сопоставления, подтверждённые оператором вручную
+#[derive(Clone, Debug, Default)]
+pub struct ManualMatches {
+    matches: HashMap<String, RecordId>,
+}
+
+impl ManualMatches {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn confirm(&mut self, entry: &StatementEntry, record: RecordId) {
+        self.matches.insert(entry.key(), record);
+    }
+
+    pub fn get(&self, entry: &StatementEntry) -> Option<RecordId> {
+        self.matches.get(&entry.key()).copied()
+    }
+
+    /// Формат файла: "Key,RecordId"
+    pub fn load(file: &str) -> Result<ManualMatches, BankError> {
+        if !Path::new(file).exists() {
+            return Ok(ManualMatches::new());
+        }
+        Self::from_csv(&fs::read_to_string(file).in_file(file)?, file)
+    }
+
+    /// Разбирает содержимое файла `file`
+    pub fn from_csv(data: &str, file: &str) -> Result<ManualMatches, BankError> {
+        let mut manual = ManualMatches::new();
+        for (i, line) in data.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match line.trim().rsplit_once(',') {
+                Some((key, id)) => {
+                    manual
+                        .matches
+                        .insert(key.to_string(), id.parse().at_line(file, i)?);
+                }
+                None => {
+                    return Err(BankError::InvalidFormat(format!("match line: {line}")))
+                        .at_line(file, i);
+                }
+            }
+        }
+        Ok(manual)
+    }
+
+    pub fn save(&self, file: &str) -> Result<(), BankError> {
+        write_file(file, &self.to_csv())
+    }
+
+    /// Содержимое файла, строки отсортированы
+    pub fn to_csv(&self) -> String {
+        let mut lines: Vec<String> = self
+            .matches
+            .iter()
+            .map(|(key, id)| format!("{key},{id}\n"))
+            .collect();
+        lines.sort();
+        lines.concat()
+    }
+}
+
+/// Результат сверки
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Reconciliation {
+    pub matched: Vec<(StatementEntry, RecordId)>,
+    pub unmatched_ours: Vec<Record>,
+    pub unmatched_theirs: Vec<StatementEntry>,
+}
+
+/// Сверяет выписку с нашим журналом. Порядок сопоставления: ручные
+/// подтверждения, затем совпадение ссылки (номер нашей записи) и суммы,
+/// затем совпадение суммы в пределах `window_days` дней от даты выписки.
+/// Наши записи рассматриваются только в периоде выписки, расширенном на окно
+pub fn reconcile(
+    statement: &[StatementEntry],
+    history: &History,
+    manual: &ManualMatches,
+    window_days: i64,
+) -> Reconciliation {
+    let mut result = Reconciliation::default();
+    let (Some(first), Some(last)) = (
+        statement.iter().map(|e| e.date).min(),
+        statement.iter().map(|e| e.date).max(),
+    ) else {
+        return result;
+    };
+    let from = first.add_days(-window_days).start();
+    let to = last.add_days(window_days + 1).start();
+    let ours: Vec<&Record> = history
+        .records()
+        .iter()
+        .filter(|r| external_amount(r).is_some() && r.time() >= from && r.time() < to)
+        .collect();
+    let by_id: HashMap<RecordId, &Record> = ours.iter().map(|r| (r.id(), *r)).collect();
+
+    let mut used: HashSet<RecordId> = HashSet::new();
+    let mut pending: Vec<&StatementEntry> = Vec::new();
+
+    // Ручные подтверждения и точные совпадения по ссылке
+    for entry in statement {
+        let candidate = manual.get(entry).or_else(|| {
+            let id: RecordId = entry.reference.parse().ok()?;
+            let record = by_id.get(&id)?;
+            (external_amount(record) == Some(entry.amount)).then_some(id)
+        });
+        match candidate {
+            Some(id) if !used.contains(&id) => {
+                used.insert(id);
+                result.matched.push((entry.clone(), id));
+            }
+            _ => pending.push(entry),
+        }
+    }
+
+    // Совпадение по сумме и дате: берём ближайшую по времени запись
+    for entry in pending {
+        let entry_time = entry.date.start() + SECS_PER_DAY / 2;
+        let best = ours
+            .iter()
+            .filter(|r| !used.contains(&r.id()) && external_amount(r) == Some(entry.amount))
+            .filter(|r| {
+                (r.time().value() - entry_time.value()).abs()
+                    <= (window_days * SECS_PER_DAY + SECS_PER_DAY / 2)
+            })
+            .min_by_key(|r| ((r.time().value() - entry_time.value()).abs(), r.id()));
+        match best {
+            Some(record) => {
+                used.insert(record.id());
+                result.matched.push((entry.clone(), record.id()));
+            }
+            None => result.unmatched_theirs.push(entry.clone()),
+        }
+    }
+
+    result.unmatched_ours = ours
+        .into_iter()
+        .filter(|r| !used.contains(&r.id()))
+        .cloned()
+        .collect();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Balance;
+    use crate::time::Timestamp;
+    use crate::transaction::{Deposit, Transfer, Withdraw};
+
+    fn at(date: &str) -> Timestamp {
+        date.parse::<Date>().unwrap().start() + 3600
+    }
+
+    fn entry(date: &str, amount: i64, reference: &str) -> StatementEntry {
+        StatementEntry {
+            date: date.parse().unwrap(),
+            amount,
+            reference: reference.to_string(),
+        }
+    }
+
+    fn history() -> History {
+        let mut history = History::new();
+        history.push(
+            at("2024-03-01"),
+            Deposit::new("Alice", Balance::new(100)).into(),
+        );
+        history.push(
+            at("2024-03-02"),
+            Withdraw::new("Alice", Balance::new(40)).into(),
+        );
+        history.push(
+            at("2024-03-02"),
+            Transfer::new("Alice", "Bob", Balance::new(10)).into(),
+        );
+        history.push(
+            at("2024-03-03"),
+            Deposit::new("Bob", Balance::new(100)).into(),
+        );
+        history.push(
+            at("2024-03-05"),
+            Deposit::new("Carol", Balance::new(7)).into(),
+        );
+        history
+    }
+
+    #[test]
+    fn test_reconcile() {
+        let statement = vec![
+            // По ссылке, хотя дата сильно отличается
+            entry("2024-03-04", 100, "4"),
+            // По сумме и дате в пределах окна
+            entry("2024-03-03", -40, "ATM"),
+            entry("2024-03-02", 100, ""),
+            // Такой операции у нас нет
+            entry("2024-03-03", 55, "X"),
+        ];
+        let res = reconcile(&statement, &history(), &ManualMatches::new(), 1);
+
+        let matched: Vec<(i64, RecordId)> =
+            res.matched.iter().map(|(e, id)| (e.amount, *id)).collect();
+        assert_eq!(matched, vec![(100, 4), (-40, 2), (100, 1)]);
+        assert_eq!(res.unmatched_theirs, vec![entry("2024-03-03", 55, "X")]);
+        // Перевод внутренний и в сверку не попадает
+        let ours: Vec<RecordId> = res.unmatched_ours.iter().map(|r| r.id()).collect();
+        assert_eq!(ours, vec![5]);
+    }
+
+    #[test]
+    fn test_manual_matches_persist() {
+        let statement = vec![entry("2024-03-10", 7, "late")];
+        let res = reconcile(&statement, &history(), &ManualMatches::new(), 1);
+        assert_eq!(res.unmatched_theirs.len(), 1);
+
+        let mut manual = ManualMatches::new();
+        manual.confirm(&statement[0], 5);
+        let path = std::env::temp_dir().join("bank_reconcile_test.csv");
+        let path = path.to_str().unwrap();
+        manual.save(path).unwrap();
+        let manual = ManualMatches::load(path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        let res = reconcile(&statement, &history(), &manual, 10);
+        assert_eq!(res.matched, vec![(statement[0].clone(), 5)]);
+        assert!(res.unmatched_theirs.is_empty());
+    }
+
+    #[test]
+    fn test_load_statement() {
+        let path = std::env::temp_dir().join("bank_statement_test.csv");
+        fs::write(
+            &path,
+            "date,amount,reference\n2024-03-01,100,4\n2024-03-02,-40,\n",
+        )
+        .unwrap();
+        let entries = load_statement(path.to_str().unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            entries,
+            vec![entry("2024-03-01", 100, "4"), entry("2024-03-02", -40, "")]
+        );
+    }
+}
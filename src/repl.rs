@@ -0,0 +1,399 @@
+use super::errors::{BankError, FileContext};
+use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// История введённых команд, которая переживает перезапуск REPL
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LineHistory {
+    lines: Vec<String>,
+    /// Сколько последних строк хранить
+    limit: usize,
+}
+
+impl LineHistory {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            lines: Vec::new(),
+            limit,
+        }
+    }
+
+    /// Читает историю из файла, по команде на строку. Нет файла - пустая история
+    pub fn load(file: &str, limit: usize) -> Result<Self, BankError> {
+        let mut history = Self::new(limit);
+        if Path::new(file).exists() {
+            for line in fs::read_to_string(file).in_file(file)?.lines() {
+                history.push(line);
+            }
+        }
+        Ok(history)
+    }
+
+    pub fn save(&self, file: &str) -> Result<(), BankError> {
+        let data: String = self.lines.iter().map(|line| format!("{line}\n")).collect();
+        fs::write(file, data).in_file(file)?;
+        Ok(())
+    }
+
+    /// Добавляет команду. Пустые строки и повтор предыдущей не запоминаются
+    pub fn push(&mut self, line: &str) {
+        let line = line.trim();
+        if line.is_empty() || self.lines.last().is_some_and(|last| last == line) {
+            return;
+        }
+        self.lines.push(line.to_owned());
+        if self.lines.len() > self.limit {
+            let extra = self.lines.len() - self.limit;
+            self.lines.drain(..extra);
+        }
+    }
+
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+}
+
+/// Варианты дополнения слова перед концом строки `line`: первое слово - команда,
+/// слово после `help` - тоже команда, остальные - имена счетов.
+/// Возвращает позицию начала слова в `line` и отсортированные варианты
+pub fn complete<'a>(
+    line: &str,
+    commands: &[&str],
+    accounts: impl IntoIterator<Item = &'a str>,
+) -> (usize, Vec<String>) {
+    let word: usize = line
+        .chars()
+        .rev()
+        .take_while(|c| !c.is_whitespace())
+        .map(char::len_utf8)
+        .sum();
+    let start = line.len() - word;
+    let prefix = &line[start..];
+    let before: Vec<&str> = line[..start].split_whitespace().collect();
+
+    let mut candidates: Vec<String> = match before.as_slice() {
+        [] | ["help"] => commands
+            .iter()
+            .filter(|c| c.starts_with(prefix))
+            .map(|c| c.to_string())
+            .collect(),
+        _ => accounts
+            .into_iter()
+            .filter(|name| name.starts_with(prefix))
+            .map(str::to_owned)
+            .collect(),
+    };
+    candidates.sort();
+    candidates.dedup();
+    (start, candidates)
+}
+
+/// Общее начало всех вариантов
+pub fn common_prefix(candidates: &[String]) -> &str {
+    let Some(first) = candidates.first() else {
+        return "";
+    };
+    let mut prefix = first.as_str();
+    for other in &candidates[1..] {
+        while !other.starts_with(prefix) {
+            let mut chars = prefix.chars();
+            chars.next_back();
+            prefix = chars.as_str();
+        }
+    }
+    prefix
+}
+
+/// Редактор строки для терминала: курсор, история по стрелкам, дополнение по Tab.
+///
+/// Терминал переводится в посимвольный режим через `stty`, поэтому редактор
+/// работает только в Unix-подобных системах и только если stdin - терминал
+pub struct Editor {
+    history: LineHistory,
+}
+
+impl Editor {
+    /// Редактор для stdin; `None`, если stdin не терминал или нет `stty`
+    pub fn for_terminal(history: LineHistory) -> Option<Self> {
+        if !io::stdin().is_terminal() || stty(&["-g"]).is_none() {
+            return None;
+        }
+        Some(Self { history })
+    }
+
+    pub fn history(&self) -> &LineHistory {
+        &self.history
+    }
+
+    /// Читает строку с терминала. `None` - конец ввода (Ctrl-D на пустой строке)
+    pub fn read_line(
+        &mut self,
+        prompt: &str,
+        complete: impl Fn(&str) -> (usize, Vec<String>),
+    ) -> io::Result<Option<String>> {
+        let saved = stty(&["-g"]).ok_or_else(|| io::Error::other("stty failed"))?;
+        stty(&["-icanon", "-echo", "-isig", "min", "1"])
+            .ok_or_else(|| io::Error::other("stty failed"))?;
+        let line = self.edit(&mut io::stdin().lock(), &mut io::stdout(), prompt, complete);
+        // Настройки терминала возвращаются и после ошибки чтения
+        stty(&[saved.trim()]);
+        line
+    }
+
+    /// Разбирает нажатия клавиш из `input` и рисует строку в `output`
+    fn edit(
+        &mut self,
+        input: &mut impl Read,
+        output: &mut impl Write,
+        prompt: &str,
+        complete: impl Fn(&str) -> (usize, Vec<String>),
+    ) -> io::Result<Option<String>> {
+        let mut line = LineBuffer::default();
+        // Позиция в истории; после последней строки - новая строка
+        let mut position = self.history.lines.len();
+        write!(output, "{prompt}")?;
+        output.flush()?;
+
+        loop {
+            let Some(byte) = read_byte(input)? else {
+                return Ok(None);
+            };
+            match byte {
+                b'\r' | b'\n' => {
+                    writeln!(output, "\r")?;
+                    let text = line.text();
+                    self.history.push(&text);
+                    return Ok(Some(text));
+                }
+                // Ctrl-D на пустой строке - конец ввода, иначе удаление под курсором
+                4 if line.chars.is_empty() => {
+                    writeln!(output, "\r")?;
+                    return Ok(None);
+                }
+                4 => line.delete(),
+                // Ctrl-C - отменить строку
+                3 => {
+                    writeln!(output, "^C\r")?;
+                    line = LineBuffer::default();
+                    position = self.history.lines.len();
+                }
+                127 | 8 => line.backspace(),
+                1 => line.cursor = 0,
+                5 => line.cursor = line.chars.len(),
+                // Ctrl-U - стереть строку до курсора
+                21 => {
+                    line.chars.drain(..line.cursor);
+                    line.cursor = 0;
+                }
+                b'\t' => {
+                    let head: String = line.chars[..line.cursor].iter().collect();
+                    let (start, candidates) = complete(&head);
+                    let word = &head[start..];
+                    let prefix = common_prefix(&candidates);
+                    if candidates.len() == 1 {
+                        line.insert_str(&format!("{} ", &prefix[word.len()..]));
+                    } else if prefix.len() > word.len() {
+                        line.insert_str(&prefix[word.len()..]);
+                    } else if candidates.len() > 1 {
+                        // Дополнять нечего - показываем варианты под строкой
+                        write!(output, "\r\n{}\r\n", candidates.join("  "))?;
+                    }
+                }
+                // Последовательности ESC [ ... от стрелок и Home/End/Delete
+                0x1b => {
+                    if read_byte(input)? != Some(b'[') {
+                        continue;
+                    }
+                    match read_byte(input)? {
+                        Some(b'A') if position > 0 => {
+                            position -= 1;
+                            line = LineBuffer::from(self.history.lines[position].as_str());
+                        }
+                        Some(b'B') if position < self.history.lines.len() => {
+                            position += 1;
+                            line = match self.history.lines.get(position) {
+                                Some(text) => LineBuffer::from(text.as_str()),
+                                None => LineBuffer::default(),
+                            };
+                        }
+                        Some(b'C') => line.cursor = (line.cursor + 1).min(line.chars.len()),
+                        Some(b'D') => line.cursor = line.cursor.saturating_sub(1),
+                        Some(b'H') => line.cursor = 0,
+                        Some(b'F') => line.cursor = line.chars.len(),
+                        Some(b'3') if read_byte(input)? == Some(b'~') => line.delete(),
+                        _ => {}
+                    }
+                }
+                byte if byte < 0x20 => {}
+                byte => {
+                    // Многобайтовый символ UTF-8: дочитываем продолжение
+                    let len = match byte {
+                        0xf0.. => 4,
+                        0xe0.. => 3,
+                        0xc0.. => 2,
+                        _ => 1,
+                    };
+                    let mut bytes = vec![byte];
+                    for _ in 1..len {
+                        bytes.extend(read_byte(input)?);
+                    }
+                    if let Ok(text) = String::from_utf8(bytes) {
+                        line.insert_str(&text);
+                    }
+                }
+            }
+
+            // Перерисовываем строку и ставим курсор на место
+            write!(output, "\r\x1b[K{prompt}{}", line.text())?;
+            let back = line.chars.len() - line.cursor;
+            if back > 0 {
+                write!(output, "\x1b[{back}D")?;
+            }
+            output.flush()?;
+        }
+    }
+}
+
+/// Строка, которую редактирует пользователь, и позиция курсора в символах
+#[derive(Default)]
+struct LineBuffer {
+    chars: Vec<char>,
+    cursor: usize,
+}
+
+impl LineBuffer {
+    fn text(&self) -> String {
+        self.chars.iter().collect()
+    }
+
+    fn insert_str(&mut self, text: &str) {
+        for c in text.chars() {
+            self.chars.insert(self.cursor, c);
+            self.cursor += 1;
+        }
+    }
+
+    fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.chars.remove(self.cursor);
+        }
+    }
+
+    fn delete(&mut self) {
+        if self.cursor < self.chars.len() {
+            self.chars.remove(self.cursor);
+        }
+    }
+}
+
+impl From<&str> for LineBuffer {
+    fn from(value: &str) -> Self {
+        let chars: Vec<char> = value.chars().collect();
+        let cursor = chars.len();
+        Self { chars, cursor }
+    }
+}
+
+fn read_byte(input: &mut impl Read) -> io::Result<Option<u8>> {
+    let mut byte = [0];
+    match input.read(&mut byte)? {
+        0 => Ok(None),
+        _ => Ok(Some(byte[0])),
+    }
+}
+
+/// Запускает `stty` для терминала на stdin и возвращает его вывод
+pub(crate) fn stty(args: &[&str]) -> Option<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const COMMANDS: &[&str] = &["balance", "deposit", "help", "transfer", "trial"];
+
+    fn accounts() -> Vec<&'static str> {
+        vec!["Alice", "Bob", "Alex"]
+    }
+
+    #[test]
+    fn test_history() {
+        let mut history = LineHistory::new(3);
+        for line in [
+            "balance Alice",
+            "balance Alice",
+            "  ",
+            "trial",
+            "report",
+            "review",
+        ] {
+            history.push(line);
+        }
+        assert_eq!(history.lines(), ["trial", "report", "review"]);
+
+        let path = std::env::temp_dir().join("bank_repl_history_test.txt");
+        let path = path.to_str().unwrap();
+        history.save(path).unwrap();
+        assert_eq!(
+            LineHistory::load(path, 2).unwrap().lines(),
+            ["report", "review"]
+        );
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_complete() {
+        assert_eq!(
+            complete("tr", COMMANDS, accounts()),
+            (0, vec!["transfer".to_string(), "trial".to_string()])
+        );
+        assert_eq!(common_prefix(&complete("tr", COMMANDS, accounts()).1), "tr");
+        assert_eq!(
+            complete("transfer Alice Al", COMMANDS, accounts()),
+            (15, vec!["Alex".to_string(), "Alice".to_string()])
+        );
+        assert_eq!(common_prefix(&["Alex".into(), "Alice".into()]), "Al");
+        assert_eq!(complete("help de", COMMANDS, accounts()).1, ["deposit"]);
+        assert!(complete("deposit Z", COMMANDS, accounts()).1.is_empty());
+    }
+
+    #[test]
+    fn test_editing_keys() {
+        let mut editor = Editor {
+            history: LineHistory::new(10),
+        };
+        editor.history.push("balance Alice");
+        let complete = |line: &str| complete(line, COMMANDS, accounts());
+        let mut read = |keys: &[u8]| {
+            let mut output = Vec::new();
+            editor
+                .edit(&mut Cursor::new(keys.to_vec()), &mut output, "> ", complete)
+                .unwrap()
+        };
+
+        // Tab дополняет команду и имя, Backspace и стрелка влево правят середину строки
+        let line = read(b"dep\tBo\t10\x7f\x7f5\x1b[D9\r");
+        assert_eq!(line.as_deref(), Some("deposit Bob 95"));
+        // Стрелка вверх достаёт предыдущую команду
+        assert_eq!(read(b"\x1b[A\r").as_deref(), Some("deposit Bob 95"));
+        assert_eq!(read(b"\x1b[A\x1b[A\r").as_deref(), Some("balance Alice"));
+        // Ctrl-C отменяет набранное, Ctrl-D на пустой строке - конец ввода
+        assert_eq!(read(b"trial\x03\x04"), None);
+        assert_eq!(read("баланс\r".as_bytes()).as_deref(), Some("баланс"));
+        assert_eq!(editor.history().lines().len(), 4);
+    }
+}
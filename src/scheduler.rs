@@ -0,0 +1,511 @@
+use super::errors::{BankError, FileContext};
+use super::storage::{Storage, write_file};
+use super::time::{SECS_PER_DAY, SECS_PER_WEEK, Timestamp};
+use super::transaction::{Transaction, TxKind};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::str::FromStr;
+
+pub type ScheduleId = u64;
+
+/// Как часто повторяется операция
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Recurrence {
+    Once,
+    Daily,
+    Weekly,
+    /// Каждый месяц в указанный день (31 означает последний день месяца)
+    Monthly {
+        day: u32,
+    },
+}
+
+/// Когда прекращается повторение
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum End {
+    Never,
+    /// Последний запуск не позже указанного момента
+    Until(Timestamp),
+    /// Всего запусков
+    Count(u32),
+}
+
+/// Сколько раз повторять неудачный запуск и через сколько секунд
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub delay: i64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Failure {
+    pub at: Timestamp,
+    pub attempt: u32,
+    pub reason: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScheduledTx {
+    id: ScheduleId,
+    tx: TxKind,
+    start: Timestamp,
+    recurrence: Recurrence,
+    end: End,
+    retry: RetryPolicy,
+    /// Номер текущего запуска, начиная с нуля
+    index: u32,
+    /// Номер попытки текущего запуска
+    attempt: u32,
+    /// Момент следующей попытки, None - расписание исчерпано
+    next_run: Option<Timestamp>,
+    failures: Vec<Failure>,
+}
+
+impl ScheduledTx {
+    pub fn id(&self) -> ScheduleId {
+        self.id
+    }
+
+    pub fn tx(&self) -> &TxKind {
+        &self.tx
+    }
+
+    pub fn next_run(&self) -> Option<Timestamp> {
+        self.next_run
+    }
+
+    pub fn failures(&self) -> &[Failure] {
+        &self.failures
+    }
+
+    /// Момент запуска с номером `index` по расписанию (без учёта повторов)
+    fn occurrence(&self, index: u32) -> Option<Timestamp> {
+        let at = match self.recurrence {
+            Recurrence::Once if index > 0 => return None,
+            Recurrence::Once => self.start,
+            Recurrence::Daily => self.start + index as i64 * SECS_PER_DAY,
+            Recurrence::Weekly => self.start + index as i64 * SECS_PER_WEEK,
+            Recurrence::Monthly { day } => {
+                let date = self.start.date();
+                let time = self.start.time_of_day();
+                let mut first = date.with_month_offset(0, day).start() + time;
+                if first < self.start {
+                    first = date.with_month_offset(1, day).start() + time;
+                }
+                first.date().with_month_offset(index as i64, day).start() + time
+            }
+        };
+        match self.end {
+            End::Count(count) if index >= count => None,
+            End::Until(until) if at > until => None,
+            _ => Some(at),
+        }
+    }
+
+    fn advance(&mut self) {
+        self.index += 1;
+        self.attempt = 0;
+        self.next_run = self.occurrence(self.index);
+    }
+}
+
+/// Результат одной попытки выполнения
+#[derive(Debug)]
+pub struct RunReport {
+    pub id: ScheduleId,
+    pub at: Timestamp,
+    pub result: Result<(), BankError>,
+}
+
+/// Планировщик отложенных и повторяющихся операций
+#[derive(Default)]
+pub struct Scheduler {
+    items: BTreeMap<ScheduleId, ScheduledTx>,
+    next_id: ScheduleId,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Регистрирует операцию и возвращает её идентификатор
+    pub fn schedule(
+        &mut self,
+        tx: TxKind,
+        start: Timestamp,
+        recurrence: Recurrence,
+        end: End,
+        retry: RetryPolicy,
+    ) -> ScheduleId {
+        self.next_id += 1;
+        let mut item = ScheduledTx {
+            id: self.next_id,
+            tx,
+            start,
+            recurrence,
+            end,
+            retry,
+            index: 0,
+            attempt: 0,
+            next_run: None,
+            failures: Vec::new(),
+        };
+        item.next_run = item.occurrence(0);
+        self.items.insert(item.id, item);
+        self.next_id
+    }
+
+    pub fn cancel(&mut self, id: ScheduleId) -> Option<ScheduledTx> {
+        self.items.remove(&id)
+    }
+
+    pub fn get(&self, id: ScheduleId) -> Option<&ScheduledTx> {
+        self.items.get(&id)
+    }
+
+    /// Выполняет все операции, срок которых наступил к моменту `now`,
+    /// в порядке их времени. Пропущенные запуски тоже выполняются
+    pub fn tick(&mut self, now: Timestamp, storage: &mut Storage) -> Vec<RunReport> {
+        let mut reports = Vec::new();
+
+        while let Some(item) = self
+            .items
+            .values_mut()
+            .filter(|item| item.next_run.is_some_and(|at| at <= now))
+            .min_by_key(|item| (item.next_run, item.id))
+        {
+            let Some(at) = item.next_run else { break };
+            let result = item.tx.apply(storage);
+            match &result {
+                Ok(()) => item.advance(),
+                Err(e) => {
+                    item.failures.push(Failure {
+                        at,
+                        attempt: item.attempt,
+                        reason: e.to_string(),
+                    });
+                    if item.attempt < item.retry.max_retries {
+                        item.attempt += 1;
+                        item.next_run = Some(at + item.retry.delay.max(1));
+                    } else {
+                        item.advance();
+                    }
+                }
+            }
+            reports.push(RunReport {
+                id: item.id,
+                at,
+                result,
+            });
+        }
+
+        reports
+    }
+
+    /// Ближайшие запуски до момента `until` включительно, по возрастанию времени
+    pub fn upcoming(&self, until: Timestamp) -> Vec<(Timestamp, ScheduleId)> {
+        let mut res = Vec::new();
+        for item in self.items.values() {
+            let Some(next) = item.next_run else { continue };
+            if next <= until {
+                res.push((next, item.id));
+            }
+            let mut index = item.index + 1;
+            while let Some(at) = item.occurrence(index) {
+                if at > until {
+                    break;
+                }
+                res.push((at, item.id));
+                index += 1;
+            }
+        }
+        res.sort();
+        res
+    }
+
+    /// Загружает расписание из файла. Если файла нет, расписание пустое
+    pub fn load(file: &str) -> Result<Scheduler, BankError> {
+        let mut scheduler = Scheduler::new();
+        if !Path::new(file).exists() {
+            return Ok(scheduler);
+        }
+
+        let reader = BufReader::new(File::open(file).in_file(file)?);
+        for (i, line) in reader.lines().enumerate() {
+            let line = line.at_line(file, i)?;
+            if !line.trim().is_empty() {
+                scheduler.load_line(line.trim()).at_line(file, i)?;
+            }
+        }
+
+        Ok(scheduler)
+    }
+
+    /// Разбирает одну строку файла расписания
+    fn load_line(&mut self, line: &str) -> Result<(), BankError> {
+        // Формат строк:
+        // item,id,start,recurrence,end,max_retries,delay,index,attempt,next_run,<операция>
+        // failure,id,at,attempt,reason
+        let parts: Vec<&str> = line.splitn(11, ',').collect();
+        match parts[0] {
+            "item" if parts.len() == 11 => {
+                let item = ScheduledTx {
+                    id: parts[1].parse()?,
+                    start: parts[2].parse()?,
+                    recurrence: parts[3].parse()?,
+                    end: parts[4].parse()?,
+                    retry: RetryPolicy {
+                        max_retries: parts[5].parse()?,
+                        delay: parts[6].parse()?,
+                    },
+                    index: parts[7].parse()?,
+                    attempt: parts[8].parse()?,
+                    next_run: match parts[9] {
+                        "-" => None,
+                        at => Some(at.parse()?),
+                    },
+                    tx: parts[10].parse()?,
+                    failures: Vec::new(),
+                };
+                self.next_id = self.next_id.max(item.id);
+                self.items.insert(item.id, item);
+            }
+            "failure" => {
+                let parts: Vec<&str> = line.splitn(5, ',').collect();
+                if parts.len() != 5 {
+                    return Err(BankError::InvalidFormat(format!("schedule line: {line}")));
+                }
+                let id: ScheduleId = parts[1].parse()?;
+                let failure = Failure {
+                    at: parts[2].parse()?,
+                    attempt: parts[3].parse()?,
+                    reason: parts[4].to_string(),
+                };
+                match self.items.get_mut(&id) {
+                    Some(item) => item.failures.push(failure),
+                    None => {
+                        return Err(BankError::InvalidFormat(format!("schedule line: {line}")));
+                    }
+                }
+            }
+            _ => return Err(BankError::InvalidFormat(format!("schedule line: {line}"))),
+        }
+        Ok(())
+    }
+
+    /// Сохраняет расписание вместе с историей ошибок
+    pub fn save(&self, file: &str) -> Result<(), BankError> {
+        let mut data = String::new();
+        for item in self.items.values() {
+            let next_run = match item.next_run {
+                Some(at) => at.to_string(),
+                None => "-".to_string(),
+            };
+            data.push_str(&format!(
+                "item,{},{},{},{},{},{},{},{},{},{}\n",
+                item.id,
+                item.start,
+                item.recurrence,
+                item.end,
+                item.retry.max_retries,
+                item.retry.delay,
+                item.index,
+                item.attempt,
+                next_run,
+                item.tx
+            ));
+            for failure in &item.failures {
+                // Перевод строки в причине сломал бы формат файла
+                let reason = failure.reason.replace('\n', " ");
+                data.push_str(&format!(
+                    "failure,{},{},{},{}\n",
+                    item.id, failure.at, failure.attempt, reason
+                ));
+            }
+        }
+        write_file(file, &data)
+    }
+}
+
+impl Display for Recurrence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Recurrence::Once => write!(f, "once"),
+            Recurrence::Daily => write!(f, "daily"),
+            Recurrence::Weekly => write!(f, "weekly"),
+            Recurrence::Monthly { day } => write!(f, "monthly:{day}"),
+        }
+    }
+}
+
+impl FromStr for Recurrence {
+    type Err = BankError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "once" => Ok(Recurrence::Once),
+            None if s == "daily" => Ok(Recurrence::Daily),
+            None if s == "weekly" => Ok(Recurrence::Weekly),
+            Some(("monthly", day)) => Ok(Recurrence::Monthly { day: day.parse()? }),
+            _ => Err(BankError::InvalidFormat(format!("unknown recurrence: {s}"))),
+        }
+    }
+}
+
+impl Display for End {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            End::Never => write!(f, "never"),
+            End::Until(at) => write!(f, "until:{at}"),
+            End::Count(count) => write!(f, "count:{count}"),
+        }
+    }
+}
+
+impl FromStr for End {
+    type Err = BankError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "never" => Ok(End::Never),
+            Some(("until", at)) => Ok(End::Until(at.parse()?)),
+            Some(("count", count)) => Ok(End::Count(count.parse()?)),
+            _ => Err(BankError::InvalidFormat(format!(
+                "unknown schedule end: {s}"
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{Balance, bank};
+    use crate::transaction::{Deposit, Transfer};
+
+    fn day(date: &str) -> Timestamp {
+        date.parse::<crate::time::Date>().unwrap().start()
+    }
+
+    #[test]
+    fn test_monthly_rent_runs_on_given_day() {
+        let mut storage = bank(&[("Alice", 0), ("Landlord", 0)]);
+        storage
+            .deposit(&"Alice".to_string(), Balance::new(1000))
+            .unwrap();
+
+        let mut scheduler = Scheduler::new();
+        let rent = Transfer::new("Alice", "Landlord", Balance::new(300));
+        let id = scheduler.schedule(
+            rent.into(),
+            day("2024-01-10"),
+            Recurrence::Monthly { day: 31 },
+            End::Count(3),
+            RetryPolicy::default(),
+        );
+
+        assert!(scheduler.tick(day("2024-01-30"), &mut storage).is_empty());
+        assert_eq!(
+            scheduler.upcoming(day("2025-01-01")),
+            vec![
+                (day("2024-01-31"), id),
+                (day("2024-02-29"), id),
+                (day("2024-03-31"), id)
+            ]
+        );
+
+        // Пропущенные запуски выполняются при следующем тике
+        let reports = scheduler.tick(day("2024-03-01"), &mut storage);
+        assert_eq!(reports.len(), 2);
+        assert!(reports.iter().all(|r| r.result.is_ok()));
+        assert_eq!(
+            storage.get_balance(&"Landlord".to_string()),
+            Some(Balance::new(600))
+        );
+        assert_eq!(
+            scheduler.get(id).unwrap().next_run(),
+            Some(day("2024-03-31"))
+        );
+
+        scheduler.tick(day("2024-12-31"), &mut storage);
+        assert_eq!(scheduler.get(id).unwrap().next_run(), None);
+        assert_eq!(
+            storage.get_balance(&"Alice".to_string()),
+            Some(Balance::new(100))
+        );
+    }
+
+    #[test]
+    fn test_failures_are_retried_then_skipped() {
+        let mut storage = bank(&[("Alice", 0), ("Landlord", 0)]);
+        let mut scheduler = Scheduler::new();
+        let id = scheduler.schedule(
+            Transfer::new("Alice", "Landlord", Balance::new(100)).into(),
+            day("2024-01-01"),
+            Recurrence::Daily,
+            End::Never,
+            RetryPolicy {
+                max_retries: 2,
+                delay: 3600,
+            },
+        );
+
+        let reports = scheduler.tick(day("2024-01-01") + 3600, &mut storage);
+        assert_eq!(reports.len(), 2);
+        assert!(reports.iter().all(|r| r.result.is_err()));
+
+        // Третья попытка проходит, после того как появились деньги
+        storage
+            .deposit(&"Alice".to_string(), Balance::new(100))
+            .unwrap();
+        let reports = scheduler.tick(day("2024-01-01") + 7200, &mut storage);
+        assert_eq!(reports.len(), 1);
+        assert!(reports[0].result.is_ok());
+
+        let item = scheduler.get(id).unwrap();
+        assert_eq!(item.failures().len(), 2);
+        assert_eq!(item.next_run(), Some(day("2024-01-02")));
+
+        // Все попытки неудачны - запуск пропускается, ждём следующего дня
+        let reports = scheduler.tick(day("2024-01-02") + 7200, &mut storage);
+        assert_eq!(reports.len(), 3);
+        assert_eq!(
+            scheduler.get(id).unwrap().next_run(),
+            Some(day("2024-01-03"))
+        );
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let mut storage = bank(&[("Alice", 0), ("Landlord", 0)]);
+        let mut scheduler = Scheduler::new();
+        scheduler.schedule(
+            Deposit::new("Alice", Balance::new(10)).into(),
+            day("2024-01-01"),
+            Recurrence::Weekly,
+            End::Until(day("2024-06-01")),
+            RetryPolicy::default(),
+        );
+        scheduler.schedule(
+            Transfer::new("Landlord", "Alice", Balance::new(5)).into(),
+            day("2024-01-01"),
+            Recurrence::Once,
+            End::Never,
+            RetryPolicy::default(),
+        );
+        scheduler.tick(day("2024-01-01"), &mut storage);
+
+        let path = std::env::temp_dir().join("bank_scheduler_test.csv");
+        let path = path.to_str().unwrap();
+        scheduler.save(path).unwrap();
+        let loaded = Scheduler::load(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(loaded.items, scheduler.items);
+        assert_eq!(loaded.get(2).unwrap().failures().len(), 1);
+    }
+}
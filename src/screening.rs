@@ -0,0 +1,612 @@
+use super::errors::{BankError, FileContext};
+use super::history::Record;
+use super::locale::{self, Lang};
+use super::storage::{Name, Storage, write_file};
+use super::time::{SECS_PER_DAY, Timestamp};
+use super::transaction::{Transaction, TxKind};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+pub type ReviewId = u64;
+
+/// Почему операция показалась подозрительной
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Reason {
+    LargeAmount {
+        account: Name,
+        amount: i64,
+        factor: i64,
+        typical: i64,
+    },
+    FanOut {
+        from: Name,
+        count: usize,
+        period: i64,
+    },
+    RoundTrip {
+        from: Name,
+        to: Name,
+        period: i64,
+    },
+}
+
+impl Reason {
+    /// Описание причины на выбранном языке
+    pub fn message(&self, lang: Lang) -> String {
+        match self {
+            Reason::LargeAmount {
+                account,
+                amount,
+                factor,
+                typical,
+            } => locale::format(
+                lang,
+                "screening.large_amount",
+                &[
+                    ("account", account),
+                    ("amount", amount),
+                    ("factor", factor),
+                    ("typical", typical),
+                ],
+            ),
+            Reason::FanOut {
+                from,
+                count,
+                period,
+            } => locale::format(
+                lang,
+                "screening.fan_out",
+                &[("from", from), ("count", count), ("period", period)],
+            ),
+            Reason::RoundTrip { from, to, period } => locale::format(
+                lang,
+                "screening.round_trip",
+                &[("from", from), ("to", to), ("period", period)],
+            ),
+        }
+    }
+
+    /// Причина для файла очереди: вид и числа через ";", счета берутся из операции
+    fn encode(&self) -> String {
+        match self {
+            Reason::LargeAmount {
+                amount,
+                factor,
+                typical,
+                ..
+            } => format!("large_amount;{amount};{factor};{typical}"),
+            Reason::FanOut { count, period, .. } => format!("fan_out;{count};{period}"),
+            Reason::RoundTrip { period, .. } => format!("round_trip;{period}"),
+        }
+    }
+
+    /// Обратное к `encode`: восстанавливает причину по операции `tx`
+    fn decode(s: &str, tx: &TxKind) -> Result<Reason, BankError> {
+        let bad = || BankError::InvalidFormat(format!("review reason: {s}"));
+        let parts: Vec<&str> = s.split(';').collect();
+        match (parts.as_slice(), tx) {
+            (["large_amount", amount, factor, typical], _) => Ok(Reason::LargeAmount {
+                account: debited(tx).ok_or_else(bad)?.clone(),
+                amount: amount.parse()?,
+                factor: factor.parse()?,
+                typical: typical.parse()?,
+            }),
+            (["fan_out", count, period], TxKind::Transfer(transfer)) => Ok(Reason::FanOut {
+                from: transfer.from().clone(),
+                count: count.parse()?,
+                period: period.parse()?,
+            }),
+            (["round_trip", period], TxKind::Transfer(transfer)) => Ok(Reason::RoundTrip {
+                from: transfer.from().clone(),
+                to: transfer.to().clone(),
+                period: period.parse()?,
+            }),
+            _ => Err(bad()),
+        }
+    }
+}
+
+impl Display for Reason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message(Lang::En))
+    }
+}
+
+/// Решение по операции
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Allow,
+    Block(Reason),
+    Review(Reason),
+}
+
+impl Verdict {
+    fn severity(&self) -> u8 {
+        match self {
+            Verdict::Allow => 0,
+            Verdict::Review(_) => 1,
+            Verdict::Block(_) => 2,
+        }
+    }
+}
+
+/// Что делать с операцией, если правило сработало
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    Block,
+    Review,
+}
+
+/// Признаки подозрительной операции. Периоды задаются в секундах
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Check {
+    /// Сумма больше типичной (медианной) для счёта в `factor` раз.
+    /// Не проверяется, пока у счёта меньше `min_history` операций
+    LargeAmount { factor: i64, min_history: usize },
+    /// За период переводы больше чем `max_new` новым получателям
+    FanOut { max_new: usize, period: i64 },
+    /// Перевод обратно тому, кто сам переводил этому счёту за период
+    RoundTrip { period: i64 },
+}
+
+impl Check {
+    /// Возвращает причину, если операция подозрительна
+    fn inspect(&self, tx: &TxKind, storage: &Storage) -> Option<Reason> {
+        let now = storage.now();
+        let history = storage.history();
+        match (self, tx) {
+            (
+                Check::LargeAmount {
+                    factor,
+                    min_history,
+                },
+                _,
+            ) => {
+                let account = debited(tx)?;
+                let mut amounts: Vec<i64> = history
+                    .for_account(account)
+                    .map(|r| r.tx().amount().value())
+                    .collect();
+                if amounts.len() < *min_history || amounts.is_empty() {
+                    return None;
+                }
+                amounts.sort_unstable();
+                let typical = amounts[amounts.len() / 2];
+                let amount = tx.amount().value();
+                (amount > typical.saturating_mul(*factor)).then(|| Reason::LargeAmount {
+                    account: account.clone(),
+                    amount,
+                    factor: *factor,
+                    typical,
+                })
+            }
+            (Check::FanOut { max_new, period }, TxKind::Transfer(transfer)) => {
+                let from = transfer.from();
+                let since = now - *period;
+                let mut known = HashSet::new();
+                let mut new = HashSet::new();
+                for (time, to) in history.for_account(from).filter_map(|r| outgoing(r, from)) {
+                    if time <= since {
+                        known.insert(to);
+                    } else if time <= now {
+                        new.insert(to);
+                    }
+                }
+                new.insert(transfer.to());
+                let count = new.difference(&known).count();
+                (count > *max_new).then(|| Reason::FanOut {
+                    from: from.clone(),
+                    count,
+                    period: *period,
+                })
+            }
+            (Check::RoundTrip { period }, TxKind::Transfer(transfer)) => {
+                let since = now - *period;
+                history
+                    .for_account_between(transfer.to(), since, now)
+                    .filter_map(|r| outgoing(r, transfer.to()))
+                    .any(|(_, to)| to == transfer.from())
+                    .then(|| Reason::RoundTrip {
+                        from: transfer.from().clone(),
+                        to: transfer.to().clone(),
+                        period: *period,
+                    })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Счёт клиента, с которого он сам списывает деньги
+fn debited(tx: &TxKind) -> Option<&Name> {
+    match tx {
+        TxKind::Withdraw(tx) => Some(tx.account()),
+        TxKind::Transfer(tx) => Some(tx.from()),
+        // Операции самого банка не проверяются
+        TxKind::Deposit(_) | TxKind::Fee(_) | TxKind::Interest(_) => None,
+    }
+}
+
+/// Время и получатель перевода со счёта `from`
+fn outgoing<'a>(record: &'a Record, from: &Name) -> Option<(Timestamp, &'a Name)> {
+    match record.tx() {
+        TxKind::Transfer(tx) if tx.from() == from => Some((record.time(), tx.to())),
+        _ => None,
+    }
+}
+
+/// Результат отправки операции через проверку
+#[derive(Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Applied,
+    Queued(ReviewId, Reason),
+}
+
+/// Набор правил, по которым проверяется каждая операция перед выполнением
+#[derive(Clone, Default, Debug)]
+pub struct Screener {
+    rules: Vec<(Check, Action)>,
+}
+
+impl Screener {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Правила по умолчанию: подозрительные операции уходят на ручную проверку
+    pub fn standard() -> Self {
+        let mut screener = Screener::new();
+        screener.add_rule(
+            Check::LargeAmount {
+                factor: 10,
+                min_history: 5,
+            },
+            Action::Review,
+        );
+        screener.add_rule(
+            Check::FanOut {
+                max_new: 5,
+                period: SECS_PER_DAY,
+            },
+            Action::Review,
+        );
+        screener.add_rule(
+            Check::RoundTrip {
+                period: SECS_PER_DAY,
+            },
+            Action::Review,
+        );
+        screener
+    }
+
+    pub fn add_rule(&mut self, check: Check, action: Action) {
+        self.rules.push((check, action));
+    }
+
+    /// Проверяет операцию по всем правилам и возвращает самое строгое решение
+    pub fn screen(&self, tx: &TxKind, storage: &Storage) -> Verdict {
+        let mut verdict = Verdict::Allow;
+        for (check, action) in &self.rules {
+            if let Some(reason) = check.inspect(tx, storage) {
+                let candidate = match action {
+                    Action::Block => Verdict::Block(reason),
+                    Action::Review => Verdict::Review(reason),
+                };
+                if candidate.severity() > verdict.severity() {
+                    verdict = candidate;
+                }
+            }
+        }
+        verdict
+    }
+
+    /// Проверяет операцию из связки, которая выполняется целиком. Отложить
+    /// на проверку часть связки нельзя, поэтому подозрительная операция
+    /// отклоняется так же, как запрещённая
+    pub fn admit(&self, tx: &TxKind, storage: &Storage) -> Result<(), BankError> {
+        match self.screen(tx, storage) {
+            Verdict::Allow => Ok(()),
+            Verdict::Block(reason) | Verdict::Review(reason) => Err(BankError::Blocked(reason)),
+        }
+    }
+
+    /// Проверяет и выполняет операцию. Подозрительная операция не выполняется,
+    /// а попадает в очередь на ручную проверку, запрещённая - возвращает ошибку
+    pub fn submit(
+        &self,
+        tx: TxKind,
+        storage: &mut Storage,
+        queue: &mut ReviewQueue,
+    ) -> Result<Outcome, BankError> {
+        match self.screen(&tx, storage) {
+            Verdict::Allow => {
+                tx.apply(storage)?;
+                Ok(Outcome::Applied)
+            }
+            Verdict::Block(reason) => Err(BankError::Blocked(reason)),
+            Verdict::Review(reason) => {
+                let id = queue.push(storage.now(), tx, reason.clone());
+                Ok(Outcome::Queued(id, reason))
+            }
+        }
+    }
+}
+
+/// Операция, ожидающая решения оператора
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReviewItem {
+    id: ReviewId,
+    time: Timestamp,
+    reason: Reason,
+    tx: TxKind,
+}
+
+impl ReviewItem {
+    pub fn id(&self) -> ReviewId {
+        self.id
+    }
+
+    pub fn time(&self) -> Timestamp {
+        self.time
+    }
+
+    pub fn reason(&self) -> &Reason {
+        &self.reason
+    }
+
+    pub fn tx(&self) -> &TxKind {
+        &self.tx
+    }
+
+    /// Разбирает строку очереди "Id,Time,Reason,<операция>"
+    fn parse(line: &str) -> Result<ReviewItem, BankError> {
+        let parts: Vec<&str> = line.splitn(4, ',').collect();
+        if parts.len() != 4 {
+            return Err(BankError::InvalidFormat(format!("review line: {line}")));
+        }
+        let tx = parts[3].parse()?;
+        Ok(ReviewItem {
+            id: parts[0].parse()?,
+            time: parts[1].parse()?,
+            reason: Reason::decode(parts[2], &tx)?,
+            tx,
+        })
+    }
+
+    /// Строка очереди на выбранном языке
+    pub fn message(&self, lang: Lang) -> String {
+        format!("#{} {} ({})", self.id, self.tx, self.reason.message(lang))
+    }
+}
+
+impl Display for ReviewItem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message(Lang::En))
+    }
+}
+
+/// Очередь операций на ручную проверку
+#[derive(Clone, Default, Debug)]
+pub struct ReviewQueue {
+    items: BTreeMap<ReviewId, ReviewItem>,
+    next_id: ReviewId,
+}
+
+impl ReviewQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, time: Timestamp, tx: TxKind, reason: Reason) -> ReviewId {
+        self.next_id += 1;
+        self.items.insert(
+            self.next_id,
+            ReviewItem {
+                id: self.next_id,
+                time,
+                reason,
+                tx,
+            },
+        );
+        self.next_id
+    }
+
+    pub fn pending(&self) -> impl Iterator<Item = &ReviewItem> {
+        self.items.values()
+    }
+
+    /// Одобряет операцию и выполняет её. Если выполнить не удалось,
+    /// операция остаётся в очереди
+    pub fn approve(&mut self, id: ReviewId, storage: &mut Storage) -> Result<(), BankError> {
+        let item = self.items.get(&id).ok_or(BankError::ReviewNotFound(id))?;
+        item.tx.apply(storage)?;
+        self.items.remove(&id);
+        Ok(())
+    }
+
+    /// Отклоняет операцию, ничего не выполняя
+    pub fn reject(&mut self, id: ReviewId) -> Result<ReviewItem, BankError> {
+        self.items.remove(&id).ok_or(BankError::ReviewNotFound(id))
+    }
+
+    /// Загружает очередь из CSV-файла формата "Id,Time,Reason,<операция>"
+    pub fn load(file: &str) -> Result<ReviewQueue, BankError> {
+        let mut queue = ReviewQueue::new();
+        if !Path::new(file).exists() {
+            return Ok(queue);
+        }
+
+        let reader = BufReader::new(File::open(file).in_file(file)?);
+        for (i, line) in reader.lines().enumerate() {
+            let line = line.at_line(file, i)?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let item = ReviewItem::parse(line.trim()).at_line(file, i)?;
+            queue.next_id = queue.next_id.max(item.id);
+            queue.items.insert(item.id, item);
+        }
+        Ok(queue)
+    }
+
+    pub fn save(&self, file: &str) -> Result<(), BankError> {
+        let mut data = String::new();
+        for item in self.items.values() {
+            data.push_str(&format!(
+                "{},{},{},{}\n",
+                item.id,
+                item.time,
+                item.reason.encode(),
+                item.tx
+            ));
+        }
+        write_file(file, &data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Balance;
+    use crate::transaction::{Deposit, Transfer, Withdraw};
+
+    fn bank(names: &[&str]) -> Storage {
+        let balances: Vec<(&str, i64)> = names.iter().map(|name| (*name, 10_000)).collect();
+        let mut storage = crate::storage::bank(&balances);
+        storage.set_time(Timestamp::new(SECS_PER_DAY * 10));
+        storage
+    }
+
+    #[test]
+    fn test_large_amount() {
+        let mut storage = bank(&["Alice"]);
+        let mut screener = Screener::new();
+        screener.add_rule(
+            Check::LargeAmount {
+                factor: 10,
+                min_history: 3,
+            },
+            Action::Block,
+        );
+
+        let big: TxKind = Withdraw::new("Alice", Balance::new(5000)).into();
+        // Истории ещё мало, судить о типичной сумме рано
+        assert_eq!(screener.screen(&big, &storage), Verdict::Allow);
+
+        for _ in 0..3 {
+            storage
+                .withdraw(&"Alice".to_string(), Balance::new(100))
+                .unwrap();
+        }
+        assert!(matches!(screener.screen(&big, &storage), Verdict::Block(_)));
+        let small: TxKind = Withdraw::new("Alice", Balance::new(900)).into();
+        assert_eq!(screener.screen(&small, &storage), Verdict::Allow);
+        let deposit: TxKind = Deposit::new("Alice", Balance::new(50_000)).into();
+        assert_eq!(screener.screen(&deposit, &storage), Verdict::Allow);
+    }
+
+    #[test]
+    fn test_fan_out_and_round_trip() {
+        let mut storage = bank(&["A", "B", "C", "D", "E"]);
+        let mut screener = Screener::new();
+        screener.add_rule(
+            Check::FanOut {
+                max_new: 2,
+                period: SECS_PER_DAY,
+            },
+            Action::Review,
+        );
+        screener.add_rule(
+            Check::RoundTrip {
+                period: SECS_PER_DAY,
+            },
+            Action::Block,
+        );
+
+        let a = "A".to_string();
+        storage.set_time(Timestamp::new(SECS_PER_DAY * 5));
+        storage
+            .transfer(&a, &"B".to_string(), Balance::new(1))
+            .unwrap();
+        storage.set_time(Timestamp::new(SECS_PER_DAY * 10));
+        storage
+            .transfer(&a, &"C".to_string(), Balance::new(1))
+            .unwrap();
+        storage
+            .transfer(&a, &"D".to_string(), Balance::new(1))
+            .unwrap();
+
+        // B - старый получатель, E - третий новый за сутки
+        let to_b: TxKind = Transfer::new("A", "B", Balance::new(1)).into();
+        assert_eq!(screener.screen(&to_b, &storage), Verdict::Allow);
+        let to_e: TxKind = Transfer::new("A", "E", Balance::new(1)).into();
+        assert!(matches!(
+            screener.screen(&to_e, &storage),
+            Verdict::Review(_)
+        ));
+
+        // C получил деньги от A и сразу отправляет обратно: блокировка важнее проверки
+        let back: TxKind = Transfer::new("C", "A", Balance::new(1)).into();
+        assert!(matches!(
+            screener.screen(&back, &storage),
+            Verdict::Block(_)
+        ));
+
+        // В связке операцию на проверку не отложить: она отклоняется
+        assert!(screener.admit(&to_b, &storage).is_ok());
+        assert!(matches!(
+            screener.admit(&to_e, &storage),
+            Err(BankError::Blocked(Reason::FanOut { count: 3, .. }))
+        ));
+    }
+
+    #[test]
+    fn test_review_queue() {
+        let mut storage = bank(&["A", "B"]);
+        let mut screener = Screener::new();
+        screener.add_rule(
+            Check::RoundTrip {
+                period: SECS_PER_DAY,
+            },
+            Action::Review,
+        );
+        let mut queue = ReviewQueue::new();
+
+        let there = Transfer::new("A", "B", Balance::new(100));
+        let back = Transfer::new("B", "A", Balance::new(100));
+        let outcome = screener.submit(there.into(), &mut storage, &mut queue);
+        assert_eq!(outcome.unwrap(), Outcome::Applied);
+        let outcome = screener.submit(back.clone().into(), &mut storage, &mut queue);
+        assert!(matches!(outcome.unwrap(), Outcome::Queued(1, _)));
+        assert_eq!(
+            storage.get_balance(&"A".to_string()),
+            Some(Balance::new(9900))
+        );
+
+        let path = std::env::temp_dir().join("bank_review_test.csv");
+        let path = path.to_str().unwrap();
+        queue.save(path).unwrap();
+        let mut queue = ReviewQueue::load(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let item = queue.pending().next().unwrap();
+        assert_eq!(item.tx(), &TxKind::from(back));
+        assert_eq!(
+            item.reason(),
+            &Reason::RoundTrip {
+                from: "B".to_string(),
+                to: "A".to_string(),
+                period: SECS_PER_DAY,
+            }
+        );
+        queue.approve(1, &mut storage).unwrap();
+        assert_eq!(
+            storage.get_balance(&"A".to_string()),
+            Some(Balance::new(10_000))
+        );
+        assert!(queue.reject(1).is_err());
+    }
+}
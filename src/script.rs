@@ -0,0 +1,209 @@
+use super::errors::{BankError, FileContext};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs;
+use std::str::FromStr;
+
+/// Что делать, если команда сценария завершилась ошибкой
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum OnError {
+    /// Остановиться на первой ошибке, выполненное остаётся в силе
+    #[default]
+    Stop,
+    /// Пропустить ошибочную команду и выполнять дальше
+    Continue,
+    /// Весь сценарий - одна транзакция: при любой ошибке всё откатывается
+    Atomic,
+}
+
+impl Display for OnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OnError::Stop => write!(f, "stop"),
+            OnError::Continue => write!(f, "continue"),
+            OnError::Atomic => write!(f, "atomic"),
+        }
+    }
+}
+
+impl FromStr for OnError {
+    type Err = BankError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "stop" => Ok(OnError::Stop),
+            "continue" => Ok(OnError::Continue),
+            "atomic" => Ok(OnError::Atomic),
+            _ => Err(BankError::InvalidFormat(format!("unknown error mode: {s}"))),
+        }
+    }
+}
+
+/// Команда сценария после подстановки переменных
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Command {
+    /// Номер строки в файле, с единицы
+    pub line: usize,
+    pub args: Vec<String>,
+}
+
+impl Display for Command {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.args.join(" "))
+    }
+}
+
+/// Сценарий: команды REPL по одной на строку.
+///
+/// Всё после `#` в начале слова - комментарий, пустые строки пропускаются.
+/// `set имя значение` задаёт переменную, `$имя` или `${имя}` подставляет её значение.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Script {
+    commands: Vec<Command>,
+}
+
+impl Script {
+    /// Читает сценарий из файла. `vars` - начальные значения переменных
+    pub fn load(file: &str, vars: &HashMap<String, String>) -> Result<Self, BankError> {
+        let text = fs::read_to_string(file).in_file(file)?;
+        Self::parse(file, &text, vars)
+    }
+
+    /// Разбирает текст сценария, `source` нужен для сообщений об ошибках
+    pub fn parse(
+        source: &str,
+        text: &str,
+        vars: &HashMap<String, String>,
+    ) -> Result<Self, BankError> {
+        let mut vars = vars.clone();
+        let mut commands = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let words: Vec<&str> = line
+                .split_whitespace()
+                .take_while(|word| !word.starts_with('#'))
+                .collect();
+            let args = words
+                .iter()
+                .map(|word| substitute(word, &vars))
+                .collect::<Result<Vec<_>, _>>()
+                .at_line(source, i)?;
+            match args.as_slice() {
+                [] => {}
+                [set, name, value] if set == "set" => {
+                    if !is_name(name) {
+                        return Err(BankError::InvalidFormat(format!(
+                            "bad variable name: {name}"
+                        )))
+                        .at_line(source, i);
+                    }
+                    vars.insert(name.clone(), value.clone());
+                }
+                [set, ..] if set == "set" => {
+                    return Err(BankError::InvalidFormat(
+                        "set expects a name and a value".to_string(),
+                    ))
+                    .at_line(source, i);
+                }
+                _ => commands.push(Command { line: i + 1, args }),
+            }
+        }
+        Ok(Self { commands })
+    }
+
+    pub fn commands(&self) -> &[Command] {
+        &self.commands
+    }
+}
+
+fn is_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Подставляет переменные в слово. `$` без имени остаётся как есть
+fn substitute(word: &str, vars: &HashMap<String, String>) -> Result<String, BankError> {
+    let mut out = String::new();
+    let mut rest = word;
+    while let Some(pos) = rest.find('$') {
+        out.push_str(&rest[..pos]);
+        rest = &rest[pos + 1..];
+        let (name, tail) = if let Some(braced) = rest.strip_prefix('{') {
+            let end = braced
+                .find('}')
+                .ok_or_else(|| BankError::InvalidFormat(format!("unclosed variable in {word}")))?;
+            (&braced[..end], &braced[end + 1..])
+        } else {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            (&rest[..end], &rest[end..])
+        };
+        if name.is_empty() {
+            out.push('$');
+            continue;
+        }
+        let value = vars
+            .get(name)
+            .ok_or_else(|| BankError::InvalidFormat(format!("undefined variable: {name}")))?;
+        out.push_str(value);
+        rest = tail;
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(script: &Script) -> Vec<(usize, String)> {
+        script
+            .commands()
+            .iter()
+            .map(|c| (c.line, c.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_comments_and_variables() {
+        let text = "# пополнение\n\
+                    set who Alice\n\
+                    set amount 100\n\
+                    \n\
+                    deposit $who $amount # на счёт\n\
+                    transfer ${who} Bob ${amount}0\n\
+                    withdraw $who 5$\n";
+        let vars = HashMap::from([("who".to_string(), "John".to_string())]);
+        let script = Script::parse("day.txt", text, &vars).unwrap();
+        assert_eq!(
+            args(&script),
+            vec![
+                (5, "deposit Alice 100".to_string()),
+                (6, "transfer Alice Bob 1000".to_string()),
+                (7, "withdraw Alice 5$".to_string()),
+            ]
+        );
+
+        // Начальные значения действуют, пока сценарий их не переопределит
+        let script = Script::parse("day.txt", "balance $who", &vars).unwrap();
+        assert_eq!(args(&script), vec![(1, "balance John".to_string())]);
+    }
+
+    #[test]
+    fn test_errors_point_to_line() {
+        let vars = HashMap::new();
+        let err =
+            Script::parse("day.txt", "deposit Alice 1\ndeposit $nobody 1", &vars).unwrap_err();
+        assert!(
+            matches!(err, BankError::File { line: Some(2), .. }),
+            "{err:?}"
+        );
+        assert_eq!(err.name(), "invalid_format");
+        assert!(err.to_string().contains("nobody"), "{err}");
+
+        for text in ["set x", "set bad-name 1", "deposit ${x 1"] {
+            assert!(Script::parse("day.txt", text, &vars).is_err(), "{text}");
+        }
+        assert_eq!("atomic".parse::<OnError>().unwrap(), OnError::Atomic);
+        assert!("later".parse::<OnError>().is_err());
+    }
+}
@@ -1,14 +1,23 @@
-use super::errors::BankError;
-use super::transaction::Transaction;
-use std::collections::HashMap;
+use super::close::{DailyTotals, DayClose, LockPolicy};
+use super::errors::{BankError, FileContext};
+use super::history::{History, RecordId};
+use super::ledger::{Ledger, LedgerAccount, Posting};
+use super::limits::Limits;
+use super::time::{Date, Timestamp};
+use super::transaction::{Deposit, Fee, Interest, Transfer, TxKind, Withdraw};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Display;
 use std::fs;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
-use std::ops::{AddAssign, SubAssign, Add};
+use std::ops::{AddAssign, SubAssign};
 use std::path::Path;
+use std::time::{Duration, Instant};
 
 pub type Name = String;
+// Не используется, но оставлено как было; без allow не проходит clippy -D warnings
+#[allow(dead_code)]
 enum Operation {
     Deposit(i64),
     Withdraw(i64),
@@ -26,6 +35,7 @@ impl Balance {
         self.0
     }
 
+    #[allow(dead_code)]
     fn apply_operations(&mut self, ops: &[Operation]) -> Vec<Operation> {
         let failed_ops = Vec::new();
         for op in ops {
@@ -60,117 +70,1019 @@ impl SubAssign for Balance {
     }
 }
 
+pub type HoldId = u64;
+
+/// Блокировка (холд) суммы на счёте до списания или отмены
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hold {
+    account: Name,
+    amount: Balance,
+    expires_at: Timestamp,
+}
+
+impl Hold {
+    pub fn account(&self) -> &Name {
+        &self.account
+    }
+
+    pub fn amount(&self) -> Balance {
+        self.amount
+    }
+
+    pub fn expires_at(&self) -> Timestamp {
+        self.expires_at
+    }
+}
+
+/// Агрегаты по балансам, обновляемые при каждом изменении счёта
+#[derive(Clone, Default, Debug)]
+struct Aggregates {
+    total: i64,
+    /// Сколько счетов имеют данный баланс
+    counts: BTreeMap<Balance, usize>,
+}
+
+impl Aggregates {
+    fn insert(&mut self, balance: Balance) {
+        self.total += balance.0;
+        *self.counts.entry(balance).or_default() += 1;
+    }
+
+    fn remove(&mut self, balance: Balance) {
+        self.total -= balance.0;
+        if let Some(count) = self.counts.get_mut(&balance) {
+            *count -= 1;
+            if *count == 0 {
+                self.counts.remove(&balance);
+            }
+        }
+    }
+}
+
+/// Когда записывать изменения на диск.
+///
+/// Изменения копятся в памяти и записываются, когда их набралось
+/// `every_changes` или с прошлой записи прошло `interval`, а в конце работы - `flush`.
+/// С `journal` каждая операция сразу дописывается в журнал, поэтому при сбое
+/// теряется только снимок балансов: при загрузке он догоняется по журналу
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct WritePolicy {
+    /// Сколько изменений можно держать в памяти. `None` - сколько угодно
+    pub every_changes: Option<usize>,
+    pub interval: Option<Duration>,
+    pub journal: bool,
+}
+
+impl Default for WritePolicy {
+    /// Запись после каждого изменения
+    fn default() -> Self {
+        Self {
+            every_changes: Some(1),
+            interval: None,
+            journal: false,
+        }
+    }
+}
+
+/// Несохранённые изменения
+#[derive(Clone, Default)]
+struct Pending {
+    changes: usize,
+    /// Есть изменения, которых нет в журнале: счета, холды, закрытие дня
+    structural: bool,
+    /// Последняя операция, уже записанная в файл журнала. `None` - файл журнала
+    /// может не совпадать с журналом в памяти и переписывается целиком
+    journaled: Option<RecordId>,
+    /// Сколько раз изменения попадали на диск: так откат узнаёт, что записанное
+    /// после точки отката надо переписать
+    writes: u64,
+    last_flush: Option<Instant>,
+}
+
+/// Прежнее значение, которое вернёт откат
+#[derive(Clone)]
+enum Undo {
+    /// Баланс счёта; `None` - счёта не было
+    Account(Name, Option<Balance>),
+    Hold(HoldId, Option<Hold>),
+}
+
+/// Точка отката: откат возвращает хранилище в состояние на момент `savepoint`.
+/// Копируются не данные, а только изменённые после неё счета и холды
+#[must_use]
+pub struct Savepoint {
+    undo: usize,
+    history: usize,
+    ledger: usize,
+    next_hold_id: HoldId,
+    closed_until: Option<Date>,
+    lock_policy: LockPolicy,
+    pending: Pending,
+}
+
+#[derive(Clone, Default)]
 pub struct Storage {
     accounts: HashMap<Name, Balance>,
+    aggregates: Aggregates,
+    holds: BTreeMap<HoldId, Hold>,
+    next_hold_id: HoldId,
+    now: Timestamp,
+    history: History,
+    limits: Limits,
+    ledger: Ledger,
+    closed_until: Option<Date>,
+    lock_policy: LockPolicy,
+    write_policy: WritePolicy,
+    pending: Pending,
+    /// Журнал отката, ведётся, пока есть открытые точки отката
+    undo: Vec<Undo>,
+    savepoints: usize,
 }
 
 impl Storage {
     /// Создаёт новый пустой банк
     pub fn new() -> Self {
-        Storage {
-            accounts: HashMap::new(),
-        }
+        Storage::default()
+    }
+
+    /// Текущее время банка. Задаётся снаружи, сами часы хранилище не читает
+    pub fn now(&self) -> Timestamp {
+        self.now
+    }
+
+    /// Переводит часы банка и снимает истёкшие холды
+    pub fn set_time(&mut self, now: Timestamp) {
+        self.now = now;
+        self.expire_holds();
     }
 
     pub fn add_user(&mut self, name: Name) -> Option<Balance> {
-        if self.accounts.contains_key(&name) {
-            None
-        } else {
-            self.accounts.insert(name, Balance::default());
-            Some(Balance::default())
+        self.remember_account(&name);
+        match self.accounts.entry(name) {
+            Entry::Occupied(_) => None,
+            Entry::Vacant(entry) => {
+                entry.insert(Balance::default());
+                self.aggregates.insert(Balance::default());
+                self.touch_structure();
+                Some(Balance::default())
+            }
         }
     }
 
+    /// Удаляет счёт. Остаток считается выданным клиенту и списывается с кассы
     pub fn remove_user(&mut self, name: &Name) -> Option<Balance> {
-        self.accounts.remove(name)
+        let holds: Vec<HoldId> = self
+            .holds
+            .iter()
+            .filter(|(_, hold)| &hold.account == name)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in holds {
+            self.remember_hold(id);
+            self.holds.remove(&id);
+        }
+        self.remember_account(name);
+        let balance = self.accounts.remove(name)?;
+        self.aggregates.remove(balance);
+        self.touch_structure();
+        if balance != Balance::default() {
+            let postings = vec![
+                Posting::debit(LedgerAccount::Customer(name.clone()), balance),
+                Posting::credit(LedgerAccount::Cash, balance),
+            ];
+            // Проводка сбалансирована по построению
+            let _ = self.ledger.post(self.now, "account closed", postings);
+        }
+        Some(balance)
     }
 
+    /// Учётный баланс счёта, включая заблокированные суммы
     pub fn get_balance(&self, name: &Name) -> Option<Balance> {
         self.accounts.get(name).copied()
     }
 
+    /// Доступный баланс: учётный баланс за вычетом активных холдов
+    pub fn get_available_balance(&self, name: &Name) -> Option<Balance> {
+        let mut balance = self.get_balance(name)?;
+        balance -= self.held(name);
+        Some(balance)
+    }
+
+    fn held(&self, name: &Name) -> Balance {
+        let mut held = Balance::default();
+        for hold in self.holds.values().filter(|hold| &hold.account == name) {
+            held += hold.amount;
+        }
+        held
+    }
+
     pub fn deposit(&mut self, name: &Name, amount: Balance) -> Result<(), BankError> {
+        positive(amount)?;
+        let time = self.posting_time()?;
+        self.credit(name, amount)?;
+        self.record(time, Deposit::new(name, amount).into())
+    }
+
+    /// Снимает деньги со счёта, проверяя доступный (а не учётный) баланс и лимиты
+    pub fn withdraw(&mut self, name: &Name, amount: Balance) -> Result<(), BankError> {
+        positive(amount)?;
+        let time = self.posting_time()?;
+        let tx: TxKind = Withdraw::new(name, amount).into();
+        self.limits.check(&tx, &self.history, self.now)?;
+        self.debit(name, amount)?;
+        self.record(time, tx)
+    }
+
+    /// Переводит деньги между счетами. Если перевод невозможен, ни один счёт не меняется
+    pub fn transfer(&mut self, from: &Name, to: &Name, amount: Balance) -> Result<(), BankError> {
+        positive(amount)?;
+        if !self.accounts.contains_key(to) {
+            return Err(BankError::UserNotFound(to.clone()));
+        }
+        let time = self.posting_time()?;
+        let tx: TxKind = Transfer::new(from, to, amount).into();
+        self.limits.check(&tx, &self.history, self.now)?;
+        self.debit(from, amount)?;
+        self.credit(to, amount)?;
+        self.record(time, tx)
+    }
+
+    /// Списывает комиссию банка со счёта клиента
+    pub fn charge_fee(&mut self, name: &Name, amount: Balance) -> Result<(), BankError> {
+        positive(amount)?;
+        let time = self.posting_time()?;
+        self.debit(name, amount)?;
+        self.record(time, Fee::new(name, amount).into())
+    }
+
+    /// Начисляет проценты на счёт клиента
+    pub fn pay_interest(&mut self, name: &Name, amount: Balance) -> Result<(), BankError> {
+        positive(amount)?;
+        let time = self.posting_time()?;
+        self.credit(name, amount)?;
+        self.record(time, Interest::new(name, amount).into())
+    }
+
+    /// Время, которым будет проведена операция. Если текущее время банка
+    /// попадает в закрытый день, операция отклоняется или переносится
+    /// в первый открытый день в зависимости от политики
+    fn posting_time(&self) -> Result<Timestamp, BankError> {
+        match self.closed_until {
+            Some(closed) if self.now < closed.add_days(1).start() => match self.lock_policy {
+                LockPolicy::Reject => Err(BankError::PeriodClosed(closed)),
+                LockPolicy::Adjust => Ok(closed.add_days(1).start()),
+            },
+            _ => Ok(self.now),
+        }
+    }
+
+    /// Заносит выполненную операцию в журнал и проводит её по главной книге
+    fn record(&mut self, time: Timestamp, tx: TxKind) -> Result<(), BankError> {
+        let memo = if time == self.now {
+            tx.to_string()
+        } else {
+            format!("adjustment for {}: {}", self.now.date(), tx)
+        };
+        self.ledger.post(time, &memo, Ledger::postings_for(&tx))?;
+        self.history.push(time, tx);
+        self.pending.changes += 1;
+        Ok(())
+    }
+
+    /// Изменение, которое нельзя восстановить по журналу: счета, холды, периоды
+    fn touch_structure(&mut self) {
+        self.pending.changes += 1;
+        self.pending.structural = true;
+    }
+
+    /// Закрывает день: снимает балансы, считает обороты и запрещает
+    /// проводить операции задним числом в этот и более ранние дни
+    pub fn close_day(&mut self, date: Date) -> Result<DayClose, BankError> {
+        if let Some(closed) = self.closed_until
+            && date <= closed
+        {
+            return Err(BankError::PeriodClosed(closed));
+        }
+
+        let mut balances = self.get_all();
+        balances.sort();
+        let close = DayClose {
+            date,
+            balances,
+            totals: DailyTotals::for_date(&self.history, date),
+        };
+        self.closed_until = Some(date);
+        self.touch_structure();
+        Ok(close)
+    }
+
+    /// Последний закрытый день
+    pub fn closed_until(&self) -> Option<Date> {
+        self.closed_until
+    }
+
+    pub fn set_lock_policy(&mut self, policy: LockPolicy) {
+        self.lock_policy = policy;
+        self.touch_structure();
+    }
+
+    pub fn write_policy(&self) -> WritePolicy {
+        self.write_policy
+    }
+
+    pub fn set_write_policy(&mut self, policy: WritePolicy) {
+        self.write_policy = policy;
+    }
+
+    /// Число изменений, ещё не записанных на диск целиком
+    pub fn unsaved_changes(&self) -> usize {
+        self.pending.changes
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.pending.changes > 0
+    }
+
+    /// Записывает изменения, если этого требует политика записи.
+    /// Иначе с включённым журналом дописывает в него новые операции.
+    /// `now` - монотонное время вызывающего, от него отсчитывается `interval`.
+    /// Возвращает, был ли записан снимок целиком
+    pub fn flush_if_due(&mut self, file: &str, now: Instant) -> Result<bool, BankError> {
+        let policy = self.write_policy;
+        let since = *self.pending.last_flush.get_or_insert(now);
+        if !self.is_dirty() {
+            return Ok(false);
+        }
+        let due = self.pending.structural
+            || self.pending.journaled.is_none()
+            || policy
+                .every_changes
+                .is_some_and(|n| self.pending.changes >= n)
+            || policy
+                .interval
+                .is_some_and(|i| now.duration_since(since) >= i);
+        if due {
+            self.flush(file)?;
+            self.pending.last_flush = Some(now);
+            return Ok(true);
+        }
+        if policy.journal
+            && let Some(journaled) = self.pending.journaled
+        {
+            self.history.append(&history_path(file), journaled)?;
+            self.pending.journaled = Some(self.history.last_id());
+            self.pending.writes += 1;
+        }
+        Ok(false)
+    }
+
+    /// Записывает накопленные изменения, если они есть. Журнал не переписывается,
+    /// а дописывается, так что запись не дорожает с ростом истории
+    pub fn flush(&mut self, file: &str) -> Result<bool, BankError> {
+        if !self.is_dirty() {
+            return Ok(false);
+        }
+        self.save_changes(file)?;
+        self.pending.changes = 0;
+        self.pending.structural = false;
+        self.pending.journaled = Some(self.history.last_id());
+        self.pending.writes += 1;
+        Ok(true)
+    }
+
+    /// Выполняет `f` целиком или никак: при ошибке все изменения откатываются
+    pub fn atomically<T, E>(
+        &mut self,
+        f: impl FnOnce(&mut Storage) -> Result<T, E>,
+    ) -> Result<T, E> {
+        let savepoint = self.savepoint();
+        let result = f(self);
+        match result {
+            Ok(_) => self.commit(savepoint),
+            Err(_) => self.rollback(savepoint),
+        }
+        result
+    }
+
+    /// Открывает точку отката. Закрывается `commit` или `rollback`,
+    /// вложенные точки закрываются раньше внешних
+    pub fn savepoint(&mut self) -> Savepoint {
+        self.savepoints += 1;
+        Savepoint {
+            undo: self.undo.len(),
+            history: self.history.records().len(),
+            ledger: self.ledger.entries().len(),
+            next_hold_id: self.next_hold_id,
+            closed_until: self.closed_until,
+            lock_policy: self.lock_policy,
+            pending: self.pending.clone(),
+        }
+    }
+
+    /// Оставляет изменения после точки отката
+    pub fn commit(&mut self, _savepoint: Savepoint) {
+        self.close_savepoint();
+    }
+
+    /// Отменяет все изменения после точки отката
+    pub fn rollback(&mut self, savepoint: Savepoint) {
+        while self.undo.len() > savepoint.undo {
+            match self.undo.pop() {
+                Some(Undo::Account(name, balance)) => self.restore_account(name, balance),
+                Some(Undo::Hold(id, Some(hold))) => {
+                    self.holds.insert(id, hold);
+                }
+                Some(Undo::Hold(id, None)) => {
+                    self.holds.remove(&id);
+                }
+                None => {}
+            }
+        }
+        self.history.truncate(savepoint.history);
+        self.ledger.truncate(savepoint.ledger);
+        self.next_hold_id = savepoint.next_hold_id;
+        self.closed_until = savepoint.closed_until;
+        self.lock_policy = savepoint.lock_policy;
+        let writes = self.pending.writes;
+        self.pending = savepoint.pending;
+        // Откатанное уже попало на диск: при следующей записи всё переписывается
+        if writes != self.pending.writes {
+            self.pending.writes = writes;
+            self.pending.journaled = None;
+            self.touch_structure();
+        }
+        self.close_savepoint();
+    }
+
+    /// Журнал отката нужен, пока открыта хотя бы одна точка: внешней
+    /// точке нужны и записи, сделанные после вложенной
+    fn close_savepoint(&mut self) {
+        self.savepoints -= 1;
+        if self.savepoints == 0 {
+            self.undo.clear();
+        }
+    }
+
+    fn remember_account(&mut self, name: &Name) {
+        if self.savepoints > 0 {
+            let balance = self.accounts.get(name).copied();
+            self.undo.push(Undo::Account(name.clone(), balance));
+        }
+    }
+
+    fn remember_hold(&mut self, id: HoldId) {
+        if self.savepoints > 0 {
+            let hold = self.holds.get(&id).cloned();
+            self.undo.push(Undo::Hold(id, hold));
+        }
+    }
+
+    fn restore_account(&mut self, name: Name, balance: Option<Balance>) {
+        if let Some(current) = self.accounts.remove(&name) {
+            self.aggregates.remove(current);
+        }
+        if let Some(balance) = balance {
+            self.aggregates.insert(balance);
+            self.accounts.insert(name, balance);
+        }
+    }
+
+    fn credit(&mut self, name: &Name, amount: Balance) -> Result<(), BankError> {
+        self.remember_account(name);
         if let Some(balance) = self.accounts.get_mut(name) {
+            self.aggregates.remove(*balance);
             balance.0 += amount.0;
+            self.aggregates.insert(*balance);
             Ok(())
         } else {
-            Err(BankError::UserNotFound)
+            Err(BankError::UserNotFound(name.clone()))
         }
     }
 
-    pub fn withdraw(&mut self, name: &Name, amount: Balance) -> Result<(), BankError> {
+    fn debit(&mut self, name: &Name, amount: Balance) -> Result<(), BankError> {
+        self.remember_account(name);
+        let held = self.held(name);
         if let Some(balance) = self.accounts.get_mut(name) {
-            if balance.0 >= amount.0 {
+            if balance.0 - held.0 >= amount.0 {
+                self.aggregates.remove(*balance);
                 balance.0 -= amount.0;
+                self.aggregates.insert(*balance);
                 Ok(())
             } else {
-                Err(BankError::FundsLimit)
+                Err(BankError::FundsLimit {
+                    account: name.clone(),
+                    requested: amount,
+                    available: Balance(balance.0 - held.0),
+                })
             }
         } else {
-            Err(BankError::UserNotFound)
+            Err(BankError::UserNotFound(name.clone()))
         }
     }
 
+    /// Журнал выполненных операций
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// Главная книга с двойной записью
+    pub fn ledger(&self) -> &Ledger {
+        &self.ledger
+    }
+
+    /// Счета, баланс которых расходится с главной книгой:
+    /// (имя, баланс в хранилище, сальдо в книге)
+    pub fn ledger_mismatches(&self) -> Vec<(Name, Balance, Balance)> {
+        let mut mismatches = Vec::new();
+        for (name, balance) in &self.accounts {
+            let booked = self.ledger.balance(&LedgerAccount::Customer(name.clone()));
+            if *balance != booked {
+                mismatches.push((name.clone(), *balance, booked));
+            }
+        }
+        for account in self.ledger.accounts() {
+            if let LedgerAccount::Customer(name) = account
+                && !self.accounts.contains_key(name)
+            {
+                let booked = self.ledger.balance(account);
+                if booked != Balance::default() {
+                    mismatches.push((name.clone(), Balance::default(), booked));
+                }
+            }
+        }
+        mismatches.sort();
+        mismatches
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    pub fn limits_mut(&mut self) -> &mut Limits {
+        &mut self.limits
+    }
+
+    /// Блокирует сумму на счёте до момента `expires_at`
+    pub fn place_hold(
+        &mut self,
+        name: &Name,
+        amount: Balance,
+        expires_at: Timestamp,
+    ) -> Result<HoldId, BankError> {
+        positive(amount)?;
+        let available = self
+            .get_available_balance(name)
+            .ok_or_else(|| BankError::UserNotFound(name.clone()))?;
+        if available < amount {
+            return Err(BankError::FundsLimit {
+                account: name.clone(),
+                requested: amount,
+                available,
+            });
+        }
+
+        self.next_hold_id += 1;
+        self.remember_hold(self.next_hold_id);
+        self.holds.insert(
+            self.next_hold_id,
+            Hold {
+                account: name.clone(),
+                amount,
+                expires_at,
+            },
+        );
+        self.touch_structure();
+        Ok(self.next_hold_id)
+    }
+
+    /// Снимает блокировку без списания денег
+    pub fn release_hold(&mut self, id: HoldId) -> Option<Hold> {
+        self.remember_hold(id);
+        let hold = self.holds.remove(&id)?;
+        self.touch_structure();
+        Some(hold)
+    }
+
+    /// Списывает `amount` в счёт холда: со счёта или переводом на счёт `to`.
+    /// Остаток холда освобождается
+    pub fn capture_hold(
+        &mut self,
+        id: HoldId,
+        amount: Balance,
+        to: Option<&Name>,
+    ) -> Result<(), BankError> {
+        let hold = self.holds.get(&id).ok_or(BankError::HoldNotFound(id))?;
+        if amount > hold.amount {
+            return Err(BankError::FundsLimit {
+                account: hold.account.clone(),
+                requested: amount,
+                available: hold.amount,
+            });
+        }
+        self.remember_hold(id);
+        let hold = self.holds.remove(&id).ok_or(BankError::HoldNotFound(id))?;
+        let result = match to {
+            Some(to) => self.transfer(&hold.account, to, amount),
+            None => self.withdraw(&hold.account, amount),
+        };
+        // Если списание не прошло (например, из-за лимита), холд остаётся на месте
+        if result.is_err() {
+            self.holds.insert(id, hold);
+        } else {
+            self.touch_structure();
+        }
+        result
+    }
+
+    pub fn get_hold(&self, id: HoldId) -> Option<&Hold> {
+        self.holds.get(&id)
+    }
+
+    /// Активные холды счёта
+    pub fn holds(&self, name: &Name) -> Vec<(HoldId, &Hold)> {
+        self.holds
+            .iter()
+            .filter(|(_, hold)| &hold.account == name)
+            .map(|(id, hold)| (*id, hold))
+            .collect()
+    }
+
+    /// Снимает холды, срок которых истёк к текущему времени банка
+    pub fn expire_holds(&mut self) -> Vec<Hold> {
+        let now = self.now;
+        let expired: Vec<HoldId> = self
+            .holds
+            .iter()
+            .filter(|(_, hold)| hold.expires_at <= now)
+            .map(|(id, _)| *id)
+            .collect();
+        let expired: Vec<Hold> = expired
+            .into_iter()
+            .filter_map(|id| {
+                self.remember_hold(id);
+                self.holds.remove(&id)
+            })
+            .collect();
+        if !expired.is_empty() {
+            self.touch_structure();
+        }
+        expired
+    }
+
     pub fn get_all(&self) -> Vec<(Name, Balance)> {
         self.accounts.iter().map(|(n, b)| (n.clone(), *b)).collect()
     }
 
-    /// Загружает данные из CSV-файла или создаёт хранилище с дефолтными пользователями
+    /// Счета без копирования имён, в произвольном порядке
+    pub fn accounts(&self) -> impl Iterator<Item = (&Name, Balance)> {
+        self.accounts.iter().map(|(n, b)| (n, *b))
+    }
+
+    /// Имена счетов без копирования, в произвольном порядке
+    pub fn names(&self) -> impl Iterator<Item = &Name> {
+        self.accounts.keys()
+    }
+
+    /// Количество счетов
+    pub fn len(&self) -> usize {
+        self.accounts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+    }
+
+    /// Сумма балансов всех счетов, за O(1)
+    pub fn total_balance(&self) -> Balance {
+        Balance(self.aggregates.total)
+    }
+
+    /// Наибольший баланс, без обхода счетов
+    pub fn max_balance(&self) -> Option<Balance> {
+        self.aggregates.counts.last_key_value().map(|(b, _)| *b)
+    }
+
+    /// Наименьший баланс, без обхода счетов
+    pub fn min_balance(&self) -> Option<Balance> {
+        self.aggregates.counts.first_key_value().map(|(b, _)| *b)
+    }
+
+    /// Загружает данные из CSV-файла. Журнал операций читается из соседнего файла
+    /// (см. `history_path`). Если файла нет - ошибка: новый банк создаёт `create`.
+    /// Операции журнала новее снимка балансов проводятся заново
     pub fn load_data(file: &str) -> Result<Storage, BankError> {
+        if !Path::new(file).exists() {
+            return Err(BankError::DataNotFound(file.to_owned()));
+        }
         let mut storage = Storage::new();
+        let path = history_path(file);
+        storage.history = History::load(&path)?;
+        storage.load_periods(&periods_path(file))?;
+        storage.load_holds(&holds_path(file))?;
+        if let Some(snapshot) = storage.load_balances(file)? {
+            for record in storage.history.after(snapshot).to_vec() {
+                storage.replay(record.time(), record.tx()).in_file(&path)?;
+            }
+        }
+        storage.pending.journaled = Some(storage.history.last_id());
+        Ok(storage)
+    }
 
-        // Проверяем, существует ли файл
-        if Path::new(file).exists() {
-            // Открываем файл
-            let file = File::open(file)?;
-
-            // Оборачиваем файл в BufReader
-            // BufReader читает данные блоками и хранит их в буфере,
-            // поэтому построчное чтение (lines()) работает быстрее, чем читать по байту
-            let reader = BufReader::new(file);
-
-            // Читаем файл построчно
-            for line in reader.lines() {
-                // Каждая строка — это Result<String>, поэтому делаем if let Ok
-                if let Ok(line) = line {
-                    // Разделяем строку по запятой: "Name,Balance"
-                    let parts: Vec<&str> = line.trim().split(',').collect();
-
-                    if parts.len() == 2 {
-                        let name = parts[0].to_string();
-                        // Пробуем преобразовать баланс из строки в число
-                        let balance = Balance(parts[1].parse()?);
-
-                        // Добавляем пользователя и выставляем баланс
-                        storage.add_user(name.clone());
-                        let _ = storage.deposit(&name, balance);
-                    }
+    /// Создаёт новый банк в файле `file`: пустой или со счетами из файла `fixture`
+    /// в том же формате "Name,Balance". Существующие данные не перезаписываются
+    pub fn create(file: &str, fixture: Option<&str>) -> Result<Storage, BankError> {
+        for path in [
+            file.to_owned(),
+            history_path(file),
+            periods_path(file),
+            holds_path(file),
+        ] {
+            if Path::new(&path).exists() {
+                return Err(BankError::DataExists(path));
+            }
+        }
+        let mut storage = Storage::seeded(fixture)?;
+        storage.save(file)?;
+        storage.pending.journaled = Some(storage.history.last_id());
+        Ok(storage)
+    }
+
+    /// Хранилище в памяти: пустое или со счетами из файла `fixture`
+    pub fn seeded(fixture: Option<&str>) -> Result<Storage, BankError> {
+        let mut storage = Storage::new();
+        if let Some(fixture) = fixture {
+            storage.load_balances(fixture)?;
+        }
+        Ok(storage)
+    }
+
+    /// Открывает счета из файла "Name,Balance" и зачисляет на них балансы.
+    /// Если хоть одна строка не подошла или счёт уже есть, данные не меняются
+    pub fn import_accounts(&mut self, file: &str) -> Result<usize, BankError> {
+        let text = fs::read_to_string(file).in_file(file)?;
+        self.atomically(|storage| {
+            let mut count = 0;
+            for (i, line) in text.lines().enumerate() {
+                if line.trim().is_empty() {
+                    continue;
                 }
+                let Some((name, balance)) = line.trim().split_once(',') else {
+                    return Err(BankError::InvalidFormat(format!("account line: {line}"))
+                        .in_file(file, Some(i + 1)));
+                };
+                let name: Name = name.to_owned();
+                let balance = Balance::new(balance.parse().at_line(file, i)?);
+                if storage.add_user(name.clone()).is_none() {
+                    return Err(BankError::UserExists(name).in_file(file, Some(i + 1)));
+                }
+                if balance.value() != 0 {
+                    storage.deposit(&name, balance).at_line(file, i)?;
+                }
+                count += 1;
             }
-        } else {
-            // если файла нет, создаём пользователей с нуля
-            for u in ["John", "Alice", "Bob", "Vasya"] {
-                storage.add_user(u.to_string());
+            Ok(count)
+        })
+    }
+
+    /// Читает балансы из CSV-файла "Name,Balance" как входящие остатки.
+    /// Возвращает номер последней операции журнала, вошедшей в снимок,
+    /// если файл записан с журналом (строка "# journal <номер>")
+    fn load_balances(&mut self, path: &str) -> Result<Option<RecordId>, BankError> {
+        let mut snapshot = None;
+        let file = File::open(path).in_file(path)?;
+
+        // Оборачиваем файл в BufReader
+        // BufReader читает данные блоками и хранит их в буфере,
+        // поэтому построчное чтение (lines()) работает быстрее, чем читать по байту
+        let reader = BufReader::new(file);
+
+        // Читаем файл построчно
+        for (i, line) in reader.lines().enumerate() {
+            // Каждая строка — это Result<String>: нечитаемые строки пропускаем
+            let Ok(line) = line else {
+                continue;
+            };
+            if let Some(id) = line.trim().strip_prefix(JOURNAL_MARK) {
+                snapshot = Some(id.trim().parse().at_line(path, i)?);
+                continue;
+            }
+            // Разделяем строку по запятой: "Name,Balance"
+            let parts: Vec<&str> = line.trim().split(',').collect();
+
+            if parts.len() == 2 {
+                let name = parts[0].to_string();
+                // Пробуем преобразовать баланс из строки в число
+                let balance = Balance(parts[1].parse().at_line(path, i)?);
+
+                // Добавляем пользователя и выставляем баланс.
+                // Это не операция, поэтому в журнал она не попадает,
+                // а в главную книгу идёт как входящий остаток
+                let postings = vec![
+                    Posting::debit(LedgerAccount::Cash, balance),
+                    Posting::credit(LedgerAccount::Customer(name.clone()), balance),
+                ];
+                self.ledger.post(self.now, "opening balance", postings)?;
+                self.remember_account(&name);
+                match self.accounts.entry(name) {
+                    Entry::Occupied(mut entry) => {
+                        self.aggregates.remove(*entry.get());
+                        *entry.get_mut() += balance;
+                        self.aggregates.insert(*entry.get());
+                    }
+                    Entry::Vacant(entry) => {
+                        entry.insert(balance);
+                        self.aggregates.insert(balance);
+                    }
+                }
             }
         }
+        Ok(snapshot)
+    }
 
-        Ok(storage)
+    /// Проводит операцию из журнала, которая не попала в снимок балансов
+    fn replay(&mut self, time: Timestamp, tx: &TxKind) -> Result<(), BankError> {
+        match tx {
+            TxKind::Deposit(tx) => self.credit(tx.account(), tx.amount())?,
+            TxKind::Interest(tx) => self.credit(tx.account(), tx.amount())?,
+            TxKind::Withdraw(tx) => self.debit(tx.account(), tx.amount())?,
+            TxKind::Fee(tx) => self.debit(tx.account(), tx.amount())?,
+            TxKind::Transfer(tx) => {
+                self.debit(tx.from(), tx.amount())?;
+                self.credit(tx.to(), tx.amount())?;
+            }
+        }
+        self.ledger.post_tx(time, tx)?;
+        Ok(())
     }
 
-    /// Сохраняет текущее состояние Storage в CSV-файл
+    /// Сохраняет текущее состояние Storage в CSV-файл, а журнал операций - рядом с ним.
+    /// Журнал пишется первым: снимок балансов никогда не опережает его
     pub fn save(&self, file: &str) -> Result<(), BankError> {
+        self.history.save(&history_path(file))?;
+        self.save_balances(file)?;
+        self.save_periods(&periods_path(file))?;
+        self.save_holds(&holds_path(file))?;
+        Ok(())
+    }
+
+    /// Записывает изменения с прошлой записи: новые операции дописываются
+    /// в журнал, снимок балансов заменяется, а периоды и холды - только если менялись
+    fn save_changes(&self, file: &str) -> Result<(), BankError> {
+        let Some(journaled) = self.pending.journaled else {
+            return self.save(file);
+        };
+        self.history.append(&history_path(file), journaled)?;
+        self.save_balances(file)?;
+        if self.pending.structural {
+            self.save_periods(&periods_path(file))?;
+            self.save_holds(&holds_path(file))?;
+        }
+        Ok(())
+    }
+
+    /// Снимок балансов "Name,Balance", с журналом - с номером последней учтённой операции
+    fn save_balances(&self, file: &str) -> Result<(), BankError> {
         let mut data = String::new();
+        if self.write_policy.journal {
+            data.push_str(&format!("{JOURNAL_MARK} {}\n", self.history.last_id()));
+        }
 
         // Собираем все данные в одну строку формата "Name,Balance"
-        for (name, balance) in self.get_all() {
+        for (name, balance) in self.accounts() {
             data.push_str(&format!("{},{}\n", name, balance));
         }
 
         // Записываем в файл
         // Здесь мы не используем BufWriter, потому что сразу пишем всю строку целиком.
-        fs::write(file, data)?;
+        write_file(file, &data)
+    }
+
+    /// Читает состояние закрытия периодов: строки "closed,YYYY-MM-DD" и "policy,reject|adjust"
+    fn load_periods(&mut self, file: &str) -> Result<(), BankError> {
+        if !Path::new(file).exists() {
+            return Ok(());
+        }
+        for (i, line) in fs::read_to_string(file).in_file(file)?.lines().enumerate() {
+            match line.trim().split_once(',') {
+                Some(("closed", date)) => self.closed_until = Some(date.parse().at_line(file, i)?),
+                Some(("policy", policy)) => self.lock_policy = policy.parse().at_line(file, i)?,
+                None if line.trim().is_empty() => {}
+                _ => {
+                    return Err(BankError::InvalidFormat(format!("periods line: {line}")))
+                        .at_line(file, i);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn save_periods(&self, file: &str) -> Result<(), BankError> {
+        let mut data = format!("policy,{}\n", self.lock_policy);
+        if let Some(closed) = self.closed_until {
+            data.push_str(&format!("closed,{closed}\n"));
+        }
+        write_file(file, &data)
+    }
+
+    /// Читает холды: строка "next,<номер>" и строки "Id,Account,Amount,ExpiresAt".
+    /// Истёкшие снимаются при следующей установке часов
+    fn load_holds(&mut self, file: &str) -> Result<(), BankError> {
+        if !Path::new(file).exists() {
+            return Ok(());
+        }
+        for (i, line) in fs::read_to_string(file).in_file(file)?.lines().enumerate() {
+            let parts: Vec<&str> = line.trim().split(',').collect();
+            match parts.as_slice() {
+                [""] => {}
+                ["next", id] => self.next_hold_id = id.parse().at_line(file, i)?,
+                [id, account, amount, expires_at] => {
+                    let id: HoldId = id.parse().at_line(file, i)?;
+                    let hold = Hold {
+                        account: account.to_string(),
+                        amount: Balance(amount.parse().at_line(file, i)?),
+                        expires_at: expires_at.parse().at_line(file, i)?,
+                    };
+                    self.next_hold_id = self.next_hold_id.max(id);
+                    self.holds.insert(id, hold);
+                }
+                _ => {
+                    return Err(BankError::InvalidFormat(format!("hold line: {line}")))
+                        .at_line(file, i);
+                }
+            }
+        }
         Ok(())
     }
+
+    fn save_holds(&self, file: &str) -> Result<(), BankError> {
+        let mut data = format!("next,{}\n", self.next_hold_id);
+        for (id, hold) in &self.holds {
+            data.push_str(&format!(
+                "{id},{},{},{}\n",
+                hold.account, hold.amount, hold.expires_at
+            ));
+        }
+        write_file(file, &data)
+    }
+}
+
+/// Строка снимка балансов с номером последней учтённой операции журнала
+const JOURNAL_MARK: &str = "# journal";
+
+/// Деньги двигаются только положительными суммами: иначе снятие пополняло бы счёт
+fn positive(amount: Balance) -> Result<(), BankError> {
+    if amount.value() <= 0 {
+        return Err(BankError::InvalidArgument(format!(
+            "amount must be positive: {amount}"
+        )));
+    }
+    Ok(())
+}
+
+/// Заменяет файл целиком: пишет во временный рядом и переименовывает,
+/// поэтому при сбое на диске остаётся либо старое, либо новое содержимое
+pub fn write_file(file: &str, data: &str) -> Result<(), BankError> {
+    let tmp = format!("{file}.tmp");
+    fs::write(&tmp, data).in_file(&tmp)?;
+    fs::rename(&tmp, file).in_file(file)?;
+    Ok(())
+}
+
+/// Путь к файлу журнала операций для файла балансов: "balance.csv" -> "balance.history.csv"
+pub fn history_path(file: &str) -> String {
+    sibling_path(file, "history")
+}
+
+/// Путь к файлу закрытых периодов: "balance.csv" -> "balance.periods.csv"
+pub fn periods_path(file: &str) -> String {
+    sibling_path(file, "periods")
+}
+
+/// Путь к файлу холдов: "balance.csv" -> "balance.holds.csv"
+pub fn holds_path(file: &str) -> String {
+    sibling_path(file, "holds")
+}
+
+fn sibling_path(file: &str, kind: &str) -> String {
+    match file.strip_suffix(".csv") {
+        Some(stem) => format!("{stem}.{kind}.csv"),
+        None => format!("{file}.{kind}"),
+    }
+}
+
+/// Банк для тестов: счета с начальными балансами, нулевой баланс не зачисляется
+#[cfg(test)]
+pub(crate) fn bank(balances: &[(&str, i64)]) -> Storage {
+    let mut storage = Storage::new();
+    for (name, balance) in balances {
+        storage.add_user(name.to_string());
+        if *balance > 0 {
+            storage
+                .deposit(&name.to_string(), Balance::new(*balance))
+                .unwrap();
+        }
+    }
+    storage
 }
 
 #[cfg(test)]
@@ -329,4 +1241,563 @@ mod tests {
         assert!(failed_ops.is_empty());
         assert_eq!(balance, Balance(50));
     }
+
+    #[test]
+    fn test_holds() {
+        let mut storage = Storage::new();
+        let alice = "Alice".to_string();
+        let bob = "Bob".to_string();
+        storage.add_user(alice.clone());
+        storage.add_user(bob.clone());
+        storage.deposit(&alice, Balance(100)).unwrap();
+
+        let hold = storage
+            .place_hold(&alice, Balance(70), Timestamp::new(100))
+            .unwrap();
+        assert_eq!(storage.get_balance(&alice), Some(Balance(100)));
+        assert_eq!(storage.get_available_balance(&alice), Some(Balance(30)));
+        assert!(storage.withdraw(&alice, Balance(50)).is_err());
+        assert!(
+            storage
+                .place_hold(&alice, Balance(40), Timestamp::new(100))
+                .is_err()
+        );
+
+        // Частичное списание переводом, остаток холда освобождается
+        assert!(storage.capture_hold(hold, Balance(80), Some(&bob)).is_err());
+        storage.capture_hold(hold, Balance(50), Some(&bob)).unwrap();
+        assert_eq!(storage.get_balance(&alice), Some(Balance(50)));
+        assert_eq!(storage.get_available_balance(&alice), Some(Balance(50)));
+        assert_eq!(storage.get_balance(&bob), Some(Balance(50)));
+        assert!(storage.capture_hold(hold, Balance(1), None).is_err());
+
+        let hold = storage
+            .place_hold(&alice, Balance(20), Timestamp::new(100))
+            .unwrap();
+        assert_eq!(
+            storage.release_hold(hold).map(|h| h.amount()),
+            Some(Balance(20))
+        );
+        assert_eq!(storage.get_available_balance(&alice), Some(Balance(50)));
+    }
+
+    #[test]
+    fn test_holds_expire() {
+        let mut storage = Storage::new();
+        let alice = "Alice".to_string();
+        storage.add_user(alice.clone());
+        storage.deposit(&alice, Balance(100)).unwrap();
+        storage
+            .place_hold(&alice, Balance(60), Timestamp::new(100))
+            .unwrap();
+        storage
+            .place_hold(&alice, Balance(10), Timestamp::new(200))
+            .unwrap();
+
+        storage.set_time(Timestamp::new(99));
+        assert_eq!(storage.get_available_balance(&alice), Some(Balance(30)));
+        storage.set_time(Timestamp::new(100));
+        assert_eq!(storage.get_available_balance(&alice), Some(Balance(90)));
+        assert_eq!(storage.holds(&alice).len(), 1);
+    }
+
+    #[test]
+    fn test_transfer_is_recorded_and_atomic() {
+        let mut storage = Storage::new();
+        let alice = "Alice".to_string();
+        let bob = "Bob".to_string();
+        storage.add_user(alice.clone());
+        storage.add_user(bob.clone());
+        storage.set_time(Timestamp::new(5));
+        storage.deposit(&alice, Balance(100)).unwrap();
+
+        assert!(
+            storage
+                .transfer(&alice, &"Nobody".to_string(), Balance(10))
+                .is_err()
+        );
+        assert_eq!(storage.get_balance(&alice), Some(Balance(100)));
+
+        storage.transfer(&alice, &bob, Balance(40)).unwrap();
+        let records = storage.history().records();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].time(), Timestamp::new(5));
+        assert_eq!(
+            records[1].tx(),
+            &Transfer::new("Alice", "Bob", Balance(40)).into()
+        );
+    }
+
+    #[test]
+    fn test_limits() {
+        use crate::limits::Rule;
+        use crate::time::SECS_PER_DAY;
+
+        let mut storage = Storage::new();
+        let alice = "Alice".to_string();
+        let bob = "Bob".to_string();
+        storage.add_user(alice.clone());
+        storage.add_user(bob.clone());
+        storage.deposit(&alice, Balance(1000)).unwrap();
+        storage.limits_mut().add_account_rule(
+            &alice,
+            Rule::MaxWithdrawn {
+                amount: Balance(100),
+                period: SECS_PER_DAY,
+            },
+        );
+        storage.limits_mut().set_class(&alice, "retail");
+        storage
+            .limits_mut()
+            .add_class_rule("retail", Rule::MaxSingleTransfer(Balance(300)));
+        storage.limits_mut().add_class_rule(
+            "retail",
+            Rule::MaxTransfers {
+                count: 2,
+                period: SECS_PER_DAY,
+            },
+        );
+
+        storage.set_time(Timestamp::new(SECS_PER_DAY));
+        storage.withdraw(&alice, Balance(60)).unwrap();
+        let err = storage.withdraw(&alice, Balance(50)).unwrap_err();
+        assert!(matches!(err, BankError::LimitExceeded { .. }));
+        assert!(err.to_string().contains("max withdrawn 100"));
+
+        assert!(storage.transfer(&alice, &bob, Balance(301)).is_err());
+        storage.transfer(&alice, &bob, Balance(10)).unwrap();
+        storage.transfer(&alice, &bob, Balance(10)).unwrap();
+        assert!(storage.transfer(&alice, &bob, Balance(10)).is_err());
+        assert_eq!(storage.get_balance(&bob), Some(Balance(20)));
+
+        // На следующий день счётчики обнуляются
+        storage.set_time(Timestamp::new(2 * SECS_PER_DAY));
+        storage.withdraw(&alice, Balance(100)).unwrap();
+        storage.transfer(&alice, &bob, Balance(10)).unwrap();
+    }
+
+    #[test]
+    fn test_history_path() {
+        assert_eq!(history_path("balance.csv"), "balance.history.csv");
+        assert_eq!(history_path("bank"), "bank.history");
+    }
+
+    #[test]
+    fn test_aggregates_follow_balances() {
+        let mut storage = Storage::new();
+        let alice = "Alice".to_string();
+        let bob = "Bob".to_string();
+        assert_eq!(storage.max_balance(), None);
+        storage.add_user(alice.clone());
+        storage.add_user(bob.clone());
+        storage.deposit(&alice, Balance(100)).unwrap();
+        storage.transfer(&alice, &bob, Balance(70)).unwrap();
+        assert!(storage.withdraw(&bob, Balance(500)).is_err());
+        storage.withdraw(&bob, Balance(5)).unwrap();
+
+        assert_eq!(storage.total_balance(), Balance(95));
+        assert_eq!(storage.max_balance(), Some(Balance(65)));
+        assert_eq!(storage.min_balance(), Some(Balance(30)));
+        let total: i64 = storage.accounts().map(|(_, b)| b.0).sum();
+        assert_eq!(total, 95);
+
+        storage.remove_user(&bob);
+        assert_eq!(storage.total_balance(), Balance(30));
+        assert_eq!(storage.max_balance(), Some(Balance(30)));
+        assert_eq!(storage.len(), 1);
+    }
+
+    /// Банк с Alice (100) и Bob (0) и проверка, что операция с суммой 0 или -50
+    /// отклоняется как неверный аргумент и ничего не меняет
+    fn assert_rejects_non_positive(op: impl Fn(&mut Storage, Balance) -> Result<(), BankError>) {
+        let mut storage = Storage::new();
+        let alice = "Alice".to_string();
+        storage.add_user(alice.clone());
+        storage.add_user("Bob".to_string());
+        storage.deposit(&alice, Balance(100)).unwrap();
+        for amount in [Balance(0), Balance(-50)] {
+            let err = op(&mut storage, amount).unwrap_err();
+            assert!(matches!(err, BankError::InvalidArgument(_)), "{err}");
+        }
+        assert_eq!(storage.get_balance(&alice), Some(Balance(100)));
+        assert_eq!(storage.get_balance(&"Bob".to_string()), Some(Balance(0)));
+        assert_eq!(storage.get_available_balance(&alice), Some(Balance(100)));
+        assert_eq!(storage.history().records().len(), 1);
+    }
+
+    #[test]
+    fn test_deposit_rejects_non_positive() {
+        assert_rejects_non_positive(|s, amount| s.deposit(&"Alice".to_string(), amount));
+    }
+
+    #[test]
+    fn test_withdraw_rejects_non_positive() {
+        assert_rejects_non_positive(|s, amount| s.withdraw(&"Alice".to_string(), amount));
+    }
+
+    #[test]
+    fn test_transfer_rejects_non_positive() {
+        assert_rejects_non_positive(|s, amount| {
+            s.transfer(&"Alice".to_string(), &"Bob".to_string(), amount)
+        });
+    }
+
+    #[test]
+    fn test_charge_fee_rejects_non_positive() {
+        assert_rejects_non_positive(|s, amount| s.charge_fee(&"Alice".to_string(), amount));
+    }
+
+    #[test]
+    fn test_pay_interest_rejects_non_positive() {
+        assert_rejects_non_positive(|s, amount| s.pay_interest(&"Alice".to_string(), amount));
+    }
+
+    #[test]
+    fn test_place_hold_rejects_non_positive() {
+        assert_rejects_non_positive(|s, amount| {
+            s.place_hold(&"Alice".to_string(), amount, Timestamp::new(100))
+                .map(|_| ())
+        });
+    }
+
+    #[test]
+    fn test_ledger_follows_storage() {
+        let mut storage = Storage::new();
+        let alice = "Alice".to_string();
+        let bob = "Bob".to_string();
+        storage.add_user(alice.clone());
+        storage.add_user(bob.clone());
+        storage.deposit(&alice, Balance(100)).unwrap();
+        storage.transfer(&alice, &bob, Balance(40)).unwrap();
+        storage.charge_fee(&bob, Balance(3)).unwrap();
+        storage.pay_interest(&alice, Balance(1)).unwrap();
+        storage.withdraw(&alice, Balance(11)).unwrap();
+        assert!(storage.withdraw(&alice, Balance(1000)).is_err());
+        storage.remove_user(&bob);
+
+        let trial = storage.ledger().trial_balance();
+        assert!(trial.is_balanced());
+        assert!(storage.ledger_mismatches().is_empty());
+        assert_eq!(storage.ledger().balance(&LedgerAccount::Cash), Balance(52));
+        assert_eq!(
+            storage.ledger().balance(&LedgerAccount::FeeIncome),
+            Balance(3)
+        );
+    }
+
+    #[test]
+    fn test_rollback_undoes_only_touched_state() {
+        let mut storage = Storage::new();
+        let alice = "Alice".to_string();
+        let bob = "Bob".to_string();
+        storage.add_user(alice.clone());
+        storage.deposit(&alice, Balance(100)).unwrap();
+        storage.set_time(Timestamp::new(0));
+        let kept = storage
+            .place_hold(&alice, Balance(10), Timestamp::new(100))
+            .unwrap();
+
+        let outer = storage.savepoint();
+        storage.add_user(bob.clone());
+        storage.transfer(&alice, &bob, Balance(30)).unwrap();
+        let inner = storage.savepoint();
+        storage.pay_interest(&bob, Balance(5)).unwrap();
+        storage.release_hold(kept);
+        storage.commit(inner);
+        storage.remove_user(&alice);
+        storage.rollback(outer);
+
+        assert_eq!(storage.get_all(), vec![(alice.clone(), Balance(100))]);
+        assert_eq!(storage.total_balance(), Balance(100));
+        assert_eq!(storage.holds(&alice).len(), 1);
+        assert_eq!(storage.history().records().len(), 1);
+        assert!(storage.ledger_mismatches().is_empty());
+        assert!(storage.ledger().trial_balance().is_balanced());
+
+        // Номера продолжаются с места отката
+        storage.withdraw(&alice, Balance(1)).unwrap();
+        assert_eq!(storage.history().records()[1].id(), 2);
+        let err: Result<(), BankError> = storage.atomically(|storage| {
+            storage.withdraw(&alice, Balance(1))?;
+            storage.withdraw(&alice, Balance(1000))
+        });
+        assert!(err.is_err());
+        assert_eq!(storage.get_balance(&alice), Some(Balance(99)));
+    }
+
+    #[test]
+    fn test_close_day_locks_period() {
+        let mut storage = Storage::new();
+        let alice = "Alice".to_string();
+        let bob = "Bob".to_string();
+        storage.add_user(alice.clone());
+        storage.add_user(bob.clone());
+        let day: Date = "2024-05-20".parse().unwrap();
+
+        storage.set_time(day.start() + 10);
+        storage.deposit(&alice, Balance(100)).unwrap();
+        storage.transfer(&alice, &bob, Balance(30)).unwrap();
+        storage.charge_fee(&bob, Balance(2)).unwrap();
+        storage.set_time(day.add_days(1).start());
+        storage.deposit(&alice, Balance(1)).unwrap();
+
+        let close = storage.close_day(day).unwrap();
+        assert_eq!(close.totals.deposits, Balance(100));
+        assert_eq!(close.totals.transfers, Balance(30));
+        assert_eq!(close.totals.fees, Balance(2));
+        assert_eq!(close.totals.operations, 3);
+        assert_eq!(close.total_balance(), Balance(99));
+        assert!(close.to_string().contains("Alice,71"));
+        assert!(storage.close_day(day).is_err());
+
+        // Операция задним числом отклоняется
+        storage.set_time(day.start() + 100);
+        let err = storage.deposit(&alice, Balance(5)).unwrap_err();
+        assert!(matches!(err, BankError::PeriodClosed(_)));
+        assert_eq!(storage.get_balance(&alice), Some(Balance(71)));
+
+        // ...или проводится корректировкой в первом открытом дне
+        storage.set_lock_policy(LockPolicy::Adjust);
+        storage.deposit(&alice, Balance(5)).unwrap();
+        let record = storage.history().records().last().unwrap();
+        assert_eq!(record.time(), day.add_days(1).start());
+        let entry = storage.ledger().entries().last().unwrap();
+        assert!(entry.memo().starts_with("adjustment for 2024-05-20"));
+    }
+
+    #[test]
+    fn test_create_instead_of_default_users() {
+        let dir = std::env::temp_dir();
+        let file = dir
+            .join("bank_create_test.csv")
+            .to_string_lossy()
+            .into_owned();
+        let fixture = dir
+            .join("bank_fixture_test.csv")
+            .to_string_lossy()
+            .into_owned();
+        fs::write(&fixture, "Alice,100\nBob,50\n").unwrap();
+
+        // Нет файла - нет и банка, никаких выдуманных пользователей
+        let err = Storage::load_data(&file).err();
+        assert!(matches!(err, Some(BankError::DataNotFound(_))), "{err:?}");
+
+        let storage = Storage::create(&file, Some(&fixture)).unwrap();
+        assert_eq!(storage.len(), 2);
+        assert_eq!(storage.ledger_mismatches(), vec![]);
+        let loaded = Storage::load_data(&file).unwrap();
+        let (mut expected, mut actual) = (storage.get_all(), loaded.get_all());
+        expected.sort();
+        actual.sort();
+        assert_eq!(actual, expected);
+
+        // Поверх существующих данных новый банк не создаётся
+        let err = Storage::create(&file, None).err();
+        assert!(matches!(err, Some(BankError::DataExists(_))), "{err:?}");
+        assert_eq!(Storage::load_data(&file).unwrap().len(), 2);
+
+        for path in [
+            &file,
+            &history_path(&file),
+            &periods_path(&file),
+            &holds_path(&file),
+            &fixture,
+        ] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_import_accounts_rejects_existing() {
+        let file = std::env::temp_dir()
+            .join("bank_import_accounts_test.csv")
+            .to_string_lossy()
+            .into_owned();
+        let alice = "Alice".to_string();
+        let mut storage = Storage::new();
+        storage.add_user(alice.clone());
+        storage.deposit(&alice, Balance(100)).unwrap();
+
+        fs::write(&file, "Bob,10\nCarol,0\n").unwrap();
+        assert_eq!(storage.import_accounts(&file).unwrap(), 2);
+        assert_eq!(storage.get_balance(&"Bob".to_string()), Some(Balance(10)));
+
+        // Alice уже есть: её баланс не растёт, а Dave из той же пачки не открывается
+        fs::write(&file, "Dave,5\nAlice,50\n").unwrap();
+        let err = storage.import_accounts(&file).unwrap_err();
+        fs::remove_file(&file).unwrap();
+        assert!(matches!(
+            err,
+            BankError::File { line: Some(2), ref source, .. }
+                if matches!(**source, BankError::UserExists(_))
+        ));
+        assert_eq!(storage.get_balance(&alice), Some(Balance(100)));
+        assert_eq!(storage.get_balance(&"Dave".to_string()), None);
+    }
+
+    #[test]
+    fn test_holds_survive_restart() {
+        let file = std::env::temp_dir()
+            .join("bank_holds_test.csv")
+            .to_string_lossy()
+            .into_owned();
+        let alice = "Alice".to_string();
+        let bob = "Bob".to_string();
+        let mut storage = Storage::create(&file, None).unwrap();
+        storage.add_user(alice.clone());
+        storage.add_user(bob.clone());
+        storage.deposit(&alice, Balance(100)).unwrap();
+        storage.flush(&file).unwrap();
+
+        let first = storage
+            .place_hold(&alice, Balance(30), Timestamp::new(100))
+            .unwrap();
+        assert!(storage.is_dirty());
+        storage.flush(&file).unwrap();
+        let second = storage
+            .place_hold(&alice, Balance(20), Timestamp::new(100))
+            .unwrap();
+        storage.release_hold(second);
+        assert!(storage.is_dirty());
+        storage.flush(&file).unwrap();
+
+        let mut loaded = Storage::load_data(&file).unwrap();
+        assert_eq!(loaded.holds(&alice).len(), 1);
+        assert_eq!(loaded.get_available_balance(&alice), Some(Balance(70)));
+        // Номера холдов не повторяются после перезапуска
+        let third = loaded
+            .place_hold(&alice, Balance(1), Timestamp::new(100))
+            .unwrap();
+        assert_eq!(third, second + 1);
+
+        loaded.flush(&file).unwrap();
+        loaded.capture_hold(first, Balance(30), Some(&bob)).unwrap();
+        assert!(loaded.is_dirty());
+        loaded.flush(&file).unwrap();
+        let loaded = Storage::load_data(&file).unwrap();
+        assert_eq!(loaded.holds(&alice).len(), 1);
+        assert_eq!(loaded.get_balance(&bob), Some(Balance(30)));
+
+        for path in [
+            &file,
+            &history_path(&file),
+            &periods_path(&file),
+            &holds_path(&file),
+        ] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_flush_appends_to_history() {
+        let file = std::env::temp_dir()
+            .join("bank_append_test.csv")
+            .to_string_lossy()
+            .into_owned();
+        let alice = "Alice".to_string();
+        let mut storage = Storage::create(&file, None).unwrap();
+        storage.add_user(alice.clone());
+        storage.deposit(&alice, Balance(100)).unwrap();
+        storage.flush(&file).unwrap();
+
+        // Операция не трогает периоды и холды: их файлы не переписываются
+        fs::remove_file(periods_path(&file)).unwrap();
+        storage.withdraw(&alice, Balance(10)).unwrap();
+        storage.flush(&file).unwrap();
+        assert!(!Path::new(&periods_path(&file)).exists());
+        let journal = fs::read_to_string(history_path(&file)).unwrap();
+        assert_eq!(journal.lines().count(), 2);
+
+        // Откат после записи: на диске осталась откатанная операция,
+        // поэтому следующая запись переписывает всё
+        let savepoint = storage.savepoint();
+        storage.withdraw(&alice, Balance(20)).unwrap();
+        storage.flush(&file).unwrap();
+        storage.rollback(savepoint);
+        assert!(storage.is_dirty());
+        storage.deposit(&alice, Balance(1)).unwrap();
+        storage.flush(&file).unwrap();
+
+        let loaded = Storage::load_data(&file).unwrap();
+        assert_eq!(loaded.get_balance(&alice), Some(Balance(91)));
+        assert_eq!(loaded.history().records(), storage.history().records());
+        assert_eq!(loaded.closed_until(), None);
+        for path in [
+            &file,
+            &history_path(&file),
+            &periods_path(&file),
+            &holds_path(&file),
+        ] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_write_policy_and_journal() {
+        let file = std::env::temp_dir()
+            .join("bank_flush_test.csv")
+            .to_string_lossy()
+            .into_owned();
+        let alice = "Alice".to_string();
+        let bob = "Bob".to_string();
+        let start = Instant::now();
+
+        let mut storage = Storage::create(&file, None).unwrap();
+        storage.set_write_policy(WritePolicy {
+            every_changes: Some(3),
+            interval: None,
+            journal: true,
+        });
+        // Новые счета в журнал не попадают, поэтому пишутся сразу
+        storage.add_user(alice.clone());
+        storage.add_user(bob.clone());
+        assert!(storage.flush_if_due(&file, start).unwrap());
+        assert!(!storage.is_dirty());
+
+        // Операция только дописана в журнал, снимок балансов прежний
+        storage.deposit(&alice, Balance(100)).unwrap();
+        assert!(!storage.flush_if_due(&file, start).unwrap());
+        assert_eq!(storage.unsaved_changes(), 1);
+        assert!(fs::read_to_string(&file).unwrap().contains("Alice,0"));
+
+        // После сбоя баланс восстанавливается по журналу
+        let loaded = Storage::load_data(&file).unwrap();
+        assert_eq!(loaded.get_balance(&alice), Some(Balance(100)));
+        assert_eq!(loaded.ledger_mismatches(), vec![]);
+
+        storage.transfer(&alice, &bob, Balance(30)).unwrap();
+        assert!(!storage.flush_if_due(&file, start).unwrap());
+        storage.withdraw(&bob, Balance(5)).unwrap();
+        assert!(storage.flush_if_due(&file, start).unwrap());
+        let loaded = Storage::load_data(&file).unwrap();
+        assert_eq!(loaded.get_all().len(), 2);
+        assert_eq!(loaded.get_balance(&bob), Some(Balance(25)));
+        assert_eq!(loaded.history().records().len(), 3);
+
+        // Без порога по числу изменений пишется по времени или при выходе
+        storage.set_write_policy(WritePolicy {
+            every_changes: None,
+            interval: Some(Duration::from_secs(10)),
+            journal: false,
+        });
+        storage.deposit(&bob, Balance(1)).unwrap();
+        assert!(!storage.flush_if_due(&file, start).unwrap());
+        let later = start + Duration::from_secs(11);
+        assert!(storage.flush_if_due(&file, later).unwrap());
+        storage.deposit(&bob, Balance(1)).unwrap();
+        assert!(!storage.flush_if_due(&file, later).unwrap());
+        assert!(storage.flush(&file).unwrap());
+        assert!(!storage.flush(&file).unwrap());
+        let loaded = Storage::load_data(&file).unwrap();
+        assert_eq!(loaded.get_balance(&bob), Some(Balance(27)));
+
+        for path in [
+            &file,
+            &history_path(&file),
+            &periods_path(&file),
+            &holds_path(&file),
+        ] {
+            fs::remove_file(path).unwrap();
+        }
+    }
 }
@@ -0,0 +1,231 @@
+use super::errors::BankError;
+use std::fmt::Display;
+use std::ops::{Add, Sub};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const SECS_PER_MINUTE: i64 = 60;
+pub const SECS_PER_HOUR: i64 = 60 * SECS_PER_MINUTE;
+pub const SECS_PER_DAY: i64 = 24 * SECS_PER_HOUR;
+pub const SECS_PER_WEEK: i64 = 7 * SECS_PER_DAY;
+
+/// Момент времени в секундах от начала эпохи Unix (UTC)
+#[derive(Copy, Clone, Default, Eq, PartialEq, Debug, PartialOrd, Ord, Hash)]
+pub struct Timestamp(i64);
+
+impl Timestamp {
+    pub fn new(secs: i64) -> Self {
+        Self(secs)
+    }
+
+    /// Текущее системное время. Библиотека сама его не вызывает,
+    /// время всегда передаётся снаружи, чтобы его можно было подменять в тестах
+    pub fn now() -> Self {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default();
+        Self(secs)
+    }
+
+    pub fn value(&self) -> i64 {
+        self.0
+    }
+
+    pub fn date(&self) -> Date {
+        Date::from_days(self.0.div_euclid(SECS_PER_DAY))
+    }
+
+    /// Секунды, прошедшие с начала суток
+    pub fn time_of_day(&self) -> i64 {
+        self.0.rem_euclid(SECS_PER_DAY)
+    }
+}
+
+impl Add<i64> for Timestamp {
+    type Output = Timestamp;
+
+    fn add(self, rhs: i64) -> Self::Output {
+        Timestamp(self.0 + rhs)
+    }
+}
+
+impl Sub<i64> for Timestamp {
+    type Output = Timestamp;
+
+    fn sub(self, rhs: i64) -> Self::Output {
+        Timestamp(self.0 - rhs)
+    }
+}
+
+impl Display for Timestamp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for Timestamp {
+    type Err = BankError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Timestamp(s.trim().parse()?))
+    }
+}
+
+/// Календарная дата (григорианский календарь)
+#[derive(Copy, Clone, Eq, PartialEq, Debug, PartialOrd, Ord, Hash)]
+pub struct Date {
+    year: i32,
+    month: u32,
+    day: u32,
+}
+
+impl Date {
+    /// Возвращает None, если такой даты не существует
+    pub fn new(year: i32, month: u32, day: u32) -> Option<Self> {
+        if (1..=12).contains(&month) && day >= 1 && day <= days_in_month(year, month) {
+            Some(Self { year, month, day })
+        } else {
+            None
+        }
+    }
+
+    pub fn year(&self) -> i32 {
+        self.year
+    }
+
+    pub fn month(&self) -> u32 {
+        self.month
+    }
+
+    pub fn day(&self) -> u32 {
+        self.day
+    }
+
+    /// Начало суток этой даты
+    pub fn start(&self) -> Timestamp {
+        Timestamp(self.to_days() * SECS_PER_DAY)
+    }
+
+    pub fn add_days(&self, days: i64) -> Date {
+        Date::from_days(self.to_days() + days)
+    }
+
+    /// Сдвигает дату на `months` месяцев, ставя день `day`.
+    /// Если в месяце меньше дней, берётся последний день месяца
+    pub fn with_month_offset(&self, months: i64, day: u32) -> Date {
+        let total = self.year as i64 * 12 + (self.month as i64 - 1) + months;
+        let year = total.div_euclid(12) as i32;
+        let month = total.rem_euclid(12) as u32 + 1;
+        let day = day.clamp(1, days_in_month(year, month));
+        Date { year, month, day }
+    }
+
+    /// День недели: 0 - понедельник, 6 - воскресенье
+    pub fn weekday(&self) -> u32 {
+        // 1970-01-01 был четвергом
+        (self.to_days() + 3).rem_euclid(7) as u32
+    }
+
+    /// Количество дней от 1970-01-01
+    pub fn to_days(&self) -> i64 {
+        let y = if self.month <= 2 {
+            self.year - 1
+        } else {
+            self.year
+        } as i64;
+        let era = y.div_euclid(400);
+        let yoe = y - era * 400;
+        let m = self.month as i64;
+        let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + self.day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146097 + doe - 719468
+    }
+
+    pub fn from_days(days: i64) -> Date {
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = (yoe + era * 400 + if month <= 2 { 1 } else { 0 }) as i32;
+        Date { year, month, day }
+    }
+}
+
+impl Display for Date {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl FromStr for Date {
+    type Err = BankError;
+
+    /// Разбирает дату в формате YYYY-MM-DD
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.trim().split('-').collect();
+        if parts.len() != 3 {
+            return Err(BankError::InvalidFormat(format!("date: {s}")));
+        }
+        Date::new(parts[0].parse()?, parts[1].parse()?, parts[2].parse()?)
+            .ok_or_else(|| BankError::InvalidFormat(format!("date: {s}")))
+    }
+}
+
+pub fn is_leap_year(year: i32) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+pub fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_days_roundtrip() {
+        assert_eq!(Date::from_days(0), Date::new(1970, 1, 1).unwrap());
+        assert_eq!(Date::new(2000, 3, 1).unwrap().to_days(), 11017);
+        for days in [-1000, -1, 0, 59, 10_000, 20_000, 100_000] {
+            assert_eq!(Date::from_days(days).to_days(), days);
+        }
+    }
+
+    #[test]
+    fn test_month_offset_clamps_day() {
+        let jan = Date::new(2024, 1, 31).unwrap();
+        assert_eq!(
+            jan.with_month_offset(1, 31),
+            Date::new(2024, 2, 29).unwrap()
+        );
+        assert_eq!(
+            jan.with_month_offset(13, 31),
+            Date::new(2025, 2, 28).unwrap()
+        );
+        assert_eq!(
+            jan.with_month_offset(-1, 15),
+            Date::new(2023, 12, 15).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_and_weekday() {
+        let date: Date = "2024-05-20".parse().unwrap();
+        assert_eq!(date.to_string(), "2024-05-20");
+        assert_eq!(date.weekday(), 0);
+        assert!("2023-02-29".parse::<Date>().is_err());
+        assert_eq!((date.start() + 3600).date(), date);
+    }
+}
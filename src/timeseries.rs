@@ -0,0 +1,307 @@
+use super::errors::BankError;
+use super::history::{History, Record, RecordId};
+use super::storage::{Balance, Name, Storage};
+use super::time::{Date, Timestamp};
+use super::transaction::TxKind;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// Шаг временного ряда
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Granularity {
+    Day,
+    /// Недели начинаются с понедельника
+    Week,
+    Month,
+}
+
+impl Granularity {
+    /// Первый день периода, в который попадает дата
+    pub fn period_start(&self, date: Date) -> Date {
+        match self {
+            Granularity::Day => date,
+            Granularity::Week => date.add_days(-(date.weekday() as i64)),
+            Granularity::Month => date.with_month_offset(0, 1),
+        }
+    }
+}
+
+impl Display for Granularity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Granularity::Day => write!(f, "day"),
+            Granularity::Week => write!(f, "week"),
+            Granularity::Month => write!(f, "month"),
+        }
+    }
+}
+
+impl FromStr for Granularity {
+    type Err = BankError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "day" => Ok(Granularity::Day),
+            "week" => Ok(Granularity::Week),
+            "month" => Ok(Granularity::Month),
+            _ => Err(BankError::InvalidFormat(format!(
+                "unknown granularity: {s}"
+            ))),
+        }
+    }
+}
+
+/// Приток и отток денег за период. `account` пуст для банка в целом
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FlowRow {
+    pub period: Date,
+    pub account: Option<Name>,
+    pub inflow: Balance,
+    pub outflow: Balance,
+}
+
+impl FlowRow {
+    pub fn net(&self) -> Balance {
+        Balance::new(self.inflow.value() - self.outflow.value())
+    }
+}
+
+/// Приток и отток по операции для счёта или, если `account` пуст, для денег
+/// всех клиентов. Переводы между клиентами банк в целом не затрагивают
+fn flow_of(tx: &TxKind, account: Option<&Name>) -> (Balance, Balance) {
+    let amount = tx.amount();
+    let zero = Balance::default();
+    let (inflow, outflow) = match (tx, account) {
+        (TxKind::Deposit(tx), Some(a)) => (tx.account() == a, false),
+        (TxKind::Withdraw(tx), Some(a)) => (false, tx.account() == a),
+        (TxKind::Transfer(tx), Some(a)) => (tx.to() == a, tx.from() == a),
+        (TxKind::Fee(tx), Some(a)) => (false, tx.account() == a),
+        (TxKind::Interest(tx), Some(a)) => (tx.account() == a, false),
+        (TxKind::Deposit(_) | TxKind::Interest(_), None) => (true, false),
+        (TxKind::Withdraw(_) | TxKind::Fee(_), None) => (false, true),
+        (TxKind::Transfer(_), None) => (false, false),
+    };
+    (
+        if inflow { amount } else { zero },
+        if outflow { amount } else { zero },
+    )
+}
+
+/// Изменение баланса счёта от операции
+fn delta_of(record: &Record, account: &Name) -> i64 {
+    let (inflow, outflow) = flow_of(record.tx(), Some(account));
+    inflow.value() - outflow.value()
+}
+
+/// Обороты по периодам для счёта или, если `account` пуст, для банка в целом.
+/// Периоды без операций не выводятся
+pub fn flows(history: &History, granularity: Granularity, account: Option<&Name>) -> Vec<FlowRow> {
+    let mut periods: BTreeMap<Date, (Balance, Balance)> = BTreeMap::new();
+    for record in history.records() {
+        if account.is_some_and(|a| !record.involves(a)) {
+            continue;
+        }
+        let (inflow, outflow) = flow_of(record.tx(), account);
+        let period = periods
+            .entry(granularity.period_start(record.time().date()))
+            .or_default();
+        period.0 += inflow;
+        period.1 += outflow;
+    }
+    periods
+        .into_iter()
+        .map(|(period, (inflow, outflow))| FlowRow {
+            period,
+            account: account.cloned(),
+            inflow,
+            outflow,
+        })
+        .collect()
+}
+
+/// Обороты по периодам для каждого счёта из журнала, по имени и периоду
+pub fn flows_by_account(history: &History, granularity: Granularity) -> Vec<FlowRow> {
+    let mut names: Vec<&Name> = history
+        .records()
+        .iter()
+        .flat_map(|r| r.tx().accounts())
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+        .into_iter()
+        .flat_map(|name| flows(history, granularity, Some(name)))
+        .collect()
+}
+
+/// Остаток до первой операции в журнале. Восстанавливается от текущего,
+/// поэтому учитываются и входящие остатки, загруженные из файла
+fn opening_balance(storage: &Storage, account: &Name) -> i64 {
+    let current = storage.get_balance(account).unwrap_or_default().value();
+    current
+        - storage
+            .history()
+            .for_account(account)
+            .map(|r| delta_of(r, account))
+            .sum::<i64>()
+}
+
+/// Баланс счёта после каждой его операции
+pub fn running_balances(storage: &Storage, account: &Name) -> Vec<(Timestamp, RecordId, Balance)> {
+    let mut balance = opening_balance(storage, account);
+    storage
+        .history()
+        .for_account(account)
+        .map(|record| {
+            balance += delta_of(record, account);
+            (record.time(), record.id(), Balance::new(balance))
+        })
+        .collect()
+}
+
+/// Средний остаток на конец дня за даты с `from` по `to` включительно
+pub fn average_daily_balance(storage: &Storage, account: &Name, from: Date, to: Date) -> f64 {
+    let days = to.to_days() - from.to_days() + 1;
+    if days <= 0 {
+        return 0.0;
+    }
+    let balances = running_balances(storage, account);
+    let mut total = 0i64;
+    let mut current = opening_balance(storage, account);
+    let mut next = balances.iter().peekable();
+    for day in 0..days {
+        let end = from.add_days(day + 1).start();
+        while let Some((time, _, balance)) = next.peek() {
+            if *time >= end {
+                break;
+            }
+            current = balance.value();
+            next.next();
+        }
+        total += current;
+    }
+    total as f64 / days as f64
+}
+
+/// Средний дневной остаток по всем счетам, по имени
+pub fn average_daily_balances(storage: &Storage, from: Date, to: Date) -> Vec<(Name, f64)> {
+    let mut all: Vec<(Name, f64)> = storage
+        .names()
+        .map(|name| (name.clone(), average_daily_balance(storage, name, from, to)))
+        .collect();
+    all.sort_by(|(lhs, _), (rhs, _)| lhs.cmp(rhs));
+    all
+}
+
+/// CSV с заголовком "period,account,inflow,outflow,net". Для банка в целом
+/// счёт пуст
+pub fn flows_to_csv(rows: &[FlowRow]) -> String {
+    let mut csv = String::from("period,account,inflow,outflow,net\n");
+    for row in rows {
+        csv.push_str(&format!(
+            "{},{},{},{},{}\n",
+            row.period,
+            row.account.as_deref().unwrap_or(""),
+            row.inflow,
+            row.outflow,
+            row.net()
+        ));
+    }
+    csv
+}
+
+/// CSV с заголовком "time,date,record,balance"
+pub fn running_balances_to_csv(rows: &[(Timestamp, RecordId, Balance)]) -> String {
+    let mut csv = String::from("time,date,record,balance\n");
+    for (time, id, balance) in rows {
+        csv.push_str(&format!("{},{},{},{}\n", time, time.date(), id, balance));
+    }
+    csv
+}
+
+/// CSV с заголовком "account,average_daily_balance"
+pub fn averages_to_csv(rows: &[(Name, f64)]) -> String {
+    let mut csv = String::from("account,average_daily_balance\n");
+    for (name, average) in rows {
+        csv.push_str(&format!("{name},{average:.2}\n"));
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::bank;
+    use crate::transaction::{Deposit, Transfer, Withdraw};
+
+    fn day(date: &str) -> Timestamp {
+        date.parse::<Date>().unwrap().start() + 3600
+    }
+
+    fn storage() -> Storage {
+        let mut storage = bank(&[("Alice", 0), ("Bob", 0)]);
+        let ops: Vec<(&str, TxKind)> = vec![
+            (
+                "2024-04-29",
+                Deposit::new("Alice", Balance::new(100)).into(),
+            ),
+            (
+                "2024-04-30",
+                Transfer::new("Alice", "Bob", Balance::new(30)).into(),
+            ),
+            ("2024-05-01", Withdraw::new("Bob", Balance::new(10)).into()),
+            ("2024-05-03", Deposit::new("Bob", Balance::new(50)).into()),
+        ];
+        for (date, tx) in ops {
+            storage.set_time(day(date));
+            crate::transaction::Transaction::apply(&tx, &mut storage).unwrap();
+        }
+        storage
+    }
+
+    #[test]
+    fn test_flows() {
+        let storage = storage();
+        let bank = flows(storage.history(), Granularity::Month, None);
+        assert_eq!(bank.len(), 2);
+        assert_eq!(bank[0].inflow, Balance::new(100));
+        assert_eq!(bank[1].net(), Balance::new(40));
+
+        let alice = "Alice".to_string();
+        let weekly = flows(storage.history(), Granularity::Week, Some(&alice));
+        assert_eq!(weekly.len(), 1);
+        assert_eq!(weekly[0].period, "2024-04-29".parse().unwrap());
+        assert_eq!(weekly[0].net(), Balance::new(70));
+
+        let all = flows_by_account(storage.history(), Granularity::Day);
+        assert_eq!(all.len(), 5);
+        assert!(flows_to_csv(&all).contains("2024-04-30,Bob,30,0,30\n"));
+    }
+
+    #[test]
+    fn test_running_and_average_balance() {
+        let storage = storage();
+        let bob = "Bob".to_string();
+        let running: Vec<i64> = running_balances(&storage, &bob)
+            .iter()
+            .map(|(_, _, b)| b.value())
+            .collect();
+        assert_eq!(running, vec![30, 20, 70]);
+
+        // 04-29: 0, 04-30: 30, 05-01: 20, 05-02: 20
+        let average = average_daily_balance(
+            &storage,
+            &bob,
+            "2024-04-29".parse().unwrap(),
+            "2024-05-02".parse().unwrap(),
+        );
+        assert_eq!(average, 17.5);
+        let csv = averages_to_csv(&average_daily_balances(
+            &storage,
+            "2024-04-29".parse().unwrap(),
+            "2024-05-02".parse().unwrap(),
+        ));
+        assert!(csv.contains("Bob,17.50\n"));
+    }
+}
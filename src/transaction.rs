@@ -1,6 +1,9 @@
-use super::storage::{Storage, Balance};
-use super::errors::BankError;
+use super::errors::{BankError, FileContext};
+use super::storage::{Balance, HoldId, Name, Storage};
+use std::fmt::Display;
+use std::fs;
 use std::ops::Add;
+use std::str::FromStr;
 
 pub trait Transaction {
     fn apply(&self, accounts: &mut Storage) -> Result<(), BankError>;
@@ -17,7 +20,7 @@ impl<T1, T2, Rhs: Transaction> Add<Rhs> for TxCombinator<T1, T2> {
     fn add(self, rhs: Rhs) -> Self::Output {
         TxCombinator { t1: self, t2: rhs }
     }
-} 
+}
 
 impl<T1: Transaction, T2: Transaction> Transaction for TxCombinator<T1, T2> {
     fn apply(&self, accounts: &mut Storage) -> Result<(), BankError> {
@@ -27,6 +30,7 @@ impl<T1: Transaction, T2: Transaction> Transaction for TxCombinator<T1, T2> {
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Deposit {
     account: String,
     amount: Balance,
@@ -34,11 +38,19 @@ pub struct Deposit {
 
 impl Deposit {
     pub fn new(account: &str, amount: Balance) -> Self {
-        Self{
+        Self {
             account: account.to_owned(),
             amount,
         }
     }
+
+    pub fn account(&self) -> &Name {
+        &self.account
+    }
+
+    pub fn amount(&self) -> Balance {
+        self.amount
+    }
 }
 
 impl Transaction for Deposit {
@@ -56,6 +68,7 @@ impl<T: Transaction> Add<T> for Deposit {
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Withdraw {
     account: String,
     amount: Balance,
@@ -63,11 +76,19 @@ pub struct Withdraw {
 
 impl Withdraw {
     pub fn new(account: &str, amount: Balance) -> Self {
-        Self{
+        Self {
             account: account.to_owned(),
             amount,
         }
     }
+
+    pub fn account(&self) -> &Name {
+        &self.account
+    }
+
+    pub fn amount(&self) -> Balance {
+        self.amount
+    }
 }
 
 impl Transaction for Withdraw {
@@ -85,6 +106,7 @@ impl<T: Transaction> Add<T> for Withdraw {
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Transfer {
     from: String,
     to: String,
@@ -99,13 +121,23 @@ impl Transfer {
             amount,
         }
     }
+
+    pub fn from(&self) -> &Name {
+        &self.from
+    }
+
+    pub fn to(&self) -> &Name {
+        &self.to
+    }
+
+    pub fn amount(&self) -> Balance {
+        self.amount
+    }
 }
 
 impl Transaction for Transfer {
     fn apply(&self, storage: &mut Storage) -> Result<(), BankError> {
-        storage.withdraw(&self.from, self.amount)?;
-        storage.deposit(&self.to, self.amount)?;
-        Ok(())
+        storage.transfer(&self.from, &self.to, self.amount)
     }
 }
 
@@ -115,4 +147,495 @@ impl<T: Transaction> Add<T> for Transfer {
     fn add(self, rhs: T) -> Self::Output {
         TxCombinator { t1: self, t2: rhs }
     }
-} 
\ No newline at end of file
+}
+
+/// Комиссия банка: списывается со счёта клиента в доход банка
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fee {
+    account: String,
+    amount: Balance,
+}
+
+impl Fee {
+    pub fn new(account: &str, amount: Balance) -> Self {
+        Self {
+            account: account.to_owned(),
+            amount,
+        }
+    }
+
+    pub fn account(&self) -> &Name {
+        &self.account
+    }
+
+    pub fn amount(&self) -> Balance {
+        self.amount
+    }
+}
+
+impl Transaction for Fee {
+    fn apply(&self, storage: &mut Storage) -> Result<(), BankError> {
+        storage.charge_fee(&self.account, self.amount)
+    }
+}
+
+impl<T: Transaction> Add<T> for Fee {
+    type Output = TxCombinator<Fee, T>;
+
+    fn add(self, rhs: T) -> Self::Output {
+        TxCombinator { t1: self, t2: rhs }
+    }
+}
+
+/// Начисление процентов на счёт клиента за счёт расходов банка
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Interest {
+    account: String,
+    amount: Balance,
+}
+
+impl Interest {
+    pub fn new(account: &str, amount: Balance) -> Self {
+        Self {
+            account: account.to_owned(),
+            amount,
+        }
+    }
+
+    pub fn account(&self) -> &Name {
+        &self.account
+    }
+
+    pub fn amount(&self) -> Balance {
+        self.amount
+    }
+}
+
+impl Transaction for Interest {
+    fn apply(&self, storage: &mut Storage) -> Result<(), BankError> {
+        storage.pay_interest(&self.account, self.amount)
+    }
+}
+
+impl<T: Transaction> Add<T> for Interest {
+    type Output = TxCombinator<Interest, T>;
+
+    fn add(self, rhs: T) -> Self::Output {
+        TxCombinator { t1: self, t2: rhs }
+    }
+}
+
+/// Списание по холду: без получателя работает как Withdraw, с получателем - как Transfer
+pub struct Capture {
+    hold: HoldId,
+    to: Option<Name>,
+    amount: Balance,
+}
+
+impl Capture {
+    pub fn withdraw(hold: HoldId, amount: Balance) -> Self {
+        Self {
+            hold,
+            to: None,
+            amount,
+        }
+    }
+
+    pub fn transfer(hold: HoldId, to: &str, amount: Balance) -> Self {
+        Self {
+            hold,
+            to: Some(to.to_owned()),
+            amount,
+        }
+    }
+}
+
+impl Transaction for Capture {
+    fn apply(&self, storage: &mut Storage) -> Result<(), BankError> {
+        storage.capture_hold(self.hold, self.amount, self.to.as_ref())
+    }
+}
+
+impl<T: Transaction> Add<T> for Capture {
+    type Output = TxCombinator<Capture, T>;
+
+    fn add(self, rhs: T) -> Self::Output {
+        TxCombinator { t1: self, t2: rhs }
+    }
+}
+
+/// Выполняет транзакцию целиком или не выполняет совсем:
+/// при ошибке состояние хранилища откатывается к исходному
+fn apply_atomic<T: Transaction + ?Sized>(tx: &T, storage: &mut Storage) -> Result<(), BankError> {
+    storage.atomically(|storage| tx.apply(storage))
+}
+
+/// Условие, которое проверяется перед выполнением транзакции
+pub trait Predicate {
+    fn check(&self, storage: &Storage) -> Result<(), BankError>;
+}
+
+impl<F: Fn(&Storage) -> bool> Predicate for F {
+    fn check(&self, storage: &Storage) -> Result<(), BankError> {
+        if self(storage) {
+            Ok(())
+        } else {
+            Err(BankError::ConditionNotMet("custom predicate".to_owned()))
+        }
+    }
+}
+
+/// Баланс счёта не меньше заданной суммы
+pub struct BalanceAtLeast {
+    account: Name,
+    amount: Balance,
+}
+
+impl BalanceAtLeast {
+    pub fn new(account: &str, amount: Balance) -> Self {
+        Self {
+            account: account.to_owned(),
+            amount,
+        }
+    }
+}
+
+impl Predicate for BalanceAtLeast {
+    fn check(&self, storage: &Storage) -> Result<(), BankError> {
+        let balance = storage
+            .get_balance(&self.account)
+            .ok_or_else(|| BankError::UserNotFound(self.account.clone()))?;
+        if balance >= self.amount {
+            Ok(())
+        } else {
+            Err(BankError::ConditionNotMet(format!(
+                "balance of {} is {}, expected at least {}",
+                self.account, balance, self.amount
+            )))
+        }
+    }
+}
+
+/// Выполняет транзакцию, только если выполнено условие
+pub struct OnlyIf<P, T> {
+    predicate: P,
+    tx: T,
+}
+
+impl<P: Predicate, T: Transaction> OnlyIf<P, T> {
+    pub fn new(predicate: P, tx: T) -> Self {
+        Self { predicate, tx }
+    }
+}
+
+impl<P: Predicate, T: Transaction> Transaction for OnlyIf<P, T> {
+    fn apply(&self, storage: &mut Storage) -> Result<(), BankError> {
+        self.predicate.check(storage)?;
+        apply_atomic(&self.tx, storage)
+    }
+}
+
+impl<P, T1, Rhs: Transaction> Add<Rhs> for OnlyIf<P, T1> {
+    type Output = TxCombinator<OnlyIf<P, T1>, Rhs>;
+
+    fn add(self, rhs: Rhs) -> Self::Output {
+        TxCombinator { t1: self, t2: rhs }
+    }
+}
+
+/// Пробует выполнить первую транзакцию, а если она не удалась - вторую.
+/// Неудачная первая транзакция не оставляет следов в хранилище
+pub struct Fallback<T1, T2> {
+    primary: T1,
+    fallback: T2,
+}
+
+impl<T1: Transaction, T2: Transaction> Fallback<T1, T2> {
+    pub fn new(primary: T1, fallback: T2) -> Self {
+        Self { primary, fallback }
+    }
+}
+
+impl<T1: Transaction, T2: Transaction> Transaction for Fallback<T1, T2> {
+    fn apply(&self, storage: &mut Storage) -> Result<(), BankError> {
+        apply_atomic(&self.primary, storage).or_else(|_| apply_atomic(&self.fallback, storage))
+    }
+}
+
+impl<T1, T2, Rhs: Transaction> Add<Rhs> for Fallback<T1, T2> {
+    type Output = TxCombinator<Fallback<T1, T2>, Rhs>;
+
+    fn add(self, rhs: Rhs) -> Self::Output {
+        TxCombinator { t1: self, t2: rhs }
+    }
+}
+
+/// Итог выполнения пакета: индексы успешных транзакций и ошибки неудачных
+#[derive(Debug, Default)]
+pub struct BatchReport {
+    pub applied: Vec<usize>,
+    pub failed: Vec<(usize, BankError)>,
+}
+
+/// Пакет транзакций, из которого выполняются все, какие получится.
+/// Каждая транзакция применяется атомарно и независимо от остальных
+#[derive(Default)]
+pub struct BestEffort {
+    txs: Vec<Box<dyn Transaction>>,
+}
+
+impl BestEffort {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push<T: Transaction + 'static>(&mut self, tx: T) {
+        self.txs.push(Box::new(tx));
+    }
+
+    pub fn apply_with_report(&self, storage: &mut Storage) -> BatchReport {
+        let mut report = BatchReport::default();
+        for (i, tx) in self.txs.iter().enumerate() {
+            match apply_atomic(tx.as_ref(), storage) {
+                Ok(()) => report.applied.push(i),
+                Err(e) => report.failed.push((i, e)),
+            }
+        }
+        report
+    }
+}
+
+/// Ошибка - только если не выполнилась ни одна транзакция пакета: тогда
+/// возвращается ошибка первой. Какие именно не выполнились, видно в `apply_with_report`
+impl Transaction for BestEffort {
+    fn apply(&self, storage: &mut Storage) -> Result<(), BankError> {
+        let report = self.apply_with_report(storage);
+        match report.failed.into_iter().next() {
+            Some((_, e)) if report.applied.is_empty() => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<T: Transaction> Add<T> for BestEffort {
+    type Output = TxCombinator<BestEffort, T>;
+
+    fn add(self, rhs: T) -> Self::Output {
+        TxCombinator { t1: self, t2: rhs }
+    }
+}
+
+/// Простая операция в виде данных: её можно сохранить в файл и прочитать обратно
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TxKind {
+    Deposit(Deposit),
+    Withdraw(Withdraw),
+    Transfer(Transfer),
+    Fee(Fee),
+    Interest(Interest),
+}
+
+impl TxKind {
+    /// Счета, которых касается операция
+    pub fn accounts(&self) -> Vec<&Name> {
+        match self {
+            TxKind::Deposit(tx) => vec![&tx.account],
+            TxKind::Withdraw(tx) => vec![&tx.account],
+            TxKind::Transfer(tx) => vec![&tx.from, &tx.to],
+            TxKind::Fee(tx) => vec![&tx.account],
+            TxKind::Interest(tx) => vec![&tx.account],
+        }
+    }
+
+    pub fn amount(&self) -> Balance {
+        match self {
+            TxKind::Deposit(tx) => tx.amount,
+            TxKind::Withdraw(tx) => tx.amount,
+            TxKind::Transfer(tx) => tx.amount,
+            TxKind::Fee(tx) => tx.amount,
+            TxKind::Interest(tx) => tx.amount,
+        }
+    }
+}
+
+impl Transaction for TxKind {
+    fn apply(&self, storage: &mut Storage) -> Result<(), BankError> {
+        match self {
+            TxKind::Deposit(tx) => tx.apply(storage),
+            TxKind::Withdraw(tx) => tx.apply(storage),
+            TxKind::Transfer(tx) => tx.apply(storage),
+            TxKind::Fee(tx) => tx.apply(storage),
+            TxKind::Interest(tx) => tx.apply(storage),
+        }
+    }
+}
+
+impl<T: Transaction> Add<T> for TxKind {
+    type Output = TxCombinator<TxKind, T>;
+
+    fn add(self, rhs: T) -> Self::Output {
+        TxCombinator { t1: self, t2: rhs }
+    }
+}
+
+impl From<Deposit> for TxKind {
+    fn from(value: Deposit) -> Self {
+        TxKind::Deposit(value)
+    }
+}
+
+impl From<Withdraw> for TxKind {
+    fn from(value: Withdraw) -> Self {
+        TxKind::Withdraw(value)
+    }
+}
+
+impl From<Transfer> for TxKind {
+    fn from(value: Transfer) -> Self {
+        TxKind::Transfer(value)
+    }
+}
+
+impl From<Fee> for TxKind {
+    fn from(value: Fee) -> Self {
+        TxKind::Fee(value)
+    }
+}
+
+impl From<Interest> for TxKind {
+    fn from(value: Interest) -> Self {
+        TxKind::Interest(value)
+    }
+}
+
+impl Display for TxKind {
+    /// Формат CSV: "deposit,Name,Amount", "withdraw,Name,Amount", "transfer,From,To,Amount",
+    /// "fee,Name,Amount", "interest,Name,Amount"
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TxKind::Deposit(tx) => write!(f, "deposit,{},{}", tx.account, tx.amount),
+            TxKind::Withdraw(tx) => write!(f, "withdraw,{},{}", tx.account, tx.amount),
+            TxKind::Transfer(tx) => write!(f, "transfer,{},{},{}", tx.from, tx.to, tx.amount),
+            TxKind::Fee(tx) => write!(f, "fee,{},{}", tx.account, tx.amount),
+            TxKind::Interest(tx) => write!(f, "interest,{},{}", tx.account, tx.amount),
+        }
+    }
+}
+
+impl FromStr for TxKind {
+    type Err = BankError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.trim().split(',').collect();
+        match parts.as_slice() {
+            ["deposit", name, amount] => {
+                Ok(Deposit::new(name, Balance::new(amount.parse()?)).into())
+            }
+            ["withdraw", name, amount] => {
+                Ok(Withdraw::new(name, Balance::new(amount.parse()?)).into())
+            }
+            ["transfer", from, to, amount] => {
+                Ok(Transfer::new(from, to, Balance::new(amount.parse()?)).into())
+            }
+            ["fee", name, amount] => Ok(Fee::new(name, Balance::new(amount.parse()?)).into()),
+            ["interest", name, amount] => {
+                Ok(Interest::new(name, Balance::new(amount.parse()?)).into())
+            }
+            _ => Err(BankError::InvalidFormat(format!(
+                "unknown transaction: {s}"
+            ))),
+        }
+    }
+}
+
+/// Читает файл операций: по одной на строку в формате `TxKind`, пустые строки пропускаются
+pub fn load_txs(file: &str) -> Result<Vec<TxKind>, BankError> {
+    let text = fs::read_to_string(file).in_file(file)?;
+    let mut txs = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        if !line.trim().is_empty() {
+            txs.push(line.parse().at_line(file, i)?);
+        }
+    }
+    Ok(txs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::bank;
+
+    fn balance(storage: &Storage, name: &str) -> i64 {
+        storage.get_balance(&name.to_string()).unwrap().value()
+    }
+
+    #[test]
+    fn test_only_if() {
+        let mut storage = bank(&[("Alice", 100), ("Bob", 0)]);
+        let tx = OnlyIf::new(
+            BalanceAtLeast::new("Alice", Balance::new(150)),
+            Transfer::new("Alice", "Bob", Balance::new(10)),
+        );
+        assert!(matches!(
+            tx.apply(&mut storage),
+            Err(BankError::ConditionNotMet(_))
+        ));
+        assert_eq!(balance(&storage, "Bob"), 0);
+
+        let tx = OnlyIf::new(
+            |s: &Storage| s.get_balance(&"Bob".to_string()) == Some(Balance::new(0)),
+            Transfer::new("Alice", "Bob", Balance::new(10)),
+        );
+        tx.apply(&mut storage).unwrap();
+        assert_eq!(balance(&storage, "Bob"), 10);
+    }
+
+    #[test]
+    fn test_fallback_rolls_back_primary() {
+        let mut storage = bank(&[("Alice", 100), ("Bob", 0)]);
+        // Первая часть выполнится, вторая нет - пополнение Bob должно откатиться
+        let primary =
+            Deposit::new("Bob", Balance::new(50)) + Withdraw::new("Alice", Balance::new(500));
+        let tx = Fallback::new(primary, Transfer::new("Alice", "Bob", Balance::new(20)));
+        tx.apply(&mut storage).unwrap();
+        assert_eq!(balance(&storage, "Alice"), 80);
+        assert_eq!(balance(&storage, "Bob"), 20);
+
+        let tx = Fallback::new(
+            Withdraw::new("Bob", Balance::new(500)),
+            Withdraw::new("Nobody", Balance::new(1)),
+        );
+        assert!(matches!(
+            tx.apply(&mut storage),
+            Err(BankError::UserNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_best_effort_report() {
+        let mut storage = bank(&[("Alice", 100), ("Bob", 0)]);
+        let mut batch = BestEffort::new();
+        batch.push(Transfer::new("Alice", "Bob", Balance::new(30)));
+        batch.push(Deposit::new("Carol", Balance::new(10)));
+        batch.push(Deposit::new("Bob", Balance::new(5)) + Withdraw::new("Bob", Balance::new(1000)));
+        batch.push(Withdraw::new("Alice", Balance::new(70)));
+
+        let report = batch.apply_with_report(&mut storage);
+        assert_eq!(report.applied, vec![0, 3]);
+        assert_eq!(report.failed.len(), 2);
+        assert_eq!(balance(&storage, "Alice"), 0);
+        assert_eq!(balance(&storage, "Bob"), 30);
+
+        // Часть выполнилась - успех, ничего не выполнилось - ошибка первой транзакции
+        assert!(
+            batch
+                .apply(&mut bank(&[("Alice", 100), ("Bob", 0)]))
+                .is_ok()
+        );
+        assert!(matches!(
+            batch.apply(&mut storage),
+            Err(BankError::FundsLimit { .. })
+        ));
+        assert!(BestEffort::new().apply(&mut storage).is_ok());
+    }
+}
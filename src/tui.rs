@@ -0,0 +1,658 @@
+use super::errors::BankError;
+use super::locale::{self, Lang};
+use super::repl::stty;
+use super::screening::{Outcome, ReviewQueue, Screener};
+use super::storage::{Balance, Name, Storage};
+use super::transaction::Transfer;
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::io::{self, IsTerminal, Read, Write};
+
+/// Клавиши, на которые реагирует панель
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Key {
+    Up,
+    Down,
+    Tab,
+    Enter,
+    Esc,
+    Backspace,
+    /// Ctrl-C
+    Interrupt,
+    Char(char),
+}
+
+/// Разбирает байты, прочитанные с терминала за одно нажатие
+pub fn parse_keys(bytes: &[u8]) -> Vec<Key> {
+    let mut keys = Vec::new();
+    let mut rest = bytes;
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        let key = match byte {
+            0x1b => match rest {
+                [b'[', b'A', tail @ ..] => {
+                    rest = tail;
+                    Key::Up
+                }
+                [b'[', b'B', tail @ ..] => {
+                    rest = tail;
+                    Key::Down
+                }
+                // Остальные последовательности (стрелки вбок, Delete, PageUp...)
+                // пропускаем вместе с параметрами до завершающего байта
+                [b'[', tail @ ..] => {
+                    let end = tail.iter().position(|b| (0x40..=0x7e).contains(b));
+                    rest = &tail[end.map_or(tail.len(), |i| i + 1)..];
+                    continue;
+                }
+                _ => Key::Esc,
+            },
+            b'\r' | b'\n' => Key::Enter,
+            b'\t' => Key::Tab,
+            127 | 8 => Key::Backspace,
+            3 => Key::Interrupt,
+            byte if byte < 0x20 => continue,
+            _ => {
+                // Символ UTF-8 целиком: начало уже прочитано
+                let len = match byte {
+                    0xf0.. => 4,
+                    0xe0.. => 3,
+                    0xc0.. => 2,
+                    _ => 1,
+                }
+                .min(rest.len() + 1);
+                let mut text = vec![byte];
+                text.extend_from_slice(&rest[..len - 1]);
+                rest = &rest[len - 1..];
+                match String::from_utf8(text).ok().and_then(|s| s.chars().next()) {
+                    Some(c) => Key::Char(c),
+                    None => continue,
+                }
+            }
+        };
+        keys.push(key);
+    }
+    keys
+}
+
+/// Кадр: прямоугольник символов, который целиком выводится на терминал
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    width: usize,
+    rows: Vec<Vec<char>>,
+}
+
+impl Frame {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            rows: vec![vec![' '; width]; height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Пишет текст с позиции (x, y); что не помещается - обрезается
+    pub fn put(&mut self, x: usize, y: usize, text: &str) {
+        let Some(row) = self.rows.get_mut(y) else {
+            return;
+        };
+        for (cell, c) in row.iter_mut().skip(x).zip(text.chars()) {
+            *cell = c;
+        }
+    }
+
+    /// Строки кадра без пробелов в конце
+    pub fn lines(&self) -> Vec<String> {
+        self.rows
+            .iter()
+            .map(|row| row.iter().collect::<String>().trim_end().to_owned())
+            .collect()
+    }
+}
+
+impl Display for Frame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.lines().join("\n"))
+    }
+}
+
+/// Терминал, на котором работает панель
+pub trait Backend {
+    /// Ширина и высота в символах
+    fn size(&self) -> (usize, usize);
+    fn draw(&mut self, frame: &Frame) -> io::Result<()>;
+    /// Следующая клавиша; `None` - ввод закончился
+    fn read_key(&mut self) -> io::Result<Option<Key>>;
+}
+
+/// Терминал в памяти для тестов: клавиши заданы заранее, кадры запоминаются
+pub struct MockBackend {
+    width: usize,
+    height: usize,
+    keys: VecDeque<Key>,
+    frames: Vec<Frame>,
+}
+
+impl MockBackend {
+    pub fn new(width: usize, height: usize, keys: impl IntoIterator<Item = Key>) -> Self {
+        Self {
+            width,
+            height,
+            keys: keys.into_iter().collect(),
+            frames: Vec::new(),
+        }
+    }
+
+    /// Клавиши для ввода текста
+    pub fn typed(text: &str) -> impl Iterator<Item = Key> + '_ {
+        text.chars().map(Key::Char)
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    pub fn last_frame(&self) -> Option<&Frame> {
+        self.frames.last()
+    }
+}
+
+impl Backend for MockBackend {
+    fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn draw(&mut self, frame: &Frame) -> io::Result<()> {
+        self.frames.push(frame.clone());
+        Ok(())
+    }
+
+    fn read_key(&mut self) -> io::Result<Option<Key>> {
+        Ok(self.keys.pop_front())
+    }
+}
+
+/// Настоящий терминал: отдельный экран, посимвольный ввод через `stty`.
+/// При удалении терминал возвращается в исходное состояние
+pub struct TerminalBackend {
+    saved: String,
+    pending: VecDeque<Key>,
+}
+
+impl TerminalBackend {
+    /// `None`, если stdin или stdout не терминал или нет `stty`
+    pub fn new() -> Option<Self> {
+        if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+            return None;
+        }
+        let saved = stty(&["-g"])?;
+        stty(&["-icanon", "-echo", "-isig", "min", "1"])?;
+        // Отдельный экран и скрытый курсор
+        print!("\x1b[?1049h\x1b[?25l");
+        Some(Self {
+            saved: saved.trim().to_owned(),
+            pending: VecDeque::new(),
+        })
+    }
+}
+
+impl Backend for TerminalBackend {
+    fn size(&self) -> (usize, usize) {
+        // `stty size` печатает "строки столбцы"
+        let size = stty(&["size"]).unwrap_or_default();
+        let mut numbers = size.split_whitespace().filter_map(|n| n.parse().ok());
+        match (numbers.next(), numbers.next()) {
+            (Some(height), Some(width)) => (width, height),
+            _ => (80, 24),
+        }
+    }
+
+    fn draw(&mut self, frame: &Frame) -> io::Result<()> {
+        let mut out = io::stdout().lock();
+        write!(out, "\x1b[H")?;
+        for (i, line) in frame.lines().iter().enumerate() {
+            if i > 0 {
+                write!(out, "\r\n")?;
+            }
+            write!(out, "{line}\x1b[K")?;
+        }
+        out.flush()
+    }
+
+    fn read_key(&mut self) -> io::Result<Option<Key>> {
+        while self.pending.is_empty() {
+            // Стрелка приходит одним чтением из нескольких байт
+            let mut buf = [0; 16];
+            let n = io::stdin().lock().read(&mut buf)?;
+            if n == 0 {
+                return Ok(None);
+            }
+            self.pending.extend(parse_keys(&buf[..n]));
+        }
+        Ok(self.pending.pop_front())
+    }
+}
+
+impl Drop for TerminalBackend {
+    fn drop(&mut self) {
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = io::stdout().flush();
+        stty(&[&self.saved]);
+    }
+}
+
+/// Что изменилось после нажатия клавиши
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    Continue,
+    /// Данные банка изменились, их нужно сохранить
+    Changed,
+    Quit,
+}
+
+/// Где сейчас ввод: список счетов или поле формы перевода
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Focus {
+    Accounts,
+    To,
+    Amount,
+}
+
+/// Панель оператора: счета, история выбранного счёта, перевод и сводка по банку.
+/// Переводы проходят проверку `screener`, подозрительные попадают в `queue`
+pub struct Dashboard<'a> {
+    lang: Lang,
+    currency: String,
+    storage: &'a mut Storage,
+    screener: &'a Screener,
+    queue: &'a mut ReviewQueue,
+    selected: usize,
+    focus: Focus,
+    to: String,
+    amount: String,
+    status: String,
+}
+
+impl<'a> Dashboard<'a> {
+    pub fn new(
+        lang: Lang,
+        currency: &str,
+        storage: &'a mut Storage,
+        screener: &'a Screener,
+        queue: &'a mut ReviewQueue,
+    ) -> Self {
+        Self {
+            lang,
+            currency: currency.to_owned(),
+            storage,
+            screener,
+            queue,
+            selected: 0,
+            focus: Focus::Accounts,
+            to: String::new(),
+            amount: String::new(),
+            status: String::new(),
+        }
+    }
+
+    pub fn focus(&self) -> Focus {
+        self.focus
+    }
+
+    /// Счета по имени
+    fn accounts(&self) -> Vec<(&Name, Balance)> {
+        let mut accounts: Vec<_> = self.storage.accounts().collect();
+        accounts.sort();
+        accounts
+    }
+
+    pub fn selected(&self) -> Option<&Name> {
+        let accounts = self.accounts();
+        let index = self.selected.min(accounts.len().checked_sub(1)?);
+        Some(accounts[index].0)
+    }
+
+    pub fn handle(&mut self, key: Key) -> Action {
+        if key == Key::Interrupt {
+            return Action::Quit;
+        }
+        match (self.focus, key) {
+            (Focus::Accounts, Key::Esc | Key::Char('q')) => return Action::Quit,
+            (Focus::Accounts, Key::Up) => self.selected = self.selected.saturating_sub(1),
+            (Focus::Accounts, Key::Down) => {
+                let last = self.storage.len().saturating_sub(1);
+                self.selected = (self.selected + 1).min(last);
+            }
+            (Focus::Accounts, Key::Tab | Key::Enter) => self.focus = Focus::To,
+            (Focus::To, Key::Tab) => self.focus = Focus::Amount,
+            (Focus::Amount, Key::Tab) => self.focus = Focus::Accounts,
+            (_, Key::Esc) => self.focus = Focus::Accounts,
+            (Focus::To | Focus::Amount, Key::Enter) => return self.transfer(),
+            (Focus::To, Key::Char(c)) => self.to.push(c),
+            (Focus::Amount, Key::Char(c)) => self.amount.push(c),
+            (Focus::To, Key::Backspace) => {
+                self.to.pop();
+            }
+            (Focus::Amount, Key::Backspace) => {
+                self.amount.pop();
+            }
+            _ => {}
+        }
+        Action::Continue
+    }
+
+    /// Перевод с выбранного счёта по данным формы
+    fn transfer(&mut self) -> Action {
+        let lang = self.lang;
+        let Some(from) = self.selected().cloned() else {
+            return Action::Continue;
+        };
+        let Ok(amount) = self.amount.trim().parse().map(Balance::new) else {
+            self.status = locale::text(lang, "input.amount_nan").to_owned();
+            self.focus = Focus::Amount;
+            return Action::Continue;
+        };
+        let to = self.to.trim().to_owned();
+        let tx = Transfer::new(&from, &to, amount).into();
+        match self.screener.submit(tx, self.storage, self.queue) {
+            Ok(outcome) => {
+                self.status = match outcome {
+                    Outcome::Applied => {
+                        let args: [(&str, &dyn Display); 3] =
+                            [("amount", &amount), ("from", &from), ("to", &to)];
+                        locale::format(lang, "tx.transfer_done", &args)
+                    }
+                    Outcome::Queued(id, reason) => {
+                        let reason = reason.message(lang);
+                        let args: [(&str, &dyn Display); 2] = [("id", &id), ("reason", &reason)];
+                        locale::format(lang, "review.queued", &args)
+                    }
+                };
+                self.to.clear();
+                self.amount.clear();
+                self.focus = Focus::Accounts;
+                Action::Changed
+            }
+            Err(e) => {
+                let error = e.message(lang);
+                self.status = locale::format(lang, "repl.error", &[("error", &error)]);
+                Action::Continue
+            }
+        }
+    }
+
+    /// Показывает сообщение в строке состояния
+    pub fn set_status(&mut self, status: String) {
+        self.status = status;
+    }
+
+    pub fn render(&self, frame: &mut Frame) {
+        let lang = self.lang;
+        let (width, height) = (frame.width(), frame.height());
+        if width < 40 || height < 10 {
+            frame.put(0, 0, locale::text(lang, "tui.too_small"));
+            return;
+        }
+        let accounts = self.accounts();
+        let rule = "─".repeat(width);
+
+        // Сводка по банку
+        let total = self.storage.total_balance();
+        let args: [(&str, &dyn Display); 3] = [
+            ("count", &accounts.len()),
+            ("total", &total),
+            ("currency", &self.currency),
+        ];
+        frame.put(
+            0,
+            0,
+            &format!(
+                "{} | {}",
+                locale::text(lang, "tui.title"),
+                locale::format(lang, "tui.summary", &args)
+            ),
+        );
+        let mut top = accounts.clone();
+        top.sort_by_key(|(_, balance)| std::cmp::Reverse(*balance));
+        let top: Vec<String> = top
+            .iter()
+            .take(3)
+            .map(|(name, balance)| format!("{name} {balance}"))
+            .collect();
+        frame.put(
+            0,
+            1,
+            &format!("{} {}", locale::text(lang, "tui.top"), top.join(", ")),
+        );
+        frame.put(0, 2, &rule);
+
+        // Список счетов слева, подробности справа
+        let body = 3..height - 4;
+        let list_width = (width / 3).min(30);
+        let rows = body.len();
+        let selected = self.selected.min(accounts.len().saturating_sub(1));
+        let offset = (selected + 1).saturating_sub(rows);
+        if accounts.is_empty() {
+            frame.put(0, body.start, locale::text(lang, "tui.no_accounts"));
+        }
+        for (row, (i, (name, balance))) in
+            body.clone().zip(accounts.iter().enumerate().skip(offset))
+        {
+            let marker = if i == selected { '>' } else { ' ' };
+            let amount = balance.to_string();
+            let name_width = list_width.saturating_sub(amount.len() + 3);
+            let name: String = name.chars().take(name_width).collect();
+            frame.put(0, row, &format!("{marker} {name:<name_width$} {amount}"));
+        }
+        for row in body.clone() {
+            frame.put(list_width, row, "│");
+        }
+
+        let x = list_width + 2;
+        if let Some(name) = self.selected() {
+            let balance = self.storage.get_balance(name).unwrap_or_default();
+            let args: [(&str, &dyn Display); 3] = [
+                ("name", name),
+                ("balance", &balance),
+                ("currency", &self.currency),
+            ];
+            frame.put(x, body.start, &locale::format(lang, "user.balance", &args));
+            frame.put(x, body.start + 1, locale::text(lang, "tui.history"));
+            // Последние операции сверху
+            let records = self.storage.history().for_account(name).rev();
+            let mut empty = true;
+            for (row, record) in (body.start + 2..body.end).zip(records) {
+                empty = false;
+                let line = format!("#{} {} {}", record.id(), record.time().date(), record.tx());
+                frame.put(x, row, &line);
+            }
+            if empty {
+                frame.put(x, body.start + 2, locale::text(lang, "tui.no_history"));
+            }
+        }
+
+        // Форма перевода, строка состояния и подсказка по клавишам
+        frame.put(0, height - 4, &rule);
+        let field = |focus: Focus, value: &str| {
+            if self.focus == focus {
+                format!("[{value}_]")
+            } else {
+                format!("[{value}]")
+            }
+        };
+        let from = self.selected().map_or("", String::as_str);
+        let args: [(&str, &dyn Display); 3] = [
+            ("from", &from),
+            ("to", &field(Focus::To, &self.to)),
+            ("amount", &field(Focus::Amount, &self.amount)),
+        ];
+        frame.put(0, height - 3, &locale::format(lang, "tui.form", &args));
+        frame.put(0, height - 2, &self.status);
+        frame.put(0, height - 1, locale::text(lang, "tui.keys"));
+    }
+
+    /// Рисует панель и обрабатывает клавиши, пока пользователь не выйдет.
+    /// `save` вызывается после каждого изменения данных или очереди на проверку
+    pub fn run(
+        &mut self,
+        backend: &mut impl Backend,
+        mut save: impl FnMut(&mut Storage, &ReviewQueue) -> Result<(), BankError>,
+    ) -> io::Result<()> {
+        loop {
+            let (width, height) = backend.size();
+            let mut frame = Frame::new(width, height);
+            self.render(&mut frame);
+            backend.draw(&frame)?;
+
+            let Some(key) = backend.read_key()? else {
+                return Ok(());
+            };
+            match self.handle(key) {
+                Action::Continue => {}
+                Action::Changed => {
+                    if let Err(e) = save(self.storage, self.queue) {
+                        let error = e.message(self.lang);
+                        self.status =
+                            locale::format(self.lang, "save.data_failed", &[("error", &error)]);
+                    }
+                }
+                Action::Quit => return Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::bank;
+
+    #[test]
+    fn test_parse_keys() {
+        assert_eq!(parse_keys(b"\x1b[A"), [Key::Up]);
+        assert_eq!(parse_keys(b"\x1b"), [Key::Esc]);
+        assert_eq!(
+            parse_keys("a\x1b[Cб\r\x7f\x03".as_bytes()),
+            [
+                Key::Char('a'),
+                Key::Char('б'),
+                Key::Enter,
+                Key::Backspace,
+                Key::Interrupt
+            ]
+        );
+        // Delete, PageUp, PageDown и Ctrl+стрелка не печатают символов
+        assert_eq!(
+            parse_keys(b"\x1b[3~x\x1b[5~\x1b[6~\x1b[1;5Cy"),
+            [Key::Char('x'), Key::Char('y')]
+        );
+    }
+
+    #[test]
+    fn test_transfer_from_form() {
+        let mut storage = bank(&[("Alice", 100), ("Bob", 50), ("Carol", 300)]);
+        let mut keys = vec![Key::Down, Key::Tab];
+        keys.extend(MockBackend::typed("Carol"));
+        keys.push(Key::Tab);
+        keys.extend(MockBackend::typed("20"));
+        keys.extend([Key::Enter, Key::Char('q'), Key::Char('x')]);
+        let mut backend = MockBackend::new(80, 20, keys);
+
+        let mut saves = 0;
+        let screener = Screener::standard();
+        let mut queue = ReviewQueue::new();
+        let mut dashboard = Dashboard::new(Lang::En, "USD", &mut storage, &screener, &mut queue);
+        dashboard
+            .run(&mut backend, |_, _| {
+                saves += 1;
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(saves, 1);
+        assert_eq!(
+            storage.get_balance(&"Bob".to_string()),
+            Some(Balance::new(30))
+        );
+        assert_eq!(
+            storage.get_balance(&"Carol".to_string()),
+            Some(Balance::new(320))
+        );
+
+        let screen = backend.last_frame().unwrap().to_string();
+        assert!(screen.contains("Accounts: 3, total: 450 USD"), "{screen}");
+        assert!(
+            screen.contains("Top: Carol 320, Alice 100, Bob 30"),
+            "{screen}"
+        );
+        assert!(screen.contains("> Bob"), "{screen}");
+        assert!(screen.contains("Balance of Bob = 30 USD"), "{screen}");
+        assert!(screen.contains("transfer,Bob,Carol,20"), "{screen}");
+        assert!(
+            screen.contains("Transaction: transfer 20 from Bob to Carol"),
+            "{screen}"
+        );
+        // `q` после перевода завершает работу, следующая клавиша не читается
+        assert_eq!(backend.frames().len(), 12);
+    }
+
+    #[test]
+    fn test_transfers_are_screened() {
+        let mut storage = bank(&[("Alice", 100), ("Bob", 50), ("Carol", 300)]);
+        let alice = "Alice".to_string();
+        let carol = "Carol".to_string();
+        storage.transfer(&carol, &alice, Balance::new(5)).unwrap();
+
+        // Перевод обратно в тот же день уходит на проверку, деньги не двигаются
+        let screener = Screener::standard();
+        let mut queue = ReviewQueue::new();
+        let mut dashboard = Dashboard::new(Lang::En, "USD", &mut storage, &screener, &mut queue);
+        dashboard.handle(Key::Enter);
+        for key in MockBackend::typed("Carol") {
+            dashboard.handle(key);
+        }
+        dashboard.handle(Key::Tab);
+        dashboard.handle(Key::Char('5'));
+        assert_eq!(dashboard.handle(Key::Enter), Action::Changed);
+
+        let mut frame = Frame::new(80, 20);
+        dashboard.render(&mut frame);
+        assert!(frame.to_string().contains("#1 sent for review"), "{frame}");
+        assert_eq!(queue.pending().count(), 1);
+        assert_eq!(storage.get_balance(&alice), Some(Balance::new(105)));
+    }
+
+    #[test]
+    fn test_errors_stay_on_screen() {
+        let mut storage = bank(&[("Alice", 100), ("Bob", 50), ("Carol", 300)]);
+        let screener = Screener::standard();
+        let mut queue = ReviewQueue::new();
+        let mut dashboard = Dashboard::new(Lang::En, "USD", &mut storage, &screener, &mut queue);
+        dashboard.handle(Key::Enter);
+        for key in MockBackend::typed("Bob") {
+            dashboard.handle(key);
+        }
+        dashboard.handle(Key::Tab);
+        for key in MockBackend::typed("1000") {
+            dashboard.handle(key);
+        }
+        assert_eq!(dashboard.handle(Key::Enter), Action::Continue);
+        assert_eq!(dashboard.focus(), Focus::Amount);
+
+        let mut frame = Frame::new(60, 12);
+        dashboard.render(&mut frame);
+        let screen = frame.to_string();
+        assert!(screen.contains("Not enough money on Alice"), "{screen}");
+        assert!(screen.contains("[Bob] amount [1000_]"), "{screen}");
+
+        let mut small = Frame::new(20, 5);
+        dashboard.render(&mut small);
+        assert_eq!(small.lines()[0], "Terminal too small");
+    }
+}